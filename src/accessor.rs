@@ -0,0 +1,243 @@
+use gltf::accessor::{DataType, Dimensions};
+
+use crate::error::{Error, Result};
+
+fn component_size(data_type: DataType) -> usize {
+    match data_type {
+        DataType::I8 | DataType::U8 => 1,
+        DataType::I16 | DataType::U16 => 2,
+        DataType::U32 | DataType::F32 => 4,
+    }
+}
+
+fn read_component(bytes: &[u8], data_type: DataType, normalized: bool) -> f32 {
+    match (data_type, normalized) {
+        (DataType::I8, false) => bytes[0] as i8 as f32,
+        (DataType::I8, true) => (bytes[0] as i8 as f32 / 127.0).max(-1.0),
+        (DataType::U8, false) => bytes[0] as f32,
+        (DataType::U8, true) => bytes[0] as f32 / 255.0,
+        (DataType::I16, false) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        (DataType::I16, true) => {
+            (i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32767.0).max(-1.0)
+        }
+        (DataType::U16, false) => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        (DataType::U16, true) => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+        (DataType::U32, _) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        (DataType::F32, _) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+}
+
+fn read_index(bytes: &[u8], data_type: DataType) -> u32 {
+    match data_type {
+        DataType::U8 => bytes[0] as u32,
+        DataType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+        _ => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+}
+
+fn check_index_type(data_type: DataType) -> Result<()> {
+    match data_type {
+        DataType::U8 | DataType::U16 | DataType::U32 => Ok(()),
+        data_type => Err(Error::UnsupportedFormat(format!(
+            "index component type {:?}",
+            data_type
+        ))),
+    }
+}
+
+// The bytes of a buffer view, checked against the end of its buffer
+fn view_bytes<'a>(
+    accessor: &gltf::Accessor,
+    view: &gltf::buffer::View,
+    buffers: &'a [gltf::buffer::Data],
+) -> Result<&'a [u8]> {
+    let buffer = &buffers[view.buffer().index()];
+    buffer
+        .get(view.offset()..view.offset() + view.length())
+        .ok_or(Error::InvalidAccessor {
+            accessor: accessor.index(),
+        })
+}
+
+// Byte range of `count` elements, checked against the end of their view's
+// `bytes`, so an accessor can't read into a neighbouring view
+fn checked_range(
+    accessor: &gltf::Accessor,
+    bytes: &[u8],
+    start: usize,
+    stride: usize,
+    element_size: usize,
+    count: usize,
+) -> Result<std::ops::Range<usize>> {
+    let end = match count {
+        0 => start,
+        count => start + (count - 1) * stride + element_size,
+    };
+    if end > bytes.len() {
+        return Err(Error::InvalidAccessor {
+            accessor: accessor.index(),
+        });
+    }
+    Ok(start..end)
+}
+
+// Calls `visit` with the bytes of every element in a buffer view, honoring the
+// view's byte stride and the accessor's own byte offset
+fn for_each_element(
+    accessor: &gltf::Accessor,
+    view: &gltf::buffer::View,
+    offset: usize,
+    element_size: usize,
+    count: usize,
+    buffers: &[gltf::buffer::Data],
+    mut visit: impl FnMut(usize, &[u8]),
+) -> Result<()> {
+    let bytes = view_bytes(accessor, view, buffers)?;
+    let stride = view.stride().unwrap_or(element_size);
+    checked_range(accessor, bytes, offset, stride, element_size, count)?;
+    for index in 0..count {
+        let element_start = offset + index * stride;
+        visit(index, &bytes[element_start..element_start + element_size]);
+    }
+    Ok(())
+}
+
+fn sparse_indices(
+    accessor: &gltf::Accessor,
+    sparse: &gltf::accessor::sparse::Sparse,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Vec<u32>> {
+    let indices = sparse.indices();
+    let data_type = match indices.index_type() {
+        gltf::accessor::sparse::IndexType::U8 => DataType::U8,
+        gltf::accessor::sparse::IndexType::U16 => DataType::U16,
+        gltf::accessor::sparse::IndexType::U32 => DataType::U32,
+    };
+    let mut result = Vec::with_capacity(sparse.count() as usize);
+    for_each_element(
+        accessor,
+        &indices.view(),
+        indices.offset() as usize,
+        component_size(data_type),
+        sparse.count() as usize,
+        buffers,
+        |_, bytes| result.push(read_index(bytes, data_type)),
+    )?;
+    if result
+        .iter()
+        .any(|&index| index as usize >= accessor.count())
+    {
+        return Err(Error::InvalidAccessor {
+            accessor: accessor.index(),
+        });
+    }
+    Ok(result)
+}
+
+// Calls `visit` with the tightly packed sparse value for each sparse index
+fn for_each_sparse_value(
+    accessor: &gltf::Accessor,
+    element_size: usize,
+    buffers: &[gltf::buffer::Data],
+    mut visit: impl FnMut(usize, &[u8]),
+) -> Result<()> {
+    if let Some(sparse) = accessor.sparse() {
+        let indices = sparse_indices(accessor, &sparse, buffers)?;
+        let values = sparse.values();
+        let bytes = view_bytes(accessor, &values.view(), buffers)?;
+        let start = values.offset() as usize;
+        checked_range(
+            accessor,
+            bytes,
+            start,
+            element_size,
+            element_size,
+            indices.len(),
+        )?;
+        for (value_index, &index) in indices.iter().enumerate() {
+            let value_start = start + value_index * element_size;
+            visit(
+                index as usize,
+                &bytes[value_start..value_start + element_size],
+            );
+        }
+    }
+    Ok(())
+}
+
+// Reads any vector accessor into floats, converting integer component types
+// (normalized or not, as used by KHR_mesh_quantization) and applying sparse
+// substitutions. Missing trailing components are filled from `default`.
+pub fn read_f32<const N: usize>(
+    accessor: &gltf::Accessor,
+    buffers: &[gltf::buffer::Data],
+    default: [f32; N],
+) -> Result<Vec<[f32; N]>> {
+    let data_type = accessor.data_type();
+    let normalized = accessor.normalized();
+    let size = component_size(data_type);
+    let components = match accessor.dimensions() {
+        Dimensions::Scalar => 1,
+        Dimensions::Vec2 => 2,
+        Dimensions::Vec3 => 3,
+        Dimensions::Vec4 => 4,
+        dimensions => {
+            return Err(Error::UnsupportedFormat(format!(
+                "attribute dimensions {:?}",
+                dimensions
+            )))
+        }
+    };
+    let element_size = size * components;
+
+    let read_element = |bytes: &[u8]| {
+        let mut element = default;
+        for (component, value) in element.iter_mut().enumerate().take(components) {
+            *value = read_component(&bytes[component * size..], data_type, normalized);
+        }
+        element
+    };
+
+    // Accessors without a buffer view are initialized to zero
+    let mut result = vec![[0.0; N]; accessor.count()];
+    if let Some(view) = accessor.view() {
+        for_each_element(
+            accessor,
+            &view,
+            accessor.offset(),
+            element_size,
+            accessor.count(),
+            buffers,
+            |index, bytes| result[index] = read_element(bytes),
+        )?;
+    }
+    for_each_sparse_value(accessor, element_size, buffers, |index, bytes| {
+        result[index] = read_element(bytes)
+    })?;
+
+    Ok(result)
+}
+
+pub fn read_indices(accessor: &gltf::Accessor, buffers: &[gltf::buffer::Data]) -> Result<Vec<u32>> {
+    let data_type = accessor.data_type();
+    check_index_type(data_type)?;
+    let size = component_size(data_type);
+
+    let mut result = vec![0; accessor.count()];
+    if let Some(view) = accessor.view() {
+        for_each_element(
+            accessor,
+            &view,
+            accessor.offset(),
+            size,
+            accessor.count(),
+            buffers,
+            |index, bytes| result[index] = read_index(bytes, data_type),
+        )?;
+    }
+    for_each_sparse_value(accessor, size, buffers, |index, bytes| {
+        result[index] = read_index(bytes, data_type)
+    })?;
+
+    Ok(result)
+}
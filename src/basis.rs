@@ -0,0 +1,628 @@
+use crate::compress::compress;
+use crate::decompress::{decompress, decompress_uastc};
+use crate::error::{Error, Result};
+use crate::texture::level_dimensions;
+
+// Transcodes the levels of a KTX2 texture holding Basis Universal ETC1S or
+// UASTC blocks, which leave their format to the reader. ETC1S blocks are
+// ETC1 blocks, so they're kept as ETC2 where the device samples it, and
+// UASTC blocks are closest to BC7. Other formats are decoded to RGBA8 and
+// compressed again, or uploaded as RGBA8 on devices without either.
+pub fn transcode(
+    reader: &ktx2::Reader<&[u8]>,
+    levels: &[Vec<u8>],
+    dimensions: (u32, u32),
+    features: wgpu::Features,
+) -> Result<(Vec<Vec<u8>>, wgpu::TextureFormat)> {
+    let descriptor = reader
+        .data_format_descriptors()
+        .next()
+        .ok_or_else(|| invalid_data("KTX2 texture has no data format descriptor"))?;
+    let descriptor = ktx2::BasicDataFormatDescriptor::parse(descriptor.data)?;
+    let srgb = descriptor.transfer_function == Some(ktx2::TransferFunction::SRGB);
+    let channels: Vec<u32> = descriptor
+        .sample_information()
+        .map(|sample| sample.channel_type)
+        .collect();
+
+    match descriptor.color_model {
+        Some(ktx2::ColorModel::ETC1S) => {
+            // The second slice of ETC1S images holds alpha
+            let alpha = channels.len() == 2;
+            let global_data = reader.supercompression_global_data();
+            let format = target_format(&[Target::Etc2, Target::Bc7], features, srgb, alpha);
+            let levels = etc1s_levels(global_data, levels, dimensions, alpha, format)?;
+            Ok((levels, format))
+        }
+        Some(ktx2::ColorModel::UASTC) => {
+            let alpha = matches!(channels.first(), Some(&UASTC_RGBA | &UASTC_RRRG));
+            let format = target_format(&[Target::Bc7, Target::Etc2], features, srgb, alpha);
+            let levels = levels
+                .iter()
+                .enumerate()
+                .map(|(level, bytes)| {
+                    let level_dimensions = level_dimensions(dimensions, level as u32);
+                    let texels = decompress_uastc(bytes, level_dimensions, srgb)?;
+                    recompress(texels, level_dimensions, format)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok((levels, format))
+        }
+        model => Err(Error::UnsupportedFormat(format!(
+            "KTX2 textures without a Vulkan format and with the {:?} color model",
+            model
+        ))),
+    }
+}
+
+// UASTC channel IDs with alpha
+const UASTC_RGBA: u32 = 3;
+const UASTC_RRRG: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Bc7,
+    Etc2,
+}
+
+// The first of `preferred` that the device samples, or RGBA8
+fn target_format(
+    preferred: &[Target],
+    features: wgpu::Features,
+    srgb: bool,
+    alpha: bool,
+) -> wgpu::TextureFormat {
+    use wgpu::TextureFormat;
+
+    let target = preferred.iter().find(|target| {
+        features.contains(match target {
+            Target::Bc7 => wgpu::Features::TEXTURE_COMPRESSION_BC,
+            Target::Etc2 => wgpu::Features::TEXTURE_COMPRESSION_ETC2,
+        })
+    });
+    match (target, alpha, srgb) {
+        (Some(Target::Bc7), _, false) => TextureFormat::Bc7RgbaUnorm,
+        (Some(Target::Bc7), _, true) => TextureFormat::Bc7RgbaUnormSrgb,
+        (Some(Target::Etc2), false, false) => TextureFormat::Etc2Rgb8Unorm,
+        (Some(Target::Etc2), false, true) => TextureFormat::Etc2Rgb8UnormSrgb,
+        (Some(Target::Etc2), true, false) => TextureFormat::Etc2Rgba8Unorm,
+        (Some(Target::Etc2), true, true) => TextureFormat::Etc2Rgba8UnormSrgb,
+        (None, _, false) => TextureFormat::Rgba8Unorm,
+        (None, _, true) => TextureFormat::Rgba8UnormSrgb,
+    }
+}
+
+// Compresses decoded RGBA8 texels to `format`, unless it is RGBA8
+fn recompress(
+    texels: Vec<u8>,
+    dimensions: (u32, u32),
+    format: wgpu::TextureFormat,
+) -> Result<Vec<u8>> {
+    match format.describe().block_dimensions {
+        (1, 1) => Ok(texels),
+        _ => compress(&texels, dimensions, format),
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid_data("BasisLZ global data is truncated"))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid_data("BasisLZ global data is truncated"))
+}
+
+// Decodes the ETC1S slices of every level, and transcodes them to `format`
+fn etc1s_levels(
+    global_data: &[u8],
+    levels: &[Vec<u8>],
+    dimensions: (u32, u32),
+    alpha: bool,
+    format: wgpu::TextureFormat,
+) -> Result<Vec<Vec<u8>>> {
+    let endpoint_count = read_u16(global_data, 0)? as usize;
+    let selector_count = read_u16(global_data, 2)? as usize;
+    let [endpoints_length, selectors_length, tables_length] =
+        [4, 8, 12].map(|offset| read_u32(global_data, offset).map(|length| length as usize));
+    let (endpoints_length, selectors_length, tables_length) =
+        (endpoints_length?, selectors_length?, tables_length?);
+    // Each image is described by its flags and the offset and length of its
+    // color and alpha slices in its level
+    let descriptions_start = 20;
+    let codebooks_start = descriptions_start + 20 * levels.len();
+    let section = |start: usize, length: usize| {
+        global_data
+            .get(start..start + length)
+            .ok_or_else(|| invalid_data("BasisLZ global data is truncated"))
+    };
+    let endpoints_data = section(codebooks_start, endpoints_length)?;
+    let selectors_data = section(codebooks_start + endpoints_length, selectors_length)?;
+    let tables_data = section(
+        codebooks_start + endpoints_length + selectors_length,
+        tables_length,
+    )?;
+    let codebooks = Etc1sCodebooks {
+        endpoints: read_endpoints(endpoints_data, endpoint_count)?,
+        selectors: read_selectors(selectors_data, selector_count)?,
+        tables: Etc1sTables::read(tables_data)?,
+    };
+
+    let mut transcoded = Vec::new();
+    for (level, bytes) in levels.iter().enumerate() {
+        let description = descriptions_start + 20 * level;
+        let [flags, color_offset, color_length, alpha_offset, alpha_length] =
+            [0, 4, 8, 12, 16].map(|offset| read_u32(global_data, description + offset));
+        if flags? & ETC1S_P_FRAME != 0 {
+            return Err(Error::UnsupportedFormat("ETC1S video frames".to_string()));
+        }
+        let level_dimensions = level_dimensions(dimensions, level as u32);
+        let slice = |offset: u32, length: u32| {
+            let slice = bytes
+                .get(offset as usize..offset as usize + length as usize)
+                .ok_or_else(|| invalid_data("ETC1S slice is outside of its level"))?;
+            codebooks.decode_slice(slice, level_dimensions)
+        };
+        let color = slice(color_offset?, color_length?)?;
+        let alpha = match alpha {
+            true => Some(slice(alpha_offset?, alpha_length?)?),
+            false => None,
+        };
+
+        // ETC1S blocks are valid ETC2 RGB8 blocks, and keep their colors
+        // when transcoded to either ETC2 format
+        let etc2_rgb = wgpu::TextureFormat::Etc2Rgb8Unorm;
+        if matches!(
+            format,
+            wgpu::TextureFormat::Etc2Rgb8Unorm | wgpu::TextureFormat::Etc2Rgb8UnormSrgb
+        ) {
+            transcoded.push(color);
+            continue;
+        }
+        let (mut texels, _) = decompress(&color, level_dimensions, etc2_rgb)?;
+        if let Some(alpha) = alpha {
+            // Alpha slices are stored in the green channel
+            let (alpha, _) = decompress(&alpha, level_dimensions, etc2_rgb)?;
+            for (texel, alpha) in texels.chunks_exact_mut(4).zip(alpha.chunks_exact(4)) {
+                texel[3] = alpha[1];
+            }
+        }
+        let mut bytes = recompress(texels, level_dimensions, format)?;
+        // Only alpha is encoded again, next to the original color blocks
+        if matches!(
+            format,
+            wgpu::TextureFormat::Etc2Rgba8Unorm | wgpu::TextureFormat::Etc2Rgba8UnormSrgb
+        ) {
+            for (block, color) in bytes.chunks_exact_mut(16).zip(color.chunks_exact(8)) {
+                block[8..].copy_from_slice(color);
+            }
+        }
+        transcoded.push(bytes);
+    }
+    Ok(transcoded)
+}
+
+const ETC1S_P_FRAME: u32 = 2;
+
+// Reads bits from the lowest bit of each byte up
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn read(&mut self, count: u32) -> Result<u32> {
+        let mut value = 0;
+        for bit in 0..count {
+            let byte = self
+                .bytes
+                .get(self.position / 8)
+                .ok_or_else(|| invalid_data("Basis Universal data is truncated"))?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << bit;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    // Reads `chunk_bits` at a time, each chunk followed by a bit that says
+    // whether another follows
+    fn read_variable(&mut self, chunk_bits: u32) -> Result<u32> {
+        let mut value = 0;
+        for shift in (0..32).step_by(chunk_bits as usize) {
+            let chunk = self.read(chunk_bits + 1)?;
+            value |= (chunk & ((1 << chunk_bits) - 1)) << shift;
+            if chunk >> chunk_bits == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data(
+            "Basis Universal variable length value is too long",
+        ))
+    }
+
+    fn read_symbol(&mut self, code: &Huffman) -> Result<u32> {
+        // Codes of the same length are consecutive, and start where the
+        // shorter codes left off
+        let (mut value, mut first, mut index) = (0, 0, 0);
+        for count in &code.counts[1..] {
+            value |= self.read(1)?;
+            if value < first + count {
+                return Ok(code.symbols[(index + value - first) as usize] as u32);
+            }
+            index += count;
+            first = (first + count) << 1;
+            value <<= 1;
+        }
+        Err(invalid_data(
+            "Basis Universal data has an invalid Huffman code",
+        ))
+    }
+
+    // Reads a Huffman code's lengths, themselves Huffman coded by a code
+    // with 3 bit lengths
+    fn read_huffman(&mut self) -> Result<Huffman> {
+        let symbol_count = self.read(14)? as usize;
+        if symbol_count == 0 {
+            return Huffman::new(&[]);
+        }
+        let length_code_count = self.read(5)? as usize;
+        if !(1..=HUFFMAN_LENGTH_CODE_ORDER.len()).contains(&length_code_count) {
+            return Err(invalid_data(
+                "Basis Universal data has an invalid Huffman code",
+            ));
+        }
+        let mut length_code_lengths = [0; HUFFMAN_LENGTH_CODE_ORDER.len()];
+        for symbol in &HUFFMAN_LENGTH_CODE_ORDER[..length_code_count] {
+            length_code_lengths[*symbol] = self.read(3)? as u8;
+        }
+        let length_code = Huffman::new(&length_code_lengths)?;
+
+        let mut lengths = vec![0; symbol_count];
+        let mut index = 0;
+        while index < symbol_count {
+            // Past the lengths themselves, codes repeat zero or the last length
+            let (length, run) = match self.read_symbol(&length_code)? {
+                length @ 0..=16 => (length as u8, 1),
+                17 => (0, self.read(3)? + 3),
+                18 => (0, self.read(7)? + 11),
+                code if index > 0 => {
+                    let run = match code {
+                        19 => self.read(2)? + 3,
+                        _ => self.read(7)? + 7,
+                    };
+                    (lengths[index - 1], run)
+                }
+                _ => {
+                    return Err(invalid_data(
+                        "Basis Universal data has an invalid Huffman code",
+                    ))
+                }
+            };
+            let run = run as usize;
+            if index + run > symbol_count {
+                return Err(invalid_data(
+                    "Basis Universal data has an invalid Huffman code",
+                ));
+            }
+            lengths[index..index + run].fill(length);
+            index += run;
+        }
+        Huffman::new(&lengths)
+    }
+}
+
+// The order lengths of the code length code are stored in
+const HUFFMAN_LENGTH_CODE_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+// A canonical Huffman code, where shorter codes come first and codes of one
+// length are assigned in symbol order
+struct Huffman {
+    // The number of codes of each length
+    counts: [u32; 17],
+    // Symbols in code order
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0; 17];
+        for &length in lengths {
+            *counts.get_mut(length as usize).ok_or_else(|| {
+                invalid_data("Basis Universal data has an invalid Huffman code")
+            })? += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|&symbol| lengths[symbol as usize] > 0)
+            .collect();
+        symbols.sort_by_key(|&symbol| lengths[symbol as usize]);
+        Ok(Self { counts, symbols })
+    }
+}
+
+// A 5 bit color and the ETC1 modifier table it's painted with
+#[derive(Clone, Copy)]
+struct Etc1sEndpoint {
+    color: [u32; 3],
+    table: u32,
+}
+
+// Selectors of a block's texels in row-major order, from the most negative
+// modifier up
+type Etc1sSelectors = [u32; 16];
+
+// Endpoints are coded as Huffman coded offsets from the previous one, with
+// the color offsets' code picked by the previous color
+fn read_endpoints(data: &[u8], count: usize) -> Result<Vec<Etc1sEndpoint>> {
+    let mut reader = BitReader::new(data);
+    let color_codes = [
+        reader.read_huffman()?,
+        reader.read_huffman()?,
+        reader.read_huffman()?,
+    ];
+    let table_code = reader.read_huffman()?;
+    let grayscale = reader.read(1)? == 1;
+
+    let mut previous = Etc1sEndpoint {
+        color: [16; 3],
+        table: 0,
+    };
+    let mut endpoints = Vec::with_capacity(count);
+    for _ in 0..count {
+        let table = (previous.table + reader.read_symbol(&table_code)?) & 7;
+        let mut color = previous.color;
+        let channels = if grayscale { 1 } else { 3 };
+        for value in &mut color[..channels] {
+            let code = match *value {
+                0..=9 => &color_codes[0],
+                10..=21 => &color_codes[1],
+                _ => &color_codes[2],
+            };
+            *value = (*value + reader.read_symbol(code)?) & 31;
+        }
+        if grayscale {
+            color = [color[0]; 3];
+        }
+        previous = Etc1sEndpoint { color, table };
+        endpoints.push(previous);
+    }
+    Ok(endpoints)
+}
+
+// Selectors are stored as four bytes of 2 bit selectors per block, raw or
+// as Huffman coded XORs with the previous block's bytes
+fn read_selectors(data: &[u8], count: usize) -> Result<Vec<Etc1sSelectors>> {
+    let mut reader = BitReader::new(data);
+    // The global and hybrid selector codebooks of older encoders
+    if reader.read(1)? == 1 || reader.read(1)? == 1 {
+        return Err(Error::UnsupportedFormat(
+            "ETC1S global selector codebooks".to_string(),
+        ));
+    }
+    let raw = reader.read(1)? == 1;
+    let code = match raw {
+        true => None,
+        false => Some(reader.read_huffman()?),
+    };
+
+    let mut previous = [0; 4];
+    let mut selectors = Vec::with_capacity(count);
+    for index in 0..count {
+        for byte in &mut previous {
+            *byte = match &code {
+                Some(code) if index > 0 => *byte ^ reader.read_symbol(code)?,
+                _ => reader.read(8)?,
+            };
+        }
+        selectors.push(std::array::from_fn(|texel| {
+            previous[texel / 4] >> (2 * (texel % 4)) & 3
+        }));
+    }
+    Ok(selectors)
+}
+
+// The Huffman codes slices are coded with
+struct Etc1sTables {
+    endpoint_predictions: Huffman,
+    endpoint_offsets: Huffman,
+    selectors: Huffman,
+    selector_runs: Huffman,
+    selector_history_size: usize,
+}
+
+impl Etc1sTables {
+    fn read(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data);
+        Ok(Self {
+            endpoint_predictions: reader.read_huffman()?,
+            endpoint_offsets: reader.read_huffman()?,
+            selectors: reader.read_huffman()?,
+            selector_runs: reader.read_huffman()?,
+            selector_history_size: reader.read(13)? as usize,
+        })
+    }
+}
+
+struct Etc1sCodebooks {
+    endpoints: Vec<Etc1sEndpoint>,
+    selectors: Vec<Etc1sSelectors>,
+    tables: Etc1sTables,
+}
+
+// The endpoint prediction symbol that repeats the last prediction, and the
+// shortest run it codes
+const ENDPOINT_PREDICTION_REPEAT: u32 = 256;
+const ENDPOINT_PREDICTION_MIN_REPEAT: u32 = 3;
+
+// Selector history runs shorter than this aren't coded as runs, and the last
+// run symbol is followed by the run's length
+const SELECTOR_RUN_MIN: u32 = 3;
+const SELECTOR_RUN_LONG: u32 = 63;
+
+// ETC1 selectors, indexed by ETC1S selectors
+const ETC1_SELECTORS: [u64; 4] = [3, 2, 0, 1];
+
+impl Etc1sCodebooks {
+    // Decodes a slice of `dimensions` texels to ETC1 blocks, in row order
+    fn decode_slice(&self, data: &[u8], dimensions: (u32, u32)) -> Result<Vec<u8>> {
+        let tables = &self.tables;
+        let mut reader = BitReader::new(data);
+        let blocks_wide = dimensions.0.div_ceil(4) as usize;
+        let blocks_high = dimensions.1.div_ceil(4) as usize;
+        let invalid = || invalid_data("ETC1S slice is invalid");
+
+        // Each pair of rows keeps the endpoint indices of the row above, and
+        // the predictions the second row of each 2x2 group shares with the
+        // first
+        let mut rows = [vec![(0, 0); blocks_wide], vec![(0, 0); blocks_wide]];
+        let mut predictions = 0;
+        let (mut previous_prediction, mut prediction_repeats) = (0, 0);
+        let mut previous_endpoint = 0;
+        let mut history = SelectorHistory::new(tables.selector_history_size);
+        let selector_run_symbol = self.selectors.len() + tables.selector_history_size;
+        let mut selector_run = 0;
+
+        let mut blocks = Vec::with_capacity(8 * blocks_wide * blocks_high);
+        for block_y in 0..blocks_high {
+            let (current, above) = match block_y & 1 {
+                0 => (0, 1),
+                _ => (1, 0),
+            };
+            for block_x in 0..blocks_wide {
+                // Two bits per block predict the endpoints of a 2x2 group
+                if block_x & 1 == 0 {
+                    if block_y & 1 == 0 {
+                        if prediction_repeats > 0 {
+                            prediction_repeats -= 1;
+                            predictions = previous_prediction;
+                        } else {
+                            predictions = reader.read_symbol(&tables.endpoint_predictions)?;
+                            if predictions == ENDPOINT_PREDICTION_REPEAT {
+                                prediction_repeats =
+                                    reader.read_variable(4)? + ENDPOINT_PREDICTION_MIN_REPEAT - 1;
+                                predictions = previous_prediction;
+                            } else {
+                                previous_prediction = predictions;
+                            }
+                        }
+                        rows[above][block_x].1 = predictions >> 4;
+                    } else {
+                        predictions = rows[current][block_x].1;
+                    }
+                }
+                let prediction = predictions & 3;
+                predictions >>= 2;
+
+                let endpoint = match prediction {
+                    // The block to the left, above, and above and to the left
+                    0 if block_x > 0 => previous_endpoint,
+                    1 if block_y > 0 => rows[above][block_x].0,
+                    2 if block_x > 0 && block_y > 0 => rows[above][block_x - 1].0,
+                    3 => {
+                        let offset = reader.read_symbol(&tables.endpoint_offsets)? as usize;
+                        (previous_endpoint + offset) % self.endpoints.len().max(1)
+                    }
+                    _ => return Err(invalid()),
+                };
+                rows[current][block_x].0 = endpoint;
+                previous_endpoint = endpoint;
+
+                // Selectors are coded directly, as an index into the history
+                // of recent selectors, or as a run of the most recent one
+                let symbol = match selector_run {
+                    0 => {
+                        let symbol = reader.read_symbol(&tables.selectors)? as usize;
+                        if symbol == selector_run_symbol {
+                            let run = reader.read_symbol(&tables.selector_runs)?;
+                            selector_run = match run {
+                                SELECTOR_RUN_LONG => reader.read_variable(7)? + SELECTOR_RUN_MIN,
+                                run => run + SELECTOR_RUN_MIN,
+                            } - 1;
+                            self.selectors.len()
+                        } else {
+                            symbol
+                        }
+                    }
+                    _ => {
+                        selector_run -= 1;
+                        self.selectors.len()
+                    }
+                };
+                let selector = match symbol.checked_sub(self.selectors.len()) {
+                    Some(index) => history.take(index).ok_or_else(invalid)?,
+                    None => {
+                        history.add(symbol);
+                        symbol
+                    }
+                };
+
+                let endpoint = self.endpoints.get(endpoint).ok_or_else(invalid)?;
+                let selectors = self.selectors.get(selector).ok_or_else(invalid)?;
+                let [red, green, blue] = endpoint.color.map(|channel| channel as u64);
+                let table = endpoint.table as u64;
+                // Differential mode with no offset, and both subblocks flipped
+                // to the same table
+                let mut block = red << 59 | green << 51 | blue << 43 | table << 37 | table << 34;
+                block |= 3 << 32;
+                for (texel, selector) in selectors.iter().enumerate() {
+                    let index = (texel % 4) * 4 + texel / 4;
+                    let selector = ETC1_SELECTORS[*selector as usize];
+                    block |= (selector >> 1) << (16 + index) | (selector & 1) << index;
+                }
+                blocks.extend(block.to_be_bytes());
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+// Recently used selectors, where each use moves a selector halfway to the
+// front and new selectors are added from the middle
+struct SelectorHistory {
+    selectors: Vec<usize>,
+    next: usize,
+}
+
+impl SelectorHistory {
+    fn new(size: usize) -> Self {
+        Self {
+            selectors: vec![0; size],
+            next: size / 2,
+        }
+    }
+
+    fn add(&mut self, selector: usize) {
+        if self.selectors.is_empty() {
+            return;
+        }
+        self.selectors[self.next] = selector;
+        self.next += 1;
+        if self.next == self.selectors.len() {
+            self.next = self.selectors.len() / 2;
+        }
+    }
+
+    fn take(&mut self, index: usize) -> Option<usize> {
+        let selector = *self.selectors.get(index)?;
+        self.selectors.swap(index / 2, index);
+        Some(selector)
+    }
+}
@@ -39,6 +39,15 @@ impl CameraIntrinsics {
     }
 }
 
+// Matches `CameraUniform` in shader.wgsl. The position is padded to a vec4
+// for uniform buffer alignment.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+    pub position: [f32; 4],
+}
+
 pub struct Camera {
     pub extrinsics: CameraExtrinsics,
     pub intrinsics: CameraIntrinsics,
@@ -62,6 +71,13 @@ impl Camera {
         (self.proj_matrix * self.view_matrix).into()
     }
 
+    pub fn to_uniform(&self) -> CameraUniform {
+        CameraUniform {
+            view_proj: self.to_uniform_matrix(),
+            position: self.extrinsics.position.into(),
+        }
+    }
+
     pub fn set_aspect(&mut self, size: PhysicalSize<u32>) {
         self.intrinsics.aspect = size.width as f32 / size.height as f32;
         self.proj_matrix = self.intrinsics.to_perspective_matrix();
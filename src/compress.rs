@@ -0,0 +1,312 @@
+use crate::decompress::{etc_texel, Block, EAC_MODIFIERS, ETC_MODIFIERS};
+use crate::error::{Error, Result};
+
+// Compresses RGBA8 texels to BC7 or ETC2 blocks, for transcoded Basis
+// Universal textures. The encoders favor speed over quality: BC7 blocks
+// only use mode 6, and ETC2 blocks only ETC1's individual and differential
+// modes.
+pub fn compress(
+    texels: &[u8],
+    dimensions: (u32, u32),
+    format: wgpu::TextureFormat,
+) -> Result<Vec<u8>> {
+    use wgpu::TextureFormat;
+
+    let encode_block: fn(&Block) -> Vec<u8> = match format {
+        TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => {
+            |block| bc7_mode6(block).to_vec()
+        }
+        TextureFormat::Etc2Rgb8Unorm | TextureFormat::Etc2Rgb8UnormSrgb => {
+            |block| etc1(block).to_be_bytes().to_vec()
+        }
+        TextureFormat::Etc2Rgba8Unorm | TextureFormat::Etc2Rgba8UnormSrgb => |block| {
+            let alpha = eac_alpha(block.map(|texel| texel[3]));
+            [alpha.to_be_bytes(), etc1(block).to_be_bytes()].concat()
+        },
+        _ => {
+            return Err(Error::UnsupportedFormat(format!(
+                "{:?} compression",
+                format
+            )))
+        }
+    };
+
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let expected = 4 * width * height;
+    if texels.len() != expected {
+        return Err(Error::InvalidImageData {
+            expected,
+            actual: texels.len(),
+        });
+    }
+    let mut bytes = Vec::new();
+    for block_y in (0..height).step_by(4) {
+        for block_x in (0..width).step_by(4) {
+            // Blocks on the right and bottom edges repeat the last texels
+            let block = std::array::from_fn(|index| {
+                let x = (block_x + index % 4).min(width - 1);
+                let y = (block_y + index / 4).min(height - 1);
+                let offset = 4 * (y * width + x);
+                texels[offset..offset + 4].try_into().unwrap()
+            });
+            bytes.extend(encode_block(&block));
+        }
+    }
+    Ok(bytes)
+}
+
+fn squared_error(a: [i32; 4], b: [u8; 4]) -> i32 {
+    a.iter().zip(b).map(|(a, b)| (a - b as i32).pow(2)).sum()
+}
+
+const BC7_WEIGHTS_4: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// A single subset of 7 bit RGBA endpoints with a p-bit each, interpolated
+// by 4 bit indices. The endpoints span the texels along their principal
+// axis.
+fn bc7_mode6(block: &Block) -> [u8; 16] {
+    let texels = block.map(|texel| texel.map(|channel| channel as f32));
+    let mean: [f32; 4] = std::array::from_fn(|channel| {
+        texels.iter().map(|texel| texel[channel]).sum::<f32>() / 16.0
+    });
+    let covariance: [[f32; 4]; 4] = std::array::from_fn(|row| {
+        std::array::from_fn(|column| {
+            texels
+                .iter()
+                .map(|texel| (texel[row] - mean[row]) * (texel[column] - mean[column]))
+                .sum()
+        })
+    });
+    // Starting from the channel that varies most keeps the iteration off
+    // axes the covariance maps to zero, like red against blue
+    let widest = (0..4)
+        .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+        .unwrap();
+    let mut axis = covariance[widest];
+    for _ in 0..8 {
+        let next: [f32; 4] = std::array::from_fn(|row| {
+            (0..4)
+                .map(|column| covariance[row][column] * axis[column])
+                .sum()
+        });
+        let length = next.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break;
+        }
+        axis = next.map(|value| value / length);
+    }
+    let projections = texels.map(|texel| {
+        (0..4)
+            .map(|channel| (texel[channel] - mean[channel]) * axis[channel])
+            .sum::<f32>()
+    });
+    let (low, high) = projections
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(low, high), &t| {
+            (low.min(t), high.max(t))
+        });
+    let endpoint = |t: f32| std::array::from_fn(|channel| mean[channel] + t * axis[channel]);
+    let endpoints: [[f32; 4]; 2] = [endpoint(low), endpoint(high)];
+
+    // Each combination of p-bits quantizes the endpoints differently
+    let mut best = (i32::MAX, [[0; 4]; 2], [0; 2], [0; 16]);
+    for pbits in [[0, 0], [0, 1], [1, 0], [1, 1]] {
+        let quantized: [[i32; 4]; 2] = std::array::from_fn(|index| {
+            endpoints[index]
+                .map(|value| (((value - pbits[index] as f32) / 2.0).round() as i32).clamp(0, 127))
+        });
+        let palette: [[i32; 4]; 16] = BC7_WEIGHTS_4.map(|weight| {
+            std::array::from_fn(|channel| {
+                let [value0, value1] =
+                    [0, 1].map(|index| quantized[index][channel] << 1 | pbits[index]);
+                (value0 * (64 - weight) + value1 * weight + 32) >> 6
+            })
+        });
+        let mut error = 0;
+        let indices = block.map(|texel| {
+            let (index, texel_error) = (0..16)
+                .map(|index| (index, squared_error(palette[index], texel)))
+                .min_by_key(|(_, error)| *error)
+                .unwrap();
+            error += texel_error;
+            index
+        });
+        if error < best.0 {
+            best = (error, quantized, pbits, indices);
+        }
+    }
+    let (_, mut quantized, mut pbits, mut indices) = best;
+
+    // The first index drops its top bit, so it must select the first half
+    if indices[0] >= 8 {
+        quantized.swap(0, 1);
+        pbits.swap(0, 1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut bits = 1u128 << 6;
+    let mut position = 7;
+    let mut write = |value: u128, count: u32| {
+        bits |= value << position;
+        position += count;
+    };
+    let [endpoint0, endpoint1] = quantized;
+    for (value0, value1) in endpoint0.into_iter().zip(endpoint1) {
+        write(value0 as u128, 7);
+        write(value1 as u128, 7);
+    }
+    write(pbits[0] as u128, 1);
+    write(pbits[1] as u128, 1);
+    for (texel, index) in indices.iter().enumerate() {
+        write(*index as u128, if texel == 0 { 3 } else { 4 });
+    }
+    bits.to_le_bytes()
+}
+
+// The table and selectors that best fit one subblock's texels to `base`,
+// and their error
+fn etc1_subblock(block: &Block, texels: &[usize], base: [i32; 3]) -> (i32, u64, [u64; 16]) {
+    let mut best = (i32::MAX, 0, [0; 16]);
+    for (table, [small, large]) in ETC_MODIFIERS.iter().enumerate() {
+        let mut error = 0;
+        let mut selectors = [0; 16];
+        for &index in texels {
+            let texel = block[etc_texel(index)];
+            let (selector, texel_error) = [*small, *large, -small, -large]
+                .iter()
+                .enumerate()
+                .map(|(selector, modifier)| {
+                    let color = base.map(|channel| (channel + modifier).clamp(0, 255));
+                    let color = [color[0], color[1], color[2], texel[3] as i32];
+                    (selector as u64, squared_error(color, texel))
+                })
+                .min_by_key(|(_, error)| *error)
+                .unwrap();
+            error += texel_error;
+            selectors[index] = selector;
+        }
+        if error < best.0 {
+            best = (error, table as u64, selectors);
+        }
+    }
+    best
+}
+
+// An ETC1 block, which ETC2 RGB8 decodes the same way. Both subblock
+// orientations are tried, with 4 bit base colors and with a 5 bit base
+// and a 3 bit offset.
+fn etc1(block: &Block) -> u64 {
+    let mut best = (i32::MAX, 0);
+    for flip in [false, true] {
+        let subblocks: [Vec<usize>; 2] = [0, 1].map(|subblock| {
+            (0..16)
+                .filter(|index| {
+                    let (x, y) = (index / 4, index % 4);
+                    (if flip { y >= 2 } else { x >= 2 }) == (subblock == 1)
+                })
+                .collect()
+        });
+        let averages = subblocks.clone().map(|texels| {
+            std::array::from_fn::<f32, 3, _>(|channel| {
+                texels
+                    .iter()
+                    .map(|&index| block[etc_texel(index)][channel] as f32)
+                    .sum::<f32>()
+                    / texels.len() as f32
+            })
+        });
+
+        let individual = averages.map(|average| average.map(|value| (value / 17.0).round() as i32));
+        let [base0, base1] = individual.map(|base| base.map(|value| value as u64));
+        let mut candidates = vec![(
+            false,
+            individual.map(|base| base.map(|value| value * 17)),
+            base0[0] << 60
+                | base1[0] << 56
+                | base0[1] << 52
+                | base1[1] << 48
+                | base0[2] << 44
+                | base1[2] << 40,
+        )];
+        let differential =
+            averages.map(|average| average.map(|value| (value * 31.0 / 255.0).round() as i32));
+        let offsets: [i32; 3] =
+            std::array::from_fn(|channel| differential[1][channel] - differential[0][channel]);
+        if offsets.iter().all(|offset| (-4..4).contains(offset)) {
+            let base = differential[0].map(|value| value as u64);
+            let offsets = offsets.map(|offset| (offset & 7) as u64);
+            candidates.push((
+                true,
+                differential.map(|base| base.map(|value| value << 3 | value >> 2)),
+                base[0] << 59
+                    | offsets[0] << 56
+                    | base[1] << 51
+                    | offsets[1] << 48
+                    | base[2] << 43
+                    | offsets[2] << 40,
+            ));
+        }
+
+        for (differential, bases, colors) in candidates {
+            let fits =
+                [0, 1].map(|subblock| etc1_subblock(block, &subblocks[subblock], bases[subblock]));
+            let error = fits[0].0 + fits[1].0;
+            if error >= best.0 {
+                continue;
+            }
+            let mut bits = colors
+                | fits[0].1 << 37
+                | fits[1].1 << 34
+                | (differential as u64) << 33
+                | (flip as u64) << 32;
+            for index in 0..16 {
+                let selector = fits[0].2[index] | fits[1].2[index];
+                bits |= (selector >> 1) << (16 + index) | (selector & 1) << index;
+            }
+            best = (error, bits);
+        }
+    }
+    best.1
+}
+
+// An EAC alpha block. Each table is tried with the multipliers closest to
+// stretching it over the alpha range, centred on the range.
+fn eac_alpha(alpha: [u8; 16]) -> u64 {
+    let (low, high) = (
+        *alpha.iter().min().unwrap() as i32,
+        *alpha.iter().max().unwrap() as i32,
+    );
+    // A zero multiplier gives every texel the base value
+    let mut best = (i32::MAX, (high as u64) << 56);
+    if low == high {
+        return best.1;
+    }
+    for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+        let span = modifiers[7] - modifiers[3];
+        let multiplier = (high - low + span / 2) / span;
+        for multiplier in (multiplier - 1).max(1)..=(multiplier + 1).min(15) {
+            let base =
+                ((low + high - (modifiers[3] + modifiers[7]) * multiplier) / 2).clamp(0, 255);
+            let mut error = 0;
+            let mut bits = (base as u64) << 56 | (multiplier as u64) << 52 | (table as u64) << 48;
+            for index in 0..16 {
+                let value = alpha[etc_texel(index)] as i32;
+                let (selector, texel_error) = modifiers
+                    .iter()
+                    .enumerate()
+                    .map(|(selector, modifier)| {
+                        let decoded = (base + modifier * multiplier).clamp(0, 255);
+                        (selector as u64, (decoded - value).pow(2))
+                    })
+                    .min_by_key(|(_, error)| *error)
+                    .unwrap();
+                error += texel_error;
+                bits |= selector << (45 - 3 * index);
+            }
+            if error < best.0 {
+                best = (error, bits);
+            }
+        }
+    }
+    best.1
+}
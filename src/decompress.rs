@@ -0,0 +1,1929 @@
+use crate::error::{Error, Result};
+use crate::texture::level_size;
+
+// 4x4 texels in row-major order
+pub(crate) type Block = [[u8; 4]; 16];
+
+// ETC1/ETC2 intensity modifiers, indexed by each subblock's table codeword
+pub(crate) const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+// Distances between the paint colors of ETC2's T and H modes
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+pub(crate) const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+// Expands block compressed texels to RGBA8, for devices without the matching
+// texture compression feature. Returns the texels and the RGBA8 format that
+// keeps the source's color space and signedness, or `Rgba16Float` for BC6H.
+pub fn decompress(
+    bytes: &[u8],
+    dimensions: (u32, u32),
+    format: wgpu::TextureFormat,
+) -> Result<(Vec<u8>, wgpu::TextureFormat)> {
+    use wgpu::TextureFormat;
+
+    let decode_block: fn(&[u8]) -> Block = match format {
+        TextureFormat::Etc2Rgb8Unorm | TextureFormat::Etc2Rgb8UnormSrgb => {
+            |block| etc2_rgb(read_u64(block), false)
+        }
+        TextureFormat::Etc2Rgb8A1Unorm | TextureFormat::Etc2Rgb8A1UnormSrgb => {
+            |block| etc2_rgb(read_u64(block), true)
+        }
+        TextureFormat::Etc2Rgba8Unorm | TextureFormat::Etc2Rgba8UnormSrgb => etc2_rgba,
+        TextureFormat::EacR11Unorm => |block| eac_r11(block, false),
+        TextureFormat::EacR11Snorm => |block| eac_r11(block, true),
+        TextureFormat::EacRg11Unorm => |block| eac_rg11(block, false),
+        TextureFormat::EacRg11Snorm => |block| eac_rg11(block, true),
+        TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => {
+            |block| bc1_colors(block, false)
+        }
+        TextureFormat::Bc2RgbaUnorm | TextureFormat::Bc2RgbaUnormSrgb => bc2,
+        TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb => bc3,
+        TextureFormat::Bc4RUnorm => |block| bc4(block, false),
+        TextureFormat::Bc4RSnorm => |block| bc4(block, true),
+        TextureFormat::Bc5RgUnorm => |block| bc5(block, false),
+        TextureFormat::Bc5RgSnorm => |block| bc5(block, true),
+        TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => bc7,
+        TextureFormat::Astc { channel, .. } if channel != wgpu::AstcChannel::Hdr => {
+            let srgb = channel == wgpu::AstcChannel::UnormSrgb;
+            let (width, height) = format.describe().block_dimensions;
+            let block_dimensions = (width as usize, height as usize);
+            let texels = texels(bytes, dimensions, format, |block| {
+                astc(block, block_dimensions, srgb)
+            })?;
+            let rgba8_format = match srgb {
+                true => TextureFormat::Rgba8UnormSrgb,
+                false => TextureFormat::Rgba8Unorm,
+            };
+            return Ok((texels, rgba8_format));
+        }
+        // HDR texels don't fit in RGBA8, so BC6H expands to half floats
+        TextureFormat::Bc6hRgbUfloat | TextureFormat::Bc6hRgbSfloat => {
+            let signed = format == TextureFormat::Bc6hRgbSfloat;
+            let texels = texels(bytes, dimensions, format, |block| bc6h(block, signed))?;
+            return Ok((texels, TextureFormat::Rgba16Float));
+        }
+        _ => {
+            return Err(Error::UnsupportedFormat(format!(
+                "{:?} decompression",
+                format
+            )))
+        }
+    };
+
+    let rgba = texels(bytes, dimensions, format, decode_block)?;
+    let rgba8_format = match format {
+        TextureFormat::EacR11Snorm
+        | TextureFormat::EacRg11Snorm
+        | TextureFormat::Bc4RSnorm
+        | TextureFormat::Bc5RgSnorm => TextureFormat::Rgba8Snorm,
+        _ if format.describe().srgb => TextureFormat::Rgba8UnormSrgb,
+        _ => TextureFormat::Rgba8Unorm,
+    };
+    Ok((rgba, rgba8_format))
+}
+
+// Expands Basis Universal's UASTC blocks to RGBA8. They're laid out like
+// ASTC 4x4 blocks, and decode like them too.
+pub fn decompress_uastc(bytes: &[u8], dimensions: (u32, u32), srgb: bool) -> Result<Vec<u8>> {
+    let format = wgpu::TextureFormat::Astc {
+        block: wgpu::AstcBlock::B4x4,
+        channel: wgpu::AstcChannel::Unorm,
+    };
+    texels(bytes, dimensions, format, |block| uastc(block, srgb))
+}
+
+// Decodes every block of a level and lays the texels out row by row
+fn texels<const N: usize, T: AsRef<[[u8; N]]>>(
+    bytes: &[u8],
+    dimensions: (u32, u32),
+    format: wgpu::TextureFormat,
+    decode_block: impl Fn(&[u8]) -> T,
+) -> Result<Vec<u8>> {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let format_info = format.describe();
+    let (block_width, block_height) = (
+        format_info.block_dimensions.0 as usize,
+        format_info.block_dimensions.1 as usize,
+    );
+    let blocks_wide = width.div_ceil(block_width);
+    let block_size = format_info.block_size as usize;
+    let expected = level_size(format, dimensions);
+    if bytes.len() != expected {
+        return Err(Error::InvalidImageData {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+
+    let mut texels = vec![0; N * width * height];
+    for (index, block) in bytes.chunks_exact(block_size).enumerate() {
+        let block_x = block_width * (index % blocks_wide);
+        let block_y = block_height * (index / blocks_wide);
+        for (texel_index, texel) in decode_block(block).as_ref().iter().enumerate() {
+            let (x, y) = (
+                block_x + texel_index % block_width,
+                block_y + texel_index / block_width,
+            );
+            // Blocks on the right and bottom edges may hang over the image
+            if x < width && y < height {
+                let offset = N * (y * width + x);
+                texels[offset..offset + N].copy_from_slice(texel);
+            }
+        }
+    }
+    Ok(texels)
+}
+
+// ETC blocks are stored big endian
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn field(block: u64, low_bit: u32, count: u32) -> i32 {
+    ((block >> low_bit) & ((1 << count) - 1)) as i32
+}
+
+// Replicates the high bits of a `count` bit channel into the low bits of a
+// byte
+fn extend(value: i32, count: u32) -> i32 {
+    (value << (8 - count)) | (value >> (2 * count - 8))
+}
+
+fn sign_extend_3(value: i32) -> i32 {
+    (value << 29) >> 29
+}
+
+fn clamp_u8(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+// Signed 8 bit channels read -128 as -127, so both ends of the range are
+// exactly -1 and 1
+fn read_i8(byte: u8) -> i32 {
+    (byte as i8).max(-127) as i32
+}
+
+// The opaque alpha of unsigned and signed channels, stored in a byte
+fn opaque(signed: bool) -> u8 {
+    match signed {
+        true => 127,
+        false => 255,
+    }
+}
+
+// ETC indices count down columns, while `Block` is row-major
+pub(crate) fn etc_texel(index: usize) -> usize {
+    (index % 4) * 4 + index / 4
+}
+
+fn etc2_rgb(block: u64, punchthrough: bool) -> Block {
+    // RGB8A1 reuses the differential bit as an opaque flag and always
+    // decodes as if it was set
+    let differential_bit = field(block, 33, 1) == 1;
+    let (differential, opaque) = match punchthrough {
+        true => (true, differential_bit),
+        false => (differential_bit, true),
+    };
+    let flip = field(block, 32, 1) == 1;
+    let selector = |index: usize| {
+        (field(block, 16 + index as u32, 1) << 1 | field(block, index as u32, 1)) as usize
+    };
+    let transparent = |selector: usize| !opaque && selector == 2;
+
+    let paint = |colors: [[i32; 3]; 4]| {
+        let mut texels = [[0; 4]; 16];
+        for index in 0..16 {
+            let selector = selector(index);
+            if !transparent(selector) {
+                let [red, green, blue] = colors[selector].map(clamp_u8);
+                texels[etc_texel(index)] = [red, green, blue, 255];
+            }
+        }
+        texels
+    };
+
+    let subblocks = |bases: [[i32; 3]; 2]| {
+        let tables = [field(block, 37, 3), field(block, 34, 3)];
+        let mut texels = [[0; 4]; 16];
+        for index in 0..16 {
+            let (x, y) = (index / 4, index % 4);
+            let subblock = usize::from(if flip { y >= 2 } else { x >= 2 });
+            let [small, large] = ETC_MODIFIERS[tables[subblock] as usize];
+            let selector = selector(index);
+            let modifier = match selector {
+                0 if !opaque => 0,
+                0 => small,
+                1 => large,
+                2 => -small,
+                _ => -large,
+            };
+            if !transparent(selector) {
+                let [red, green, blue] = bases[subblock].map(|base| clamp_u8(base + modifier));
+                texels[etc_texel(index)] = [red, green, blue, 255];
+            }
+        }
+        texels
+    };
+
+    if !differential {
+        let base = |low_bit| extend(field(block, low_bit, 4), 4);
+        return subblocks([
+            [base(60), base(52), base(44)],
+            [base(56), base(48), base(40)],
+        ]);
+    }
+
+    let red = field(block, 59, 5);
+    let green = field(block, 51, 5);
+    let blue = field(block, 43, 5);
+    let red_2 = red + sign_extend_3(field(block, 56, 3));
+    let green_2 = green + sign_extend_3(field(block, 48, 3));
+    let blue_2 = blue + sign_extend_3(field(block, 40, 3));
+
+    // Overflowing differential colors select ETC2's extra modes
+    if !(0..32).contains(&red_2) {
+        // T mode
+        let color = |values: [i32; 3]| values.map(|value| extend(value, 4));
+        let color_1 = color([
+            field(block, 59, 2) << 2 | field(block, 56, 2),
+            field(block, 52, 4),
+            field(block, 48, 4),
+        ]);
+        let color_2 = color([
+            field(block, 44, 4),
+            field(block, 40, 4),
+            field(block, 36, 4),
+        ]);
+        let distance = ETC_DISTANCES[(field(block, 34, 2) << 1 | field(block, 32, 1)) as usize];
+        paint([
+            color_1,
+            color_2.map(|value| value + distance),
+            color_2,
+            color_2.map(|value| value - distance),
+        ])
+    } else if !(0..32).contains(&green_2) {
+        // H mode
+        let values_1 = [
+            field(block, 59, 4),
+            field(block, 56, 3) << 1 | field(block, 52, 1),
+            field(block, 51, 1) << 3 | field(block, 47, 3),
+        ];
+        let values_2 = [
+            field(block, 43, 4),
+            field(block, 39, 4),
+            field(block, 35, 4),
+        ];
+        let packed = |[red, green, blue]: [i32; 3]| red << 8 | green << 4 | blue;
+        let distance_index = field(block, 34, 1) << 2
+            | field(block, 32, 1) << 1
+            | i32::from(packed(values_1) >= packed(values_2));
+        let distance = ETC_DISTANCES[distance_index as usize];
+        let color_1 = values_1.map(|value| extend(value, 4));
+        let color_2 = values_2.map(|value| extend(value, 4));
+        paint([
+            color_1.map(|value| value + distance),
+            color_1.map(|value| value - distance),
+            color_2.map(|value| value + distance),
+            color_2.map(|value| value - distance),
+        ])
+    } else if !(0..32).contains(&blue_2) {
+        // Planar mode, which is always opaque
+        let origin = [
+            extend(field(block, 57, 6), 6),
+            extend(field(block, 56, 1) << 6 | field(block, 49, 6), 7),
+            extend(
+                field(block, 48, 1) << 5 | field(block, 43, 2) << 3 | field(block, 39, 3),
+                6,
+            ),
+        ];
+        let horizontal = [
+            extend(field(block, 34, 5) << 1 | field(block, 32, 1), 6),
+            extend(field(block, 25, 7), 7),
+            extend(field(block, 19, 6), 6),
+        ];
+        let vertical = [
+            extend(field(block, 13, 6), 6),
+            extend(field(block, 6, 7), 7),
+            extend(field(block, 0, 6), 6),
+        ];
+        let mut texels = [[0; 4]; 16];
+        for (index, texel) in texels.iter_mut().enumerate() {
+            let (x, y) = ((index % 4) as i32, (index / 4) as i32);
+            let channel = |channel: usize| {
+                clamp_u8(
+                    (x * (horizontal[channel] - origin[channel])
+                        + y * (vertical[channel] - origin[channel])
+                        + 4 * origin[channel]
+                        + 2)
+                        >> 2,
+                )
+            };
+            *texel = [channel(0), channel(1), channel(2), 255];
+        }
+        texels
+    } else {
+        let base = |value| extend(value, 5);
+        subblocks([
+            [base(red), base(green), base(blue)],
+            [base(red_2), base(green_2), base(blue_2)],
+        ])
+    }
+}
+
+// EAC modifiers for each texel, in row-major order, and their multiplier
+fn eac_modifiers(block: u64) -> ([i32; 16], i32) {
+    let table = EAC_MODIFIERS[field(block, 48, 4) as usize];
+    let mut modifiers = [0; 16];
+    for index in 0..16 {
+        modifiers[etc_texel(index)] = table[field(block, 45 - 3 * index as u32, 3) as usize];
+    }
+    (modifiers, field(block, 52, 4))
+}
+
+fn eac_alpha(block: u64) -> [u8; 16] {
+    let base = field(block, 56, 8);
+    let (modifiers, multiplier) = eac_modifiers(block);
+    modifiers.map(|modifier| clamp_u8(base + modifier * multiplier))
+}
+
+// 11 bit channels, rounded to 8 bits. Signed channels are stored as `i8`
+// bytes.
+fn eac_11(block: u64, signed: bool) -> [u8; 16] {
+    let (modifiers, multiplier) = eac_modifiers(block);
+    let step = if multiplier == 0 { 1 } else { 8 * multiplier };
+    if signed {
+        let base = read_i8(field(block, 56, 8) as u8);
+        modifiers.map(|modifier| {
+            let value = (8 * base + modifier * step).clamp(-1023, 1023);
+            ((value * 127 + value.signum() * 511) / 1023) as i8 as u8
+        })
+    } else {
+        let base = field(block, 56, 8);
+        modifiers.map(|modifier| {
+            let value = (8 * base + 4 + modifier * step).clamp(0, 2047);
+            ((value * 255 + 1023) / 2047) as u8
+        })
+    }
+}
+
+fn etc2_rgba(block: &[u8]) -> Block {
+    let alpha = eac_alpha(read_u64(block));
+    let mut texels = etc2_rgb(read_u64(&block[8..]), false);
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha;
+    }
+    texels
+}
+
+fn eac_r11(block: &[u8], signed: bool) -> Block {
+    eac_11(read_u64(block), signed).map(|red| [red, 0, 0, opaque(signed)])
+}
+
+fn eac_rg11(block: &[u8], signed: bool) -> Block {
+    let red = eac_11(read_u64(block), signed);
+    let green = eac_11(read_u64(&block[8..]), signed);
+    let mut texels = [[0, 0, 0, opaque(signed)]; 16];
+    for (index, texel) in texels.iter_mut().enumerate() {
+        texel[0] = red[index];
+        texel[1] = green[index];
+    }
+    texels
+}
+
+// BC blocks are stored little endian, and read from the lowest bit up
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            bits: u128::from_le_bytes(block[..16].try_into().unwrap()),
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> i32 {
+        let value = (self.bits >> self.position) & ((1 << count) - 1);
+        self.position += count;
+        value as i32
+    }
+}
+
+fn read_u64_le(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn rgb565(color: u16) -> [i32; 3] {
+    let color = color as i32;
+    [
+        extend(color >> 11, 5),
+        extend((color >> 5) & 0x3f, 6),
+        extend(color & 0x1f, 5),
+    ]
+}
+
+// The color half of BC1, BC2 and BC3 blocks. BC2 and BC3 always use four
+// colors, while BC1 switches to three colors and transparent black when the
+// endpoints are in ascending order.
+fn bc1_colors(block: &[u8], four_colors: bool) -> Block {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let (rgb0, rgb1) = (rgb565(color0), rgb565(color1));
+    let mix = |weight0: i32, weight1: i32| {
+        let channel =
+            |c: usize| ((weight0 * rgb0[c] + weight1 * rgb1[c]) / (weight0 + weight1)) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+    let palette = if four_colors || color0 > color1 {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0; 4]]
+    };
+
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    std::array::from_fn(|index| palette[((indices >> (2 * index)) & 3) as usize])
+}
+
+// A single channel interpolated between two 8 bit endpoints, as used by
+// BC3's alpha and BC4/BC5. Signed channels are stored as `i8` bytes.
+fn bc4_channel(block: &[u8], signed: bool) -> [u8; 16] {
+    // Signed endpoints are compared and interpolated as raw bytes, with -128
+    // only clamped to -127 at the end
+    let (value0, value1, min, max) = match signed {
+        true => (block[0] as i8 as i32, block[1] as i8 as i32, -127, 127),
+        false => (block[0] as i32, block[1] as i32, 0, 255),
+    };
+    // Signed values round to nearest, as dividing towards zero would pull
+    // negative and positive values in opposite directions
+    let divide = |value: i32, divisor: i32| match signed {
+        true => (value + value.signum() * divisor / 2) / divisor,
+        false => value / divisor,
+    };
+    let palette: [u8; 8] = std::array::from_fn(|code| {
+        let code = code as i32;
+        (match code {
+            0 => value0,
+            1 => value1,
+            _ if value0 > value1 => divide((8 - code) * value0 + (code - 1) * value1, 7),
+            6 => min,
+            7 => max,
+            _ => divide((6 - code) * value0 + (code - 1) * value1, 5),
+        })
+        .max(min) as u8
+    });
+
+    let indices = read_u64_le(block) >> 16;
+    std::array::from_fn(|index| palette[((indices >> (3 * index)) & 7) as usize])
+}
+
+fn bc2(block: &[u8]) -> Block {
+    let alpha = read_u64_le(block);
+    let mut texels = bc1_colors(&block[8..], true);
+    for (index, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (4 * index)) & 0xf) as u8 * 17;
+    }
+    texels
+}
+
+fn bc3(block: &[u8]) -> Block {
+    let alpha = bc4_channel(block, false);
+    let mut texels = bc1_colors(&block[8..], true);
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha;
+    }
+    texels
+}
+
+fn bc4(block: &[u8], signed: bool) -> Block {
+    bc4_channel(block, signed).map(|red| [red, 0, 0, opaque(signed)])
+}
+
+fn bc5(block: &[u8], signed: bool) -> Block {
+    let red = bc4_channel(block, signed);
+    let green = bc4_channel(&block[8..], signed);
+    let mut texels = [[0, 0, 0, opaque(signed)]; 16];
+    for (index, texel) in texels.iter_mut().enumerate() {
+        texel[0] = red[index];
+        texel[1] = green[index];
+    }
+    texels
+}
+
+// Interpolation weights out of 64 for 2, 3 and 4 bit BC6H/BC7 indices
+fn bc_weight(index_bits: u32, index: i32) -> i32 {
+    const WEIGHTS_2: [i32; 4] = [0, 21, 43, 64];
+    const WEIGHTS_3: [i32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+    const WEIGHTS_4: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+    match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+fn bc_interpolate(endpoint0: i32, endpoint1: i32, weight: i32) -> i32 {
+    (endpoint0 * (64 - weight) + endpoint1 * weight + 32) >> 6
+}
+
+// Which subset each texel of a BC6H/BC7 block belongs to
+fn bc_subsets(subset_count: usize, partition: usize) -> [usize; 16] {
+    std::array::from_fn(|texel| match subset_count {
+        1 => 0,
+        2 => (BC_PARTITIONS_2[partition] >> texel) as usize & 1,
+        _ => BC_PARTITIONS_3[partition][texel] as usize,
+    })
+}
+
+// The first texel of each subset, whose index drops its high bit
+fn bc_anchors(subset_count: usize, partition: usize) -> [usize; 3] {
+    match subset_count {
+        1 => [0, 0, 0],
+        2 => [0, BC_ANCHORS_2[partition] as usize, 0],
+        _ => [
+            0,
+            BC_ANCHORS_3_SECOND[partition] as usize,
+            BC_ANCHORS_3_THIRD[partition] as usize,
+        ],
+    }
+}
+
+fn bc_indices(reader: &mut BitReader, index_bits: u32, anchors: [usize; 3]) -> [i32; 16] {
+    std::array::from_fn(|texel| {
+        let anchor = anchors.contains(&texel);
+        reader.read(index_bits - anchor as u32)
+    })
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    // One p-bit per endpoint, or one shared by both endpoints of a subset
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const fn bc7_mode(
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    [color_bits, alpha_bits]: [u32; 2],
+    [endpoint_pbits, shared_pbits]: [bool; 2],
+    [index_bits, secondary_index_bits]: [u32; 2],
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_pbits,
+        shared_pbits,
+        index_bits,
+        secondary_index_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, [4, 0], [true, false], [3, 0]),
+    bc7_mode(2, 6, 0, 0, [6, 0], [false, true], [3, 0]),
+    bc7_mode(3, 6, 0, 0, [5, 0], [false, false], [2, 0]),
+    bc7_mode(2, 6, 0, 0, [7, 0], [true, false], [2, 0]),
+    bc7_mode(1, 0, 2, 1, [5, 6], [false, false], [2, 3]),
+    bc7_mode(1, 0, 2, 0, [7, 8], [false, false], [2, 2]),
+    bc7_mode(1, 0, 0, 0, [7, 7], [true, false], [4, 0]),
+    bc7_mode(2, 6, 0, 0, [5, 5], [true, false], [2, 0]),
+];
+
+fn bc7(block: &[u8]) -> Block {
+    let mut reader = BitReader::new(block);
+    // The mode is the number of zero bits before the first set bit, and
+    // blocks without one decode to transparent black
+    let Some(mode) = (0..8).find(|_| reader.read(1) == 1) else {
+        return [[0; 4]; 16];
+    };
+    let mode = &BC7_MODES[mode];
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // Endpoints are stored channel by channel
+    let endpoint_count = 2 * mode.subsets;
+    let mut endpoints = [[0; 4]; 6];
+    for channel in 0..4 {
+        let bits = if channel < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = reader.read(bits);
+        }
+    }
+
+    let mut pbits = [0; 6];
+    if mode.endpoint_pbits {
+        for pbit in &mut pbits[..endpoint_count] {
+            *pbit = reader.read(1);
+        }
+    } else if mode.shared_pbits {
+        for subset in 0..mode.subsets {
+            let pbit = reader.read(1);
+            pbits[2 * subset] = pbit;
+            pbits[2 * subset + 1] = pbit;
+        }
+    }
+
+    // P-bits become each channel's lowest bit before expanding to 8 bits
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+    for (endpoint, pbit) in endpoints[..endpoint_count].iter_mut().zip(pbits) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let bits = if channel < 3 {
+                mode.color_bits
+            } else {
+                mode.alpha_bits
+            };
+            *value = match (bits, has_pbits) {
+                (0, _) => 255,
+                (_, true) => extend((*value << 1) | pbit, bits + 1),
+                (_, false) => extend(*value, bits),
+            };
+        }
+    }
+
+    let anchors = bc_anchors(mode.subsets, partition);
+    let indices = bc_indices(&mut reader, mode.index_bits, anchors);
+    let secondary_indices = match mode.secondary_index_bits {
+        0 => indices,
+        bits => bc_indices(&mut reader, bits, [0; 3]),
+    };
+    let secondary_bits = match mode.secondary_index_bits {
+        0 => mode.index_bits,
+        bits => bits,
+    };
+
+    // Modes 4 and 5 have separate color and alpha indices, and mode 4 can
+    // swap which of them is the wider one
+    let ((color_indices, color_bits), (alpha_indices, alpha_bits)) = match index_selection {
+        0 => (
+            (indices, mode.index_bits),
+            (secondary_indices, secondary_bits),
+        ),
+        _ => (
+            (secondary_indices, secondary_bits),
+            (indices, mode.index_bits),
+        ),
+    };
+
+    let subsets = bc_subsets(mode.subsets, partition);
+    std::array::from_fn(|texel| {
+        let endpoint0 = endpoints[2 * subsets[texel]];
+        let endpoint1 = endpoints[2 * subsets[texel] + 1];
+        let color_weight = bc_weight(color_bits, color_indices[texel]);
+        let alpha_weight = bc_weight(alpha_bits, alpha_indices[texel]);
+        let mut rgba: [u8; 4] = std::array::from_fn(|channel| {
+            let weight = if channel < 3 {
+                color_weight
+            } else {
+                alpha_weight
+            };
+            bc_interpolate(endpoint0[channel], endpoint1[channel], weight) as u8
+        });
+        // Rotation swaps alpha with one of the color channels
+        if rotation > 0 {
+            rgba.swap(rotation as usize - 1, 3);
+        }
+        rgba
+    })
+}
+
+// A BC6H mode, its endpoint precision and the bits of each channel's
+// deltas from the first endpoint
+struct Bc6hMode {
+    mode: i32,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    transformed: bool,
+    layout: &'static str,
+}
+
+// Where each mode stores its endpoints and partition, following the layout
+// tables of the BC6H specification. Fields are read from the rightmost bit of
+// each range, so `rw[9:0]` starts at bit 0 and `rw[10:15]` at bit 15.
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        mode: 0b00,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        transformed: true,
+        layout: "gy[4] by[4] bz[4] rw[9:0] gw[9:0] bw[9:0] rx[4:0] gz[4] gy[3:0] gx[4:0] \
+                 bz[0] gz[3:0] bx[4:0] bz[1] by[3:0] ry[4:0] bz[2] rz[4:0] bz[3] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b01,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        transformed: true,
+        layout: "gy[5] gz[4] gz[5] rw[6:0] bz[0] bz[1] by[4] gw[6:0] by[5] bz[2] gy[4] \
+                 bw[6:0] bz[3] bz[5] bz[4] rx[5:0] gy[3:0] gx[5:0] gz[3:0] bx[5:0] by[3:0] \
+                 ry[5:0] rz[5:0] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b00010,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        transformed: true,
+        layout: "rw[9:0] gw[9:0] bw[9:0] rx[4:0] rw[10] gy[3:0] gx[3:0] gw[10] bz[0] \
+                 gz[3:0] bx[3:0] bw[10] bz[1] by[3:0] ry[4:0] bz[2] rz[4:0] bz[3] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b00110,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        transformed: true,
+        layout: "rw[9:0] gw[9:0] bw[9:0] rx[3:0] rw[10] gz[4] gy[3:0] gx[4:0] gw[10] \
+                 gz[3:0] bx[3:0] bw[10] bz[1] by[3:0] ry[3:0] bz[0] bz[2] rz[3:0] gy[4] \
+                 bz[3] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b01010,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        transformed: true,
+        layout: "rw[9:0] gw[9:0] bw[9:0] rx[3:0] rw[10] by[4] gy[3:0] gx[3:0] gw[10] \
+                 bz[0] gz[3:0] bx[4:0] bw[10] by[3:0] ry[3:0] bz[1] bz[2] rz[3:0] bz[4] \
+                 bz[3] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b01110,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        transformed: true,
+        layout: "rw[8:0] by[4] gw[8:0] gy[4] bw[8:0] bz[4] rx[4:0] gz[4] gy[3:0] gx[4:0] \
+                 bz[0] gz[3:0] bx[4:0] bz[1] by[3:0] ry[4:0] bz[2] rz[4:0] bz[3] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b10010,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        transformed: true,
+        layout: "rw[7:0] gz[4] by[4] gw[7:0] bz[2] gy[4] bw[7:0] bz[3] bz[4] rx[5:0] \
+                 gy[3:0] gx[4:0] bz[0] gz[3:0] bx[4:0] bz[1] by[3:0] ry[5:0] rz[5:0] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b10110,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        transformed: true,
+        layout: "rw[7:0] bz[0] by[4] gw[7:0] gy[5] gy[4] bw[7:0] gz[5] bz[4] rx[4:0] \
+                 gz[4] gy[3:0] gx[5:0] gz[3:0] bx[4:0] bz[1] by[3:0] ry[4:0] bz[2] rz[4:0] \
+                 bz[3] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b11010,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        transformed: true,
+        layout: "rw[7:0] bz[1] by[4] gw[7:0] by[5] gy[4] bw[7:0] bz[5] bz[4] rx[4:0] \
+                 gz[4] gy[3:0] gx[4:0] bz[0] gz[3:0] bx[5:0] by[3:0] ry[4:0] bz[2] rz[4:0] \
+                 bz[3] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b11110,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        transformed: false,
+        layout: "rw[5:0] gz[4] bz[0] bz[1] by[4] gw[5:0] gy[5] by[5] bz[2] gy[4] bw[5:0] \
+                 gz[5] bz[3] bz[5] bz[4] rx[5:0] gy[3:0] gx[5:0] gz[3:0] bx[5:0] by[3:0] \
+                 ry[5:0] rz[5:0] d[4:0]",
+    },
+    Bc6hMode {
+        mode: 0b00011,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        transformed: false,
+        layout: "rw[9:0] gw[9:0] bw[9:0] rx[9:0] gx[9:0] bx[9:0]",
+    },
+    Bc6hMode {
+        mode: 0b00111,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        transformed: true,
+        layout: "rw[9:0] gw[9:0] bw[9:0] rx[8:0] rw[10] gx[8:0] gw[10] bx[8:0] bw[10]",
+    },
+    Bc6hMode {
+        mode: 0b01011,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        transformed: true,
+        layout: "rw[9:0] gw[9:0] bw[9:0] rx[7:0] rw[10:11] gx[7:0] gw[10:11] bx[7:0] \
+                 bw[10:11]",
+    },
+    Bc6hMode {
+        mode: 0b01111,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        transformed: true,
+        layout: "rw[9:0] gw[9:0] bw[9:0] rx[3:0] rw[10:15] gx[3:0] gw[10:15] bx[3:0] \
+                 bw[10:15]",
+    },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    (value << (32 - bits)) >> (32 - bits)
+}
+
+// Scales an endpoint to 16 bits, keeping the extremes exact
+fn bc6h_unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        let magnitude = value.abs();
+        let unquantized = if bits >= 16 {
+            magnitude
+        } else if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        unquantized * value.signum()
+    } else if bits >= 15 {
+        value
+    } else if value == 0 {
+        0
+    } else if value == (1 << bits) - 1 {
+        0xffff
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+// Rescales an interpolated value to the bits of a finite half float
+fn bc6h_half(value: i32, signed: bool) -> u16 {
+    match (signed, value < 0) {
+        (false, _) => ((value * 31) >> 6) as u16,
+        (true, false) => ((value * 31) >> 5) as u16,
+        (true, true) => 0x8000 | ((-value * 31) >> 5) as u16,
+    }
+}
+
+// Decodes to RGBA16Float texels with an alpha of one
+fn bc6h(block: &[u8], signed: bool) -> [[u8; 8]; 16] {
+    const ONE: [u8; 2] = half::f16::ONE.to_bits().to_le_bytes();
+
+    let mut reader = BitReader::new(block);
+    let mut mode = reader.read(2);
+    if mode > 1 {
+        mode |= reader.read(3) << 2;
+    }
+    // Reserved modes decode to black
+    let Some(mode) = BC6H_MODES.iter().find(|candidate| candidate.mode == mode) else {
+        let mut black = [0; 8];
+        black[6..].copy_from_slice(&ONE);
+        return [black; 16];
+    };
+
+    // Endpoints w, x, y and z, each with red, green and blue
+    let mut endpoints = [[0; 3]; 4];
+    let mut partition = 0;
+    for field in mode.layout.split_whitespace() {
+        let (name, range) = field.trim_end_matches(']').split_once('[').unwrap();
+        let (first, last) = match range.split_once(':') {
+            Some((first, last)) => (first.parse::<u32>().unwrap(), last.parse().unwrap()),
+            None => {
+                let bit = range.parse().unwrap();
+                (bit, bit)
+            }
+        };
+        for step in 0..=first.abs_diff(last) {
+            let bit = if last <= first {
+                last + step
+            } else {
+                last - step
+            };
+            let value = reader.read(1) << bit;
+            match name.as_bytes() {
+                b"d" => partition |= value,
+                [channel, endpoint] => {
+                    let channel = match channel {
+                        b'r' => 0,
+                        b'g' => 1,
+                        _ => 2,
+                    };
+                    endpoints[(endpoint - b'w') as usize][channel] |= value;
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Transformed modes store the other endpoints as deltas from the first
+    let subset_count = if mode.mode & 0b11 == 0b11 { 1 } else { 2 };
+    let endpoint_count = 2 * subset_count;
+    for channel in 0..3 {
+        if signed {
+            endpoints[0][channel] = sign_extend(endpoints[0][channel], mode.endpoint_bits);
+        }
+        let base = endpoints[0][channel];
+        for endpoint in &mut endpoints[1..endpoint_count] {
+            let mut value = endpoint[channel];
+            if mode.transformed || signed {
+                value = sign_extend(value, mode.delta_bits[channel]);
+            }
+            if mode.transformed {
+                value = (base + value) & ((1 << mode.endpoint_bits) - 1);
+                if signed {
+                    value = sign_extend(value, mode.endpoint_bits);
+                }
+            }
+            endpoint[channel] = value;
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        for value in endpoint.iter_mut() {
+            *value = bc6h_unquantize(*value, mode.endpoint_bits, signed);
+        }
+    }
+
+    let partition = partition as usize;
+    let index_bits = if subset_count == 1 { 4 } else { 3 };
+    let indices = bc_indices(&mut reader, index_bits, bc_anchors(subset_count, partition));
+    let subsets = bc_subsets(subset_count, partition);
+    std::array::from_fn(|texel| {
+        let endpoint0 = endpoints[2 * subsets[texel]];
+        let endpoint1 = endpoints[2 * subsets[texel] + 1];
+        let weight = bc_weight(index_bits, indices[texel]);
+        let mut rgba = [0; 8];
+        for channel in 0..3 {
+            let value = bc_interpolate(endpoint0[channel], endpoint1[channel], weight);
+            rgba[2 * channel..2 * channel + 2]
+                .copy_from_slice(&bc6h_half(value, signed).to_le_bytes());
+        }
+        rgba[6..].copy_from_slice(&ONE);
+        rgba
+    })
+}
+
+// ASTC blocks decode to magenta when they are malformed or use HDR
+// endpoints, which an LDR decoder doesn't support
+const ASTC_ERROR: [u8; 4] = [255, 0, 255, 255];
+
+// Decodes one ASTC LDR block of `block_width` by `block_height` texels, in
+// row-major order. Channels are interpolated at 16 bits and rounded to 8,
+// except for sRGB blocks, which keep the top 8 bits.
+fn astc(block: &[u8], (block_width, block_height): (usize, usize), srgb: bool) -> Vec<[u8; 4]> {
+    let texel_count = block_width * block_height;
+    let bits = u128::from_le_bytes(block[..16].try_into().unwrap());
+
+    // Void extent blocks hold a single 16 bit color, and the texel
+    // coordinates it extends over, or all ones
+    if bits & 0x1ff == 0x1fc {
+        let [low_s, high_s, low_t, high_t] =
+            std::array::from_fn(|index| (bits >> (12 + 13 * index)) as u32 & 0x1fff);
+        let all_ones = [low_s, high_s, low_t, high_t] == [0x1fff; 4];
+        let hdr = bits >> 9 & 1 == 1;
+        if hdr || (!all_ones && (low_s >= high_s || low_t >= high_t)) {
+            return vec![ASTC_ERROR; texel_count];
+        }
+        let color = std::array::from_fn(|channel| {
+            astc_to_u8((bits >> (64 + 16 * channel)) as u16 as u32, srgb)
+        });
+        return vec![color; texel_count];
+    }
+    let Some(block) = AstcBlock::read(bits, (block_width, block_height)) else {
+        return vec![ASTC_ERROR; texel_count];
+    };
+
+    let color_end = block.color_start + ise_bit_count(block.color_count, block.color_range);
+    let mut colors = AstcBitReader::new(bits, block.color_start, color_end);
+    let color_values = ise_decode(&mut colors, block.color_count, block.color_range)
+        .into_iter()
+        .map(|value| unquantize_color(value, block.color_range))
+        .collect::<Vec<_>>();
+    let mut endpoints = [[[0; 4]; 2]; 4];
+    let mut values = color_values.as_slice();
+    for (partition, mode) in block.endpoint_modes[..block.partitions].iter().enumerate() {
+        let count = 2 * (*mode as usize / 4 + 1);
+        // Partitions with HDR endpoints decode to the error color
+        endpoints[partition] = astc_endpoints(*mode, &values[..count]).unwrap_or([ASTC_ERROR; 2]);
+        values = &values[count..];
+    }
+
+    // Weights are stored from the top of the block down
+    let mut weight_reader = AstcBitReader::new(bits.reverse_bits(), 0, block.weight_bits);
+    let planes = 1 + block.dual_plane as usize;
+    let (grid_width, grid_height) = block.weight_grid;
+    let weights = ise_decode(
+        &mut weight_reader,
+        grid_width * grid_height * planes,
+        block.weight_range,
+    )
+    .into_iter()
+    .map(|value| unquantize_weight(value, block.weight_range))
+    .collect::<Vec<_>>();
+
+    let colors = AstcColors {
+        partitions: block.partitions,
+        partition_index: block.partition_index,
+        endpoints,
+        weight_grid: block.weight_grid,
+        weights,
+        plane2_channel: block.dual_plane.then_some(block.plane2_channel),
+    };
+    astc_interpolate(&colors, (block_width, block_height), srgb)
+}
+
+// The unquantized endpoints and weights of an ASTC block
+struct AstcColors {
+    partitions: usize,
+    partition_index: u32,
+    endpoints: [[[u8; 4]; 2]; 4],
+    weight_grid: (usize, usize),
+    // In the range 0..=64, interleaved by plane
+    weights: Vec<u32>,
+    // The channel that takes the second plane of weights, if there is one
+    plane2_channel: Option<usize>,
+}
+
+fn astc_interpolate(
+    colors: &AstcColors,
+    (block_width, block_height): (usize, usize),
+    srgb: bool,
+) -> Vec<[u8; 4]> {
+    let texel_count = block_width * block_height;
+    let planes = 1 + colors.plane2_channel.is_some() as usize;
+    let (grid_width, grid_height) = colors.weight_grid;
+    let small_block = texel_count < 31;
+    let infill_x = (1024 + block_width / 2) / (block_width - 1).max(1);
+    let infill_y = (1024 + block_height / 2) / (block_height - 1).max(1);
+    (0..texel_count)
+        .map(|texel| {
+            let (x, y) = (texel % block_width, texel / block_width);
+            let partition = match colors.partitions {
+                1 => 0,
+                count => astc_partition(colors.partition_index, x, y, count, small_block),
+            };
+            let [endpoint0, endpoint1] = colors.endpoints[partition];
+
+            // Bilinear infill from the weight grid to the texel
+            let grid_x = (infill_x * x * (grid_width - 1) + 32) >> 6;
+            let grid_y = (infill_y * y * (grid_height - 1) + 32) >> 6;
+            let (index_x, fraction_x) = (grid_x >> 4, grid_x & 0xf);
+            let (index_y, fraction_y) = (grid_y >> 4, grid_y & 0xf);
+            let factor11 = (fraction_x * fraction_y + 8) >> 4;
+            let factors = [
+                16 + factor11 - fraction_x - fraction_y,
+                fraction_x - factor11,
+                fraction_y - factor11,
+                factor11,
+            ];
+            let weight = |plane: usize| {
+                let offsets = [0, 1, grid_width, grid_width + 1];
+                let base = index_y * grid_width + index_x;
+                let sum: u32 = offsets
+                    .iter()
+                    .zip(factors)
+                    .filter(|(_, factor)| *factor > 0)
+                    .map(|(offset, factor)| {
+                        colors.weights[planes * (base + offset) + plane] * factor as u32
+                    })
+                    .sum();
+                (sum + 8) >> 4
+            };
+            let (weight0, weight1) = (weight(0), weight(planes - 1));
+
+            std::array::from_fn(|channel| {
+                let weight = match colors.plane2_channel == Some(channel) {
+                    true => weight1,
+                    false => weight0,
+                };
+                let expand = |value: u8| match srgb {
+                    true => (value as u32) << 8 | 0x80,
+                    false => value as u32 * 257,
+                };
+                let (value0, value1) = (expand(endpoint0[channel]), expand(endpoint1[channel]));
+                astc_to_u8((value0 * (64 - weight) + value1 * weight + 32) >> 6, srgb)
+            })
+        })
+        .collect()
+}
+
+// Rounds an interpolated 16 bit channel to 8 bits, or keeps the top 8 bits
+// of sRGB channels
+fn astc_to_u8(value: u32, srgb: bool) -> u8 {
+    match srgb {
+        true => (value >> 8) as u8,
+        false => ((value * 255 + 32767) / 65535) as u8,
+    }
+}
+
+// The layout of an ASTC block that isn't a void extent
+struct AstcBlock {
+    weight_grid: (usize, usize),
+    weight_range: u32,
+    weight_bits: usize,
+    dual_plane: bool,
+    plane2_channel: usize,
+    partitions: usize,
+    partition_index: u32,
+    endpoint_modes: [u32; 4],
+    color_start: usize,
+    color_count: usize,
+    color_range: u32,
+}
+
+impl AstcBlock {
+    // None when the block is malformed
+    fn read(bits: u128, (block_width, block_height): (usize, usize)) -> Option<Self> {
+        let mode = (bits & 0x7ff) as u32;
+        let field = |low_bit: u32, count: u32| (mode >> low_bit) & ((1 << count) - 1);
+        let (a, mut high_precision, mut dual_plane) = (field(5, 2), field(9, 1), field(10, 1));
+        let mut range_bits = field(4, 1);
+        let (grid_width, grid_height) = if field(0, 2) != 0 {
+            range_bits |= field(0, 2) << 1;
+            let b = field(7, 2);
+            match field(2, 2) {
+                0 => (b + 4, a + 2),
+                1 => (b + 8, a + 2),
+                2 => (a + 2, b + 8),
+                _ if field(8, 1) == 1 => ((b & 1) + 2, a + 2),
+                _ => (a + 2, (b & 1) + 6),
+            }
+        } else {
+            range_bits |= field(2, 2) << 1;
+            if field(2, 2) == 0 {
+                return None;
+            }
+            match field(7, 2) {
+                0 => (12, a + 2),
+                1 => (a + 2, 12),
+                2 => {
+                    (high_precision, dual_plane) = (0, 0);
+                    (a + 6, field(9, 2) + 6)
+                }
+                _ => match a {
+                    0 => (6, 10),
+                    1 => (10, 6),
+                    _ => return None,
+                },
+            }
+        };
+        let (grid_width, grid_height) = (grid_width as usize, grid_height as usize);
+        let dual_plane = dual_plane == 1;
+        let weight_range = ASTC_RANGES[(range_bits - 2 + 6 * high_precision) as usize];
+        let weight_count = grid_width * grid_height * (1 + dual_plane as usize);
+        let weight_bits = ise_bit_count(weight_count, weight_range);
+        if grid_width > block_width
+            || grid_height > block_height
+            || weight_count > 64
+            || !(24..=96).contains(&weight_bits)
+        {
+            return None;
+        }
+
+        let partitions = (bits >> 11 & 3) as usize + 1;
+        if dual_plane && partitions == 4 {
+            return None;
+        }
+        let mut below_weights = 128 - weight_bits;
+        let (partition_index, endpoint_modes, color_start) = if partitions == 1 {
+            (0, [(bits >> 13 & 0xf) as u32; 4], 17)
+        } else {
+            // Mixed endpoint modes continue below the weights
+            let extra_bits = 3 * partitions - 4;
+            let high = (bits >> (below_weights - extra_bits)) as u32 & ((1 << extra_bits) - 1);
+            let encoded = (bits >> 23 & 0x3f) as u32 | high << 6;
+            let modes = match encoded & 3 {
+                0 => [encoded >> 2 & 0xf; 4],
+                class => {
+                    below_weights -= extra_bits;
+                    std::array::from_fn(|partition| {
+                        let c = encoded >> (2 + partition) & 1;
+                        let m = encoded >> (2 + partitions + 2 * partition) & 3;
+                        (class - 1 + c) << 2 | m
+                    })
+                }
+            };
+            ((bits >> 13 & 0x3ff) as u32, modes, 29)
+        };
+        let plane2_channel = match dual_plane {
+            true => {
+                below_weights -= 2;
+                (bits >> below_weights & 3) as usize
+            }
+            false => 0,
+        };
+
+        // Endpoints take the widest range that fits between the header and
+        // the weights, which may not be narrower than six values
+        let color_count = endpoint_modes[..partitions]
+            .iter()
+            .map(|mode| 2 * (*mode as usize / 4 + 1))
+            .sum();
+        let color_bits = below_weights.checked_sub(color_start)?;
+        let color_range = *ASTC_RANGES
+            .iter()
+            .rev()
+            .find(|range| ise_bit_count(color_count, **range) <= color_bits)?;
+        if color_count > 18 || color_range < 6 {
+            return None;
+        }
+
+        Some(Self {
+            weight_grid: (grid_width, grid_height),
+            weight_range,
+            weight_bits,
+            dual_plane,
+            plane2_channel,
+            partitions,
+            partition_index,
+            endpoint_modes,
+            color_start,
+            color_count,
+            color_range,
+        })
+    }
+}
+
+// The ranges integer sequences can encode values in, as a power of two, three
+// or five times a power of two
+const ASTC_RANGES: [u32; 21] = [
+    2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256,
+];
+
+// Low bits per value, and whether a trit or a quint is packed on top of them
+fn ise_layout(range: u32) -> (u32, u32) {
+    match range {
+        range if range % 3 == 0 => ((range / 3).trailing_zeros(), 3),
+        range if range % 5 == 0 => ((range / 5).trailing_zeros(), 5),
+        range => (range.trailing_zeros(), 1),
+    }
+}
+
+fn ise_bit_count(count: usize, range: u32) -> usize {
+    let (bits, packing) = ise_layout(range);
+    let low_bits = count * bits as usize;
+    match packing {
+        3 => low_bits + (8 * count).div_ceil(5),
+        5 => low_bits + (7 * count).div_ceil(3),
+        _ => low_bits,
+    }
+}
+
+// Reads bits from the lowest up, returning zeros past `end`
+struct AstcBitReader {
+    bits: u128,
+    position: usize,
+    end: usize,
+}
+
+impl AstcBitReader {
+    fn new(bits: u128, start: usize, end: usize) -> Self {
+        Self {
+            bits,
+            position: start,
+            end,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for bit in 0..count {
+            if self.position < self.end.min(128) {
+                value |= (((self.bits >> self.position) & 1) as u32) << bit;
+            }
+            self.position += 1;
+        }
+        value
+    }
+}
+
+// Decodes an integer sequence of `count` values in `range`, returned as the
+// trit or quint above the low bits
+fn ise_decode(reader: &mut AstcBitReader, count: usize, range: u32) -> Vec<(u32, u32)> {
+    let (bits, packing) = ise_layout(range);
+    let mut values = Vec::with_capacity(count);
+    while values.len() < count {
+        match packing {
+            3 => {
+                // Five values share eight bits of trits, interleaved with
+                // their low bits
+                let mut low = [0; 5];
+                let mut packed = 0;
+                for (index, bit_count) in [2, 2, 1, 2, 1].into_iter().enumerate() {
+                    low[index] = reader.read(bits);
+                    let shift = [0, 2, 4, 5, 7][index];
+                    packed |= reader.read(bit_count) << shift;
+                }
+                let trits = decode_trits(packed);
+                values.extend((0..5).map(|index| (trits[index], low[index])));
+            }
+            5 => {
+                let mut low = [0; 3];
+                let mut packed = 0;
+                for (index, bit_count) in [3, 2, 2].into_iter().enumerate() {
+                    low[index] = reader.read(bits);
+                    let shift = [0, 3, 5][index];
+                    packed |= reader.read(bit_count) << shift;
+                }
+                let quints = decode_quints(packed);
+                values.extend((0..3).map(|index| (quints[index], low[index])));
+            }
+            _ => values.push((0, reader.read(bits))),
+        }
+    }
+    values.truncate(count);
+    values
+}
+
+fn decode_trits(packed: u32) -> [u32; 5] {
+    let bit = |index: u32| packed >> index & 1;
+    let (c, t4, t3) = if packed >> 2 & 7 == 7 {
+        ((packed >> 5 & 7) << 2 | packed & 3, 2, 2)
+    } else if packed >> 5 & 3 == 3 {
+        (packed & 0x1f, 2, bit(7))
+    } else {
+        (packed & 0x1f, bit(7), packed >> 5 & 3)
+    };
+    let c_bit = |index: u32| c >> index & 1;
+    let (t2, t1, t0) = if c & 3 == 3 {
+        (2, c_bit(4), c_bit(3) << 1 | (c_bit(2) & !c_bit(3) & 1))
+    } else if c >> 2 & 3 == 3 {
+        (2, 2, c & 3)
+    } else {
+        (
+            c_bit(4),
+            c >> 2 & 3,
+            c_bit(1) << 1 | (c_bit(0) & !c_bit(1) & 1),
+        )
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(packed: u32) -> [u32; 3] {
+    let bit = |index: u32| packed >> index & 1;
+    if packed >> 1 & 3 == 3 && packed >> 5 & 3 == 0 {
+        let q2 = bit(0) << 2 | (bit(4) & !bit(0) & 1) << 1 | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (q2, c) = if packed >> 1 & 3 == 3 {
+        (
+            4,
+            (packed >> 3 & 3) << 3 | (!(packed >> 5) & 3) << 1 | bit(0),
+        )
+    } else {
+        (packed >> 5 & 3, packed & 0x1f)
+    };
+    let (q1, q0) = if c & 7 == 5 {
+        (4, c >> 3 & 3)
+    } else {
+        (c >> 3 & 3, c & 7)
+    };
+    [q0, q1, q2]
+}
+
+// Expands an endpoint value to 8 bits. Values with a trit or quint scatter
+// their low bits over a 9 bit pattern, and an odd lowest bit mirrors the
+// result.
+fn unquantize_color(value: (u32, u32), range: u32) -> u8 {
+    let (bits, packing) = ise_layout(range);
+    let (packed, low) = value;
+    if packing == 1 {
+        return replicate(low, bits, 8) as u8;
+    }
+    let mirror = if low & 1 == 1 { 0x1ff } else { 0 };
+    let [a, b, c, d, e] = std::array::from_fn(|index| low >> (index + 1) & 1);
+    let (step, pattern) = match (packing, bits) {
+        (3, 1) => (204, 0),
+        (5, 1) => (113, 0),
+        (3, 2) => (93, a * 0b100010110),
+        (5, 2) => (54, a * 0b100001100),
+        (3, 3) => (44, b * 0b100001010 + a * 0b010000101),
+        (5, 3) => (26, b * 0b100000101 + a * 0b010000010),
+        (3, 4) => (22, c * 0b100000100 + b * 0b010000010 + a * 0b001000001),
+        (5, 4) => (13, c * 0b100000010 + b * 0b010000001 + a * 0b001000000),
+        (3, 5) => (
+            11,
+            d * 0b100000010 + c * 0b010000001 + b * 0b001000000 + a * 0b000100000,
+        ),
+        (5, 5) => (
+            6,
+            d * 0b100000001 + c * 0b010000000 + b * 0b001000000 + a * 0b000100000,
+        ),
+        _ => (
+            5,
+            e * 0b100000001 + d * 0b010000000 + c * 0b001000000 + b * 0b000100000 + a * 0b000010000,
+        ),
+    };
+    let unquantized = (packed * step + pattern) ^ mirror;
+    ((mirror & 0x80) | unquantized >> 2) as u8
+}
+
+// Expands a weight to the range 0..=64
+fn unquantize_weight(value: (u32, u32), range: u32) -> u32 {
+    let (bits, packing) = ise_layout(range);
+    let (packed, low) = value;
+    let unquantized = match (packing, bits) {
+        (1, _) => replicate(low, bits, 6),
+        (3, 0) => return packed * 32,
+        (5, 0) => return packed * 16,
+        _ => {
+            let mirror = if low & 1 == 1 { 0x7f } else { 0 };
+            let [a, b] = std::array::from_fn(|index| low >> (index + 1) & 1);
+            let (step, pattern) = match (packing, bits) {
+                (3, 1) => (50, 0),
+                (5, 1) => (28, 0),
+                (3, 2) => (23, a * 0b1000101),
+                (5, 2) => (13, a * 0b1000010),
+                _ => (11, b * 0b1000010 + a * 0b0100001),
+            };
+            let unquantized = (packed * step + pattern) ^ mirror;
+            (mirror & 0x20) | unquantized >> 2
+        }
+    };
+    unquantized + (unquantized > 32) as u32
+}
+
+// Repeats the `bits` bits of `value` until they fill `width` bits
+fn replicate(value: u32, bits: u32, width: u32) -> u32 {
+    if bits == 0 {
+        return 0;
+    }
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < width {
+        result = result << bits | value;
+        filled += bits;
+    }
+    result >> (filled - width)
+}
+
+// The two 8 bit RGBA endpoints of a partition, or None for HDR modes
+fn astc_endpoints(mode: u32, values: &[u8]) -> Option<[[u8; 4]; 2]> {
+    let v: Vec<i32> = values.iter().map(|&value| value as i32).collect();
+    let rgba = |red: i32, green: i32, blue: i32, alpha: i32| {
+        [red, green, blue, alpha].map(|value| value.clamp(0, 255) as u8)
+    };
+    // Moves half of blue into red and green, for endpoints stored swapped
+    let blue_contract = |red: i32, green: i32, blue: i32, alpha: i32| {
+        rgba((red + blue) >> 1, (green + blue) >> 1, blue, alpha)
+    };
+    // Moves the top bit of an offset into its base, leaving a signed 6 bit
+    // offset
+    let transfer = |offset: i32, base: i32| {
+        let base = (base >> 1) | (offset & 0x80);
+        let offset = (offset >> 1) & 0x3f;
+        let offset = if offset & 0x20 != 0 {
+            offset - 0x40
+        } else {
+            offset
+        };
+        (offset, base)
+    };
+    Some(match mode {
+        0 => [rgba(v[0], v[0], v[0], 255), rgba(v[1], v[1], v[1], 255)],
+        1 => {
+            let low = (v[0] >> 2) | (v[1] & 0xc0);
+            let high = (low + (v[1] & 0x3f)).min(255);
+            [rgba(low, low, low, 255), rgba(high, high, high, 255)]
+        }
+        4 => [rgba(v[0], v[0], v[0], v[2]), rgba(v[1], v[1], v[1], v[3])],
+        5 => {
+            let (luminance_offset, luminance) = transfer(v[1], v[0]);
+            let (alpha_offset, alpha) = transfer(v[3], v[2]);
+            let (high, high_alpha) = (luminance + luminance_offset, alpha + alpha_offset);
+            [
+                rgba(luminance, luminance, luminance, alpha),
+                rgba(high, high, high, high_alpha),
+            ]
+        }
+        6 | 10 => {
+            let scale = |value: i32| (value * v[3]) >> 8;
+            let (alpha0, alpha1) = if mode == 10 { (v[4], v[5]) } else { (255, 255) };
+            [
+                rgba(scale(v[0]), scale(v[1]), scale(v[2]), alpha0),
+                rgba(v[0], v[1], v[2], alpha1),
+            ]
+        }
+        8 | 12 => {
+            let (alpha0, alpha1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [
+                    rgba(v[0], v[2], v[4], alpha0),
+                    rgba(v[1], v[3], v[5], alpha1),
+                ]
+            } else {
+                [
+                    blue_contract(v[1], v[3], v[5], alpha1),
+                    blue_contract(v[0], v[2], v[4], alpha0),
+                ]
+            }
+        }
+        9 | 13 => {
+            let (red_offset, red) = transfer(v[1], v[0]);
+            let (green_offset, green) = transfer(v[3], v[2]);
+            let (blue_offset, blue) = transfer(v[5], v[4]);
+            let (alpha_offset, alpha) = match mode {
+                13 => transfer(v[7], v[6]),
+                _ => (0, 255),
+            };
+            let (red1, green1, blue1) =
+                (red + red_offset, green + green_offset, blue + blue_offset);
+            let alpha1 = alpha + alpha_offset;
+            if red_offset + green_offset + blue_offset >= 0 {
+                [
+                    rgba(red, green, blue, alpha),
+                    rgba(red1, green1, blue1, alpha1),
+                ]
+            } else {
+                [
+                    blue_contract(red1, green1, blue1, alpha1),
+                    blue_contract(red, green, blue, alpha),
+                ]
+            }
+        }
+        _ => return None,
+    })
+}
+
+// Which partition a texel falls in, from a hash of the partition index
+fn astc_partition(index: u32, x: usize, y: usize, partitions: usize, small_block: bool) -> usize {
+    let (x, y) = match small_block {
+        true => (x as u32 * 2, y as u32 * 2),
+        false => (x as u32, y as u32),
+    };
+    let seed = index + (partitions as u32 - 1) * 1024;
+
+    let mut hash = seed;
+    hash ^= hash >> 15;
+    hash = hash.wrapping_sub(hash << 17);
+    hash = hash.wrapping_add(hash << 7);
+    hash = hash.wrapping_add(hash << 4);
+    hash ^= hash >> 5;
+    hash = hash.wrapping_add(hash << 16);
+    hash ^= hash >> 7;
+    hash ^= hash >> 3;
+    hash ^= hash << 6;
+    hash ^= hash >> 17;
+
+    let shifts = [0, 4, 8, 12, 16, 20, 24, 28];
+    let mut seeds = shifts.map(|shift| (hash >> shift) & 0xf);
+    seeds = seeds.map(|seed| seed * seed);
+    let (shift1, shift2) = match (seed & 1 == 1, seed & 2 == 2) {
+        (true, high) => (
+            if high { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        ),
+        (false, high) => (
+            if partitions == 3 { 6 } else { 5 },
+            if high { 4 } else { 5 },
+        ),
+    };
+    for (index, seed) in seeds.iter_mut().enumerate() {
+        *seed >>= if index % 2 == 0 { shift1 } else { shift2 };
+    }
+
+    let a = (seeds[0] * x + seeds[1] * y + (hash >> 14)) & 0x3f;
+    let b = (seeds[2] * x + seeds[3] * y + (hash >> 10)) & 0x3f;
+    let c = match partitions {
+        3.. => (seeds[4] * x + seeds[5] * y + (hash >> 6)) & 0x3f,
+        _ => 0,
+    };
+    let d = match partitions {
+        4 => (seeds[6] * x + seeds[7] * y + (hash >> 2)) & 0x3f,
+        _ => 0,
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+// Decodes a UASTC block, which maps one to one onto an ASTC 4x4 block
+// behind a mode code and the hints Basis Universal uses to transcode it.
+// The reserved mode and unknown patterns decode to the ASTC error color.
+fn uastc(block: &[u8], srgb: bool) -> Block {
+    let bits = u128::from_le_bytes(block[..16].try_into().unwrap());
+    let Some((mode, code_length)) = UASTC_MODE_CODES
+        .iter()
+        .enumerate()
+        .find(|(_, (code, length))| bits as u32 & ((1 << length) - 1) == *code)
+        .map(|(mode, (_, length))| (mode, *length as usize))
+    else {
+        return [ASTC_ERROR; 16];
+    };
+    let mut reader = AstcBitReader::new(bits, code_length, 128);
+    if mode == UASTC_SOLID_MODE {
+        let color = std::array::from_fn(|_| reader.read(8) as u8);
+        return [color; 16];
+    }
+
+    let mode = &UASTC_MODES[mode];
+    // RGB modes lead with a BC1 hint, ahead of the pattern
+    let leading_hint_bits = (mode.endpoint_mode == 8) as usize;
+    reader.position += leading_hint_bits;
+    let partition_index = match mode.subsets {
+        1 => Some(0),
+        2 if mode.three_subset_patterns => UASTC_PARTITIONS_2_OF_3
+            .get(reader.read(5) as usize)
+            .copied(),
+        2 => UASTC_PARTITIONS_2.get(reader.read(5) as usize).copied(),
+        _ => UASTC_PARTITIONS_3.get(reader.read(4) as usize).copied(),
+    };
+    let Some(partition_index) = partition_index else {
+        return [ASTC_ERROR; 16];
+    };
+    let plane2_channel = match mode.plane2_channel {
+        Some(UASTC_STORED_CHANNEL) => Some(reader.read(2) as usize),
+        channel => channel,
+    };
+    reader.position += mode.hint_bits - leading_hint_bits;
+
+    // Trits and quints are packed ahead of all the values' low bits
+    let value_count = 2 * (mode.endpoint_mode as usize / 4 + 1) * mode.subsets;
+    let (low_bits, packing) = ise_layout(mode.endpoint_range);
+    let (group_size, group_bits, last_group_bits): (usize, u32, &[u32]) = match packing {
+        3 => (5, 8, &[2, 4, 5, 7, 8]),
+        5 => (3, 7, &[3, 5, 7]),
+        _ => (1, 0, &[0]),
+    };
+    let group_count = value_count.div_ceil(group_size);
+    let mut groups: Vec<u32> = (0..group_count)
+        .map(|group| match group + 1 == group_count {
+            true => reader.read(last_group_bits[value_count - group * group_size - 1]),
+            false => reader.read(group_bits),
+        })
+        .collect();
+    let values: Vec<u8> = (0..value_count)
+        .map(|index| {
+            let low = reader.read(low_bits);
+            let group = &mut groups[index / group_size];
+            let packed = *group % packing;
+            *group /= packing;
+            unquantize_color((packed, low), mode.endpoint_range)
+        })
+        .collect();
+    let mut endpoints = [[[0; 4]; 2]; 4];
+    for (partition, values) in values.chunks_exact(value_count / mode.subsets).enumerate() {
+        endpoints[partition] = astc_endpoints(mode.endpoint_mode, values).unwrap();
+    }
+
+    // The first texel of each subset drops the top bit of its weights
+    let partitions: [usize; 16] = std::array::from_fn(|texel| match mode.subsets {
+        1 => 0,
+        count => astc_partition(partition_index, texel % 4, texel / 4, count, true),
+    });
+    let planes = 1 + plane2_channel.is_some() as usize;
+    let weights = (0..16 * planes)
+        .map(|index| {
+            let texel = index / planes;
+            let anchor = !partitions[..texel].contains(&partitions[texel]);
+            let value = reader.read(mode.weight_bits - anchor as u32);
+            unquantize_weight((0, value), 1 << mode.weight_bits)
+        })
+        .collect();
+
+    let colors = AstcColors {
+        partitions: mode.subsets,
+        partition_index,
+        endpoints,
+        weight_grid: (4, 4),
+        weights,
+        plane2_channel,
+    };
+    astc_interpolate(&colors, (4, 4), srgb).try_into().unwrap()
+}
+
+// UASTC mode codes and their lengths, read from the lowest bit up. The one
+// code left over, 0x45, is reserved.
+const UASTC_MODE_CODES: [(u32, u32); 19] = [
+    (0x01, 4),
+    (0x35, 6),
+    (0x1d, 5),
+    (0x03, 5),
+    (0x13, 5),
+    (0x0b, 5),
+    (0x1b, 5),
+    (0x07, 5),
+    (0x17, 5),
+    (0x0f, 5),
+    (0x02, 3),
+    (0x00, 2),
+    (0x06, 3),
+    (0x1f, 5),
+    (0x0d, 5),
+    (0x05, 7),
+    (0x15, 6),
+    (0x25, 6),
+    (0x09, 4),
+];
+
+// A single RGBA8 color, with a placeholder in `UASTC_MODES`
+const UASTC_SOLID_MODE: usize = 8;
+
+// Marks modes that store their second plane's channel, where the others fix it
+const UASTC_STORED_CHANNEL: usize = usize::MAX;
+
+struct UastcMode {
+    endpoint_mode: u32,
+    subsets: usize,
+    // Two subset modes with patterns that merge two subsets of a BC7 three
+    // subset pattern
+    three_subset_patterns: bool,
+    plane2_channel: Option<usize>,
+    endpoint_range: u32,
+    weight_bits: u32,
+    hint_bits: usize,
+}
+
+const fn uastc_mode(
+    endpoint_mode: u32,
+    subsets: usize,
+    plane2_channel: Option<usize>,
+    endpoint_range: u32,
+    weight_bits: u32,
+    hint_bits: usize,
+) -> UastcMode {
+    UastcMode {
+        endpoint_mode,
+        subsets,
+        three_subset_patterns: false,
+        plane2_channel,
+        endpoint_range,
+        weight_bits,
+        hint_bits,
+    }
+}
+
+const UASTC_MODES: [UastcMode; 19] = [
+    uastc_mode(8, 1, None, 192, 4, 15),
+    uastc_mode(8, 1, None, 256, 2, 15),
+    uastc_mode(8, 2, None, 16, 3, 15),
+    uastc_mode(8, 3, None, 12, 2, 15),
+    uastc_mode(8, 2, None, 40, 2, 15),
+    uastc_mode(8, 1, None, 256, 3, 15),
+    uastc_mode(8, 1, Some(UASTC_STORED_CHANNEL), 160, 2, 15),
+    UastcMode {
+        three_subset_patterns: true,
+        ..uastc_mode(8, 2, None, 40, 2, 15)
+    },
+    uastc_mode(0, 1, None, 2, 1, 0),
+    uastc_mode(12, 2, None, 16, 2, 23),
+    uastc_mode(12, 1, None, 48, 4, 17),
+    uastc_mode(12, 1, Some(UASTC_STORED_CHANNEL), 48, 2, 17),
+    uastc_mode(12, 1, None, 192, 3, 17),
+    uastc_mode(12, 1, Some(UASTC_STORED_CHANNEL), 256, 1, 23),
+    uastc_mode(4, 1, None, 256, 2, 23),
+    uastc_mode(4, 1, None, 256, 4, 23),
+    uastc_mode(4, 2, None, 256, 2, 23),
+    uastc_mode(4, 1, Some(3), 256, 2, 23),
+    uastc_mode(8, 1, None, 32, 5, 15),
+];
+
+// The ASTC partition indices of UASTC's two and three subset patterns, which
+// match BC7 partitions with their subsets relabelled
+const UASTC_PARTITIONS_2: [u32; 30] = [
+    28, 20, 16, 29, 91, 9, 107, 72, 149, 204, 50, 114, 496, 17, 78, 39, 252, 828, 43, 156, 116,
+    210, 476, 273, 684, 359, 246, 195, 694, 524,
+];
+const UASTC_PARTITIONS_3: [u32; 11] = [260, 74, 32, 156, 183, 15, 745, 0, 335, 902, 254];
+const UASTC_PARTITIONS_2_OF_3: [u32; 19] = [
+    36, 48, 61, 137, 161, 183, 226, 281, 302, 307, 479, 495, 593, 594, 605, 799, 812, 988, 993,
+];
+
+// Subset 1 texels of each two subset partition, one bit per texel
+const BC_PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+const BC_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+const BC_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const BC_ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+const BC_ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
@@ -0,0 +1,17 @@
+// Copies a depth texture into a color target so it can be read back on
+// devices that can't copy depth textures to buffers directly
+@group(0) @binding(0)
+var t_depth: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // Full screen triangle
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let depth = textureLoad(t_depth, vec2<i32>(position.xy), 0).r;
+    return vec4<f32>(depth, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,65 @@
+// Renders one face of a cube map from an equirectangular panorama, whose
+// top row looks along +Y and whose center column looks along -Z
+struct Face {
+    index: u32,
+    lod: f32,
+};
+
+@group(0) @binding(0)
+var t_panorama: texture_2d<f32>;
+@group(0) @binding(1)
+var s_panorama: sampler;
+@group(0) @binding(2)
+var<uniform> face: Face;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) face_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // Full screen triangle
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    // -1 to 1 across the face, with y pointing down its rows
+    out.face_coords = 2.0 * vec2<f32>(uv.x, 1.0 - uv.y) - 1.0;
+    return out;
+}
+
+// Faces are ordered +X, -X, +Y, -Y, +Z, -Z and oriented as in Vulkan and D3D
+fn face_direction(index: u32, coords: vec2<f32>) -> vec3<f32> {
+    switch index {
+        case 0u: {
+            return vec3<f32>(1.0, -coords.y, -coords.x);
+        }
+        case 1u: {
+            return vec3<f32>(-1.0, -coords.y, coords.x);
+        }
+        case 2u: {
+            return vec3<f32>(coords.x, 1.0, coords.y);
+        }
+        case 3u: {
+            return vec3<f32>(coords.x, -1.0, -coords.y);
+        }
+        case 4u: {
+            return vec3<f32>(coords.x, -coords.y, 1.0);
+        }
+        default: {
+            return vec3<f32>(-coords.x, -coords.y, -1.0);
+        }
+    }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pi = 3.14159265358979;
+    let direction = normalize(face_direction(face.index, in.face_coords));
+    let texcoords = vec2<f32>(
+        0.5 + atan2(direction.x, -direction.z) / (2.0 * pi),
+        acos(clamp(direction.y, -1.0, 1.0)) / pi,
+    );
+    // An explicit level avoids the seam where longitude wraps around
+    return textureSampleLevel(t_panorama, s_panorama, texcoords, face.lod);
+}
@@ -0,0 +1,139 @@
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Gltf(gltf::Error),
+    Image(image::ImageError),
+    Ktx2(ktx2::ParseError),
+    Dds(ddsfile::Error),
+    MissingAttribute {
+        mesh: usize,
+        primitive: usize,
+        semantic: String,
+    },
+    InvalidAccessor {
+        accessor: usize,
+    },
+    InvalidNodeHierarchy {
+        node: usize,
+    },
+    UnsupportedFormat(String),
+    InvalidImageData {
+        expected: usize,
+        actual: usize,
+    },
+    NoAdapter,
+    CreateSurface(wgpu::CreateSurfaceError),
+    IncompatibleSurface,
+    RequestDevice(wgpu::RequestDeviceError),
+    MapBuffer(wgpu::BufferAsyncError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Gltf(e) => write!(f, "glTF error: {}", e),
+            Error::Image(e) => write!(f, "image error: {}", e),
+            Error::Ktx2(e) => write!(f, "KTX2 error: {}", e),
+            Error::Dds(e) => write!(f, "DDS error: {}", e),
+            Error::MissingAttribute {
+                mesh,
+                primitive,
+                semantic,
+            } => write!(
+                f,
+                "primitive {} of mesh {} has no {} attribute",
+                primitive, mesh, semantic
+            ),
+            Error::InvalidAccessor { accessor } => {
+                write!(
+                    f,
+                    "accessor {} reads outside of its buffer or doesn't match its primitive's vertices",
+                    accessor
+                )
+            }
+            Error::InvalidNodeHierarchy { node } => write!(
+                f,
+                "node {} is reached more than once, the node hierarchy is not a tree",
+                node
+            ),
+            Error::UnsupportedFormat(format) => write!(f, "unsupported format: {}", format),
+            Error::InvalidImageData { expected, actual } => write!(
+                f,
+                "expected {} bytes of image data, found {}",
+                expected, actual
+            ),
+            Error::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            Error::CreateSurface(e) => write!(f, "failed to create surface: {}", e),
+            Error::IncompatibleSurface => write!(f, "surface is incompatible with the adapter"),
+            Error::RequestDevice(e) => write!(f, "failed to request device: {}", e),
+            Error::MapBuffer(e) => write!(f, "failed to map readback buffer: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Gltf(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::Ktx2(e) => Some(e),
+            Error::Dds(e) => Some(e),
+            Error::CreateSurface(e) => Some(e),
+            Error::RequestDevice(e) => Some(e),
+            Error::MapBuffer(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<gltf::Error> for Error {
+    fn from(e: gltf::Error) -> Self {
+        Error::Gltf(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<ktx2::ParseError> for Error {
+    fn from(e: ktx2::ParseError) -> Self {
+        Error::Ktx2(e)
+    }
+}
+
+impl From<ddsfile::Error> for Error {
+    fn from(e: ddsfile::Error) -> Self {
+        Error::Dds(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        Error::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Error::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for Error {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        Error::MapBuffer(e)
+    }
+}
@@ -1,17 +1,36 @@
 pub mod bind_groups {
     pub mod camera_bind_group;
+    pub mod light_bind_group;
     pub mod material_bind_group;
 }
 pub mod pipelines {
+    pub mod depth_readback_pipeline;
+    pub mod equirectangular_pipeline;
     pub mod mesh_pipeline;
+    pub mod mipmap_pipeline;
+    pub mod shadow_pipeline;
 }
+pub mod accessor;
+pub mod basis;
 pub mod camera;
 pub mod camera_controller;
+pub mod compress;
+pub mod decompress;
+pub mod error;
+pub mod light;
 pub mod material;
 pub mod mesh;
+pub mod normals;
 pub mod renderer;
+pub mod sampler;
+pub mod scene;
+pub mod screenshot;
+pub mod shadow;
+pub mod tangents;
 pub mod texture;
 
+pub use error::{Error, Result};
+
 pub mod constants {
     use crate::camera::{CameraExtrinsics, CameraIntrinsics};
     use std::f32::consts::*;
@@ -0,0 +1,305 @@
+use bytemuck::Zeroable;
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::mesh::Bounds;
+use crate::shadow::{self, MAX_CASCADES};
+
+extern crate nalgebra_glm as glm;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    // Angles are measured from the spot's axis, in radians. Light fades out
+    // between the inner and outer cone.
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+// How a light's shadow map is read. Biases push the compared depth towards
+// the light to keep surfaces from shadowing themselves: `depth_bias` in
+// normalized depth, `normal_bias` along the surface normal in shadow map
+// texels. PCF averages comparisons `pcf_radius` texels apart. Directional
+// lights split their shadow into `cascade_count` maps, up to `MAX_CASCADES`,
+// each covering a farther slice of the view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    pub depth_bias: f32,
+    pub normal_bias: f32,
+    pub pcf_radius: f32,
+    pub cascade_count: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            depth_bias: 0.0005,
+            normal_bias: 1.5,
+            pcf_radius: 1.0,
+            cascade_count: MAX_CASCADES as u32,
+        }
+    }
+}
+
+// A punctual light as defined by KHR_lights_punctual. Directional intensity
+// is illuminance in lux, point and spot intensity is luminous intensity in
+// candela. Lights shine down their node's -Z axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Light {
+    pub name: Option<String>,
+    pub kind: LightKind,
+    pub color: glm::Vec3,
+    pub intensity: f32,
+    // Distance at which point and spot lights have faded out completely,
+    // unlimited when `None`
+    pub range: Option<f32>,
+    // Lights cast shadows unless this is `None`, point lights only within
+    // the budget in `ShadowConfig`
+    pub shadow: Option<ShadowSettings>,
+}
+
+impl Light {
+    pub fn from_gltf(light: &gltf::khr_lights_punctual::Light) -> Self {
+        use gltf::khr_lights_punctual::Kind;
+
+        let kind = match light.kind() {
+            Kind::Directional => LightKind::Directional,
+            Kind::Point => LightKind::Point,
+            Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            },
+        };
+        Self {
+            name: light.name().map(str::to_string),
+            kind,
+            color: light.color().into(),
+            intensity: light.intensity(),
+            range: light.range(),
+            shadow: Some(ShadowSettings::default()),
+        }
+    }
+
+    // Places the light with its node's world matrix
+    pub fn to_uniform(&self, world_matrix: &glm::Mat4) -> LightUniform {
+        let position = world_matrix.column(3).xyz();
+        let direction = glm::normalize(&-world_matrix.column(2).xyz());
+        // Spot cones are blended with a linear ramp on the cosine of the
+        // angle, as in the KHR_lights_punctual reference implementation
+        let (kind, angle_scale, angle_offset) = match self.kind {
+            LightKind::Directional => (LIGHT_DIRECTIONAL, 0.0, 0.0),
+            LightKind::Point => (LIGHT_POINT, 0.0, 0.0),
+            LightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => {
+                let cos_outer = outer_cone_angle.cos();
+                let angle_scale = 1.0 / (inner_cone_angle.cos() - cos_outer).max(0.001);
+                (LIGHT_SPOT, angle_scale, -cos_outer * angle_scale)
+            }
+        };
+        LightUniform {
+            position: position.into(),
+            range: self.range.unwrap_or(0.0),
+            direction: direction.into(),
+            kind,
+            color: self.color.into(),
+            intensity: self.intensity,
+            angle_scale,
+            angle_offset,
+            shadow_layer: -1,
+            depth_bias: 0.0,
+            normal_bias: 0.0,
+            pcf_radius: 0.0,
+            cascade_count: 0,
+            shadow_far: 0.0,
+            shadow_extents: [0.0; MAX_CASCADES],
+            shadow_view_projs: [glm::Mat4::identity().into(); MAX_CASCADES],
+        }
+    }
+
+    // Like `to_uniform`, with the light's shadow read from the shadow maps
+    // starting at `shadow_layer`, or from that shadow cube for point lights,
+    // when it casts one over `bounds`, as seen by `camera`
+    pub fn to_shadowed_uniform(
+        &self,
+        world_matrix: &glm::Mat4,
+        bounds: &Bounds,
+        camera: &Camera,
+        map_size: u32,
+        shadow_layer: u32,
+    ) -> LightUniform {
+        let mut uniform = self.to_uniform(world_matrix);
+        let Some(settings) = self.shadow else {
+            return uniform;
+        };
+        uniform.shadow_layer = shadow_layer as i32;
+        uniform.depth_bias = settings.depth_bias;
+        uniform.normal_bias = settings.normal_bias;
+        uniform.pcf_radius = settings.pcf_radius;
+        if self.kind == LightKind::Point {
+            // Cube faces span 90 degrees, so twice their distance across
+            uniform.cascade_count = 1;
+            uniform.shadow_extents[0] = 2.0;
+            uniform.shadow_far = self.shadow_far(world_matrix, bounds);
+            return uniform;
+        }
+        let projections = self.shadow_projections(world_matrix, bounds, camera, map_size);
+        uniform.cascade_count = projections.len() as u32;
+        for (cascade, (view_proj, extent)) in projections.into_iter().enumerate() {
+            uniform.shadow_view_projs[cascade] = view_proj.into();
+            uniform.shadow_extents[cascade] = extent;
+        }
+        uniform
+    }
+
+    // The projection of each shadow map the light renders, fit around
+    // `bounds`, and the width it covers: in world units for directional
+    // lights, and at unit distance from spot lights. Directional lights have
+    // one per cascade, nearest to `camera` first. Point lights render shadow
+    // cubes instead, with `shadow::cube_face_view_projs`.
+    pub fn shadow_projections(
+        &self,
+        world_matrix: &glm::Mat4,
+        bounds: &Bounds,
+        camera: &Camera,
+        map_size: u32,
+    ) -> Vec<(glm::Mat4, f32)> {
+        let position = world_matrix.column(3).xyz();
+        let direction = glm::normalize(&-world_matrix.column(2).xyz());
+        match self.kind {
+            LightKind::Directional => {
+                let cascade_count = self
+                    .shadow
+                    .map_or(1, |settings| settings.cascade_count)
+                    .clamp(1, MAX_CASCADES as u32);
+                let splits = shadow::cascade_splits(&camera.intrinsics, cascade_count as usize);
+                let nears = std::iter::once(camera.intrinsics.near).chain(splits.iter().copied());
+                nears
+                    .zip(splits.iter().copied())
+                    .map(|(near, far)| {
+                        shadow::cascade_view_proj(&direction, camera, near, far, bounds, map_size)
+                    })
+                    .collect()
+            }
+            LightKind::Spot {
+                outer_cone_angle, ..
+            } => {
+                let far = self.shadow_far(world_matrix, bounds);
+                let fovy = (2.0 * outer_cone_angle).min(std::f32::consts::PI - 0.01);
+                let view = glm::look_at_rh(
+                    &position,
+                    &(position + direction),
+                    &shadow::up_vector(&direction),
+                );
+                let proj = glm::perspective_zo(1.0, fovy, far * 0.001, far);
+                vec![(proj * view, 2.0 * (fovy / 2.0).tan())]
+            }
+            LightKind::Point => Vec::new(),
+        }
+    }
+
+    // How far point and spot light shadows reach: the light's range, or the
+    // far side of `bounds` when it reaches any distance
+    pub fn shadow_far(&self, world_matrix: &glm::Mat4, bounds: &Bounds) -> f32 {
+        let position = world_matrix.column(3).xyz();
+        self.range.unwrap_or_else(|| {
+            glm::distance(&position, &bounds.center()) + bounds.radius().max(f32::EPSILON)
+        })
+    }
+}
+
+// Matches `Light::kind` in shader.wgsl
+pub const LIGHT_DIRECTIONAL: u32 = 0;
+pub const LIGHT_POINT: u32 = 1;
+pub const LIGHT_SPOT: u32 = 2;
+
+// One light in world space, matching `Light` in shader.wgsl. A range of zero
+// means the light reaches any distance, and a negative shadow layer that it
+// casts no shadow. Shadows take `cascade_count` layers from `shadow_layer` on,
+// except for point lights, whose shadow is the cube at `shadow_layer`, holding
+// distances up to `shadow_far`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    pub position: [f32; 3],
+    pub range: f32,
+    pub direction: [f32; 3],
+    pub kind: u32,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub angle_scale: f32,
+    pub angle_offset: f32,
+    pub shadow_layer: i32,
+    pub depth_bias: f32,
+    pub normal_bias: f32,
+    pub pcf_radius: f32,
+    pub cascade_count: u32,
+    pub shadow_far: f32,
+    pub shadow_extents: [f32; MAX_CASCADES],
+    pub shadow_view_projs: [[[f32; 4]; 4]; MAX_CASCADES],
+}
+
+// Scenes without lights of their own are lit by a white key light above and
+// in front of them, placed by the returned world matrix
+pub fn default_light() -> (Light, glm::Mat4) {
+    let light = Light {
+        name: None,
+        kind: LightKind::Directional,
+        color: glm::Vec3::new(1.0, 1.0, 1.0),
+        intensity: 3.0,
+        range: None,
+        shadow: Some(ShadowSettings::default()),
+    };
+    let world_matrix = glm::inverse(&glm::look_at_rh(
+        &glm::Vec3::zeros(),
+        &glm::Vec3::new(-1.0, -2.0, -2.0),
+        &glm::Vec3::y(),
+    ));
+    (light, world_matrix)
+}
+
+// Byte offset of the light array in `Lights` in shader.wgsl, after the light
+// count and debug flags
+const LIGHTS_OFFSET: u64 = 16;
+
+// The light count and cleared debug flags, padded to the alignment of the
+// array that follows them. Bindings must hold at least one element, so an
+// empty array is padded with an unused light.
+fn buffer_contents(lights: &[LightUniform]) -> Vec<u8> {
+    let mut contents = Vec::new();
+    contents.extend_from_slice(bytemuck::cast_slice(&[lights.len() as u32, 0, 0, 0]));
+    match lights {
+        [] => contents.extend_from_slice(bytemuck::bytes_of(&LightUniform::zeroed())),
+        lights => contents.extend_from_slice(bytemuck::cast_slice(lights)),
+    }
+    contents
+}
+
+// Storage buffers can't be resized, so later writes must hold the same
+// number of lights
+pub fn create_buffer(device: &wgpu::Device, lights: &[LightUniform]) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Light Buffer"),
+        contents: &buffer_contents(lights),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+pub fn write_buffer(queue: &wgpu::Queue, buffer: &wgpu::Buffer, lights: &[LightUniform]) {
+    if !lights.is_empty() {
+        queue.write_buffer(buffer, LIGHTS_OFFSET, bytemuck::cast_slice(lights));
+    }
+}
+
+// Tints everything lit by a directional light's shadow with the color of the
+// cascade it's read from
+pub fn write_debug_cascades(queue: &wgpu::Queue, buffer: &wgpu::Buffer, enabled: bool) {
+    queue.write_buffer(buffer, 4, bytemuck::bytes_of(&(enabled as u32)));
+}
@@ -18,7 +18,9 @@ async fn run() {
         window
     };
 
-    let mut renderer_state = renderer::RendererState::new(&window).await;
+    let mut renderer_state = renderer::RendererState::new(&window)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to initialize renderer: {}", e));
 
     let mut camera = camera::Camera::new(DEFAULT_CAMERA_EXTRINSICS, DEFAULT_CAMERA_INTRINSICS);
     camera.set_aspect(window.inner_size());
@@ -30,7 +32,7 @@ async fn run() {
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Camera Buffer"),
-                contents: bytemuck::cast_slice(&[camera.to_uniform_matrix()]),
+                contents: bytemuck::cast_slice(&[camera.to_uniform()]),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
 
@@ -41,34 +43,74 @@ async fn run() {
         mesh_path.pop();
         mesh_path.push("res/avocado/avocado.gltf");
         mesh::Mesh::from_gltf(&mesh_path, &renderer_state.device, &renderer_state.queue)
+            .unwrap_or_else(|e| panic!("Failed to load {}: {}", mesh_path.display(), e))
     };
 
+    let shadow_config = shadow::ShadowConfig::default();
+    let lights = mesh.light_uniforms(&camera, &shadow_config);
+    let light_buffer = light::create_buffer(&renderer_state.device, &lights);
+
     // TODO: block not really necessary, just helps clarify what is minimum
     // required for the main render loop. Feel free to revert this
-    let (camera_bind_group, material_bind_group, render_pipeline) = {
+    let (
+        camera_bind_group,
+        material_bind_groups,
+        light_bind_group,
+        render_pipeline,
+        shadow_maps,
+        shadow_pipeline,
+        point_shadow_pipeline,
+    ) = {
         let camera_bind_group_layout =
             camera_bind_group::create_bind_group_layout(&renderer_state.device);
+        let shadow_maps = shadow::ShadowMaps::new(
+            &renderer_state.device,
+            &camera_bind_group_layout,
+            &shadow_config,
+            shadow::layer_count(&lights),
+            shadow::cube_count(&lights),
+        );
         let material_bind_group_layout =
             material_bind_group::create_bind_group_layout(&renderer_state.device);
+        let light_bind_group_layout =
+            light_bind_group::create_bind_group_layout(&renderer_state.device);
         (
             camera_bind_group::create_bind_group(
                 &renderer_state.device,
                 &camera_bind_group_layout,
                 &camera_buffer,
             ),
-            material_bind_group::create_bind_group(
+            mesh.materials
+                .iter()
+                .map(|material| {
+                    material_bind_group::create_bind_group(
+                        &renderer_state.device,
+                        &material_bind_group_layout,
+                        material,
+                    )
+                })
+                .collect::<Vec<_>>(),
+            light_bind_group::create_bind_group(
                 &renderer_state.device,
-                &material_bind_group_layout,
-                &mesh.material.albedo_map.view,
-                &mesh.material.normal_map.view,
-                &mesh.material.roughness_metalness_map.view,
-                &mesh.material.sampler,
+                &light_bind_group_layout,
+                &light_buffer,
+                &shadow_maps,
             ),
             mesh_pipeline::create_render_pipeline(
                 &renderer_state.device,
                 renderer_state.surface_config.format,
                 &camera_bind_group_layout,
                 &material_bind_group_layout,
+                &light_bind_group_layout,
+            ),
+            shadow_maps,
+            shadow_pipeline::create_render_pipeline(
+                &renderer_state.device,
+                &camera_bind_group_layout,
+            ),
+            shadow_pipeline::create_point_render_pipeline(
+                &renderer_state.device,
+                &camera_bind_group_layout,
             ),
         )
     };
@@ -79,6 +121,7 @@ async fn run() {
         window.inner_size().height,
     );
 
+    let mut debug_cascades = false;
     let mut last_update_time = std::time::Instant::now();
     event_loop.run(move |winit_event, _, control_flow| {
         control_flow.set_poll();
@@ -98,6 +141,65 @@ async fn run() {
                         },
                     ..
                 } => control_flow.set_exit(),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F12),
+                            ..
+                        },
+                    ..
+                } => {
+                    // Surface textures can't be copied from, so draw the frame
+                    // again into a texture that can
+                    let target = texture::Texture::create_render_target(
+                        &renderer_state.device,
+                        renderer_state.surface_config.width,
+                        renderer_state.surface_config.height,
+                        renderer_state.surface_config.format,
+                        "Screenshot Texture",
+                    );
+                    renderer_state.render_to_view(
+                        &target.view,
+                        &render_pipeline,
+                        &camera_bind_group,
+                        &material_bind_groups,
+                        &light_bind_group,
+                        &mesh,
+                        &depth_texture.view,
+                    );
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    let path = std::path::PathBuf::from(format!("screenshot_{}.png", timestamp));
+                    let result = screenshot::read_color_image(
+                        &renderer_state.device,
+                        &renderer_state.queue,
+                        &target.texture,
+                    )
+                    .and_then(|image| screenshot::save_color_image(&image, &path));
+                    match result {
+                        Ok(_) => println!("Saved screenshot to {}", path.display()),
+                        Err(e) => eprintln!("Failed to save screenshot: {}", e),
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F3),
+                            ..
+                        },
+                    ..
+                } => {
+                    debug_cascades = !debug_cascades;
+                    light::write_debug_cascades(
+                        &renderer_state.queue,
+                        &light_buffer,
+                        debug_cascades,
+                    );
+                }
                 WindowEvent::KeyboardInput {
                     input:
                         KeyboardInput {
@@ -120,12 +222,25 @@ async fn run() {
                 renderer_state.queue.write_buffer(
                     &camera_buffer,
                     0,
-                    bytemuck::cast_slice(&[camera.to_uniform_matrix()]),
+                    bytemuck::cast_slice(&[camera.to_uniform()]),
+                );
+                // Directional light cascades follow the camera, and so does
+                // the choice of point lights casting shadows
+                let lights = mesh.light_uniforms(&camera, &shadow_config);
+                light::write_buffer(&renderer_state.queue, &light_buffer, &lights);
+                shadow_maps.render(
+                    &renderer_state.device,
+                    &renderer_state.queue,
+                    &shadow_pipeline,
+                    &point_shadow_pipeline,
+                    &mesh,
+                    &lights,
                 );
                 match renderer_state.render(
                     &render_pipeline,
                     &camera_bind_group,
-                    &material_bind_group,
+                    &material_bind_groups,
+                    &light_bind_group,
                     &mesh,
                     &depth_texture.view,
                 ) {
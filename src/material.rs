@@ -1,56 +1,370 @@
-use crate::texture::Texture;
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::error::{Error, Result};
+use crate::sampler::{gltf_mipmaps, SamplerCache, SamplerKey};
+use crate::texture::{ColorSpace, Mipmaps, Texture};
+
+// 1x1 stand-ins for missing maps, chosen so the material's factors apply
+// unchanged
+pub const DEFAULT_ALBEDO: [u8; 4] = [255, 255, 255, 255];
+pub const DEFAULT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+pub const DEFAULT_ROUGHNESS_METALNESS: [u8; 4] = [255, 255, 255, 255];
+pub const DEFAULT_OCCLUSION: [u8; 4] = [255, 255, 255, 255];
+pub const DEFAULT_EMISSIVE: [u8; 4] = [255, 255, 255, 255];
+
+// Scalar factors and the UV set each map is sampled with, matching
+// `MaterialUniform` in shader.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub albedo_texcoord: u32,
+    pub normal_texcoord: u32,
+    pub roughness_metalness_texcoord: u32,
+    pub occlusion_texcoord: u32,
+    pub emissive_texcoord: u32,
+}
+
+// glTF's defaults for a material that doesn't specify anything
+impl Default for MaterialUniform {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            emissive_factor: [0.0; 3],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            albedo_texcoord: 0,
+            normal_texcoord: 0,
+            roughness_metalness_texcoord: 0,
+            occlusion_texcoord: 0,
+            emissive_texcoord: 0,
+        }
+    }
+}
+
+// Tightly packed RGBA8 pixels of one map
+#[derive(Clone, Copy, Debug)]
+pub struct TextureBytes<'a> {
+    pub bytes: &'a [u8],
+    pub dimensions: (u32, u32),
+    pub mipmaps: Mipmaps,
+}
 
 pub struct Material {
     pub albedo_map: Texture,
     pub normal_map: Texture,
     pub roughness_metalness_map: Texture,
-    pub sampler: wgpu::Sampler,
+    pub occlusion_map: Texture,
+    pub emissive_map: Texture,
+    pub albedo_sampler: Arc<wgpu::Sampler>,
+    pub normal_sampler: Arc<wgpu::Sampler>,
+    pub roughness_metalness_sampler: Arc<wgpu::Sampler>,
+    pub occlusion_sampler: Arc<wgpu::Sampler>,
+    pub emissive_sampler: Arc<wgpu::Sampler>,
+    pub uniform: MaterialUniform,
+    pub uniform_buffer: wgpu::Buffer,
+}
+
+// Uploads `bytes`, or a 1x1 `default` texel when the map is missing
+fn load_bytes(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    bytes: Option<TextureBytes>,
+    default: [u8; 4],
+    color_space: ColorSpace,
+    label: &str,
+) -> Result<Texture> {
+    match bytes {
+        Some(TextureBytes {
+            bytes,
+            dimensions,
+            mipmaps,
+        }) => Texture::from_bytes(
+            device,
+            queue,
+            bytes,
+            dimensions,
+            color_space,
+            mipmaps,
+            label,
+        ),
+        None => Texture::from_bytes(
+            device,
+            queue,
+            &default,
+            (1, 1),
+            color_space,
+            Mipmaps::Skip,
+            label,
+        ),
+    }
+}
+
+// Uploads the image a glTF texture refers to, or a 1x1 `default` texel when
+// the material doesn't have that map
+fn load_map(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &Option<(gltf::Texture, u32)>,
+    images: &[gltf::image::Data],
+    default: [u8; 4],
+    color_space: ColorSpace,
+    label: &str,
+) -> Result<Texture> {
+    match texture {
+        Some((texture, _)) => Texture::from_gltf_image(
+            device,
+            queue,
+            &images[texture.source().index()],
+            color_space,
+            gltf_mipmaps(&texture.sampler()),
+            label,
+        ),
+        None => Texture::from_bytes(
+            device,
+            queue,
+            &default,
+            (1, 1),
+            color_space,
+            Mipmaps::Skip,
+            label,
+        ),
+    }
+}
+
+// The glTF texture's sampler, or glTF's default one when the material doesn't
+// have that map
+fn sampler_key(texture: &Option<(gltf::Texture, u32)>) -> SamplerKey {
+    texture
+        .as_ref()
+        .map_or_else(SamplerKey::default, |(texture, _)| {
+            SamplerKey::from_gltf(&texture.sampler())
+        })
+}
+
+// Only TEXCOORD_0 and TEXCOORD_1 are uploaded
+fn texcoord_set(texture: &Option<(gltf::Texture, u32)>) -> Result<u32> {
+    match texture.as_ref().map_or(0, |&(_, set)| set) {
+        set @ (0 | 1) => Ok(set),
+        set => Err(Error::UnsupportedFormat(format!(
+            "texture coordinate set {}",
+            set
+        ))),
+    }
 }
 
 impl Material {
-    pub fn from_bytes(
+    fn new(
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        albedo_bytes: &[u8],
-        normal_bytes: &[u8],
-        roughness_metalness_bytes: &[u8],
-        dimensions: (u32, u32),
+        maps: [Texture; 5],
+        samplers: [Arc<wgpu::Sampler>; 5],
+        uniform: MaterialUniform,
         label: &str,
     ) -> Self {
-        let albedo_label = label.to_string() + " Albedo";
-        let albedo_map =
-            Texture::from_bytes(device, queue, albedo_bytes, dimensions, &albedo_label);
+        let [albedo_map, normal_map, roughness_metalness_map, occlusion_map, emissive_map] = maps;
+        let [albedo_sampler, normal_sampler, roughness_metalness_sampler, occlusion_sampler, emissive_sampler] =
+            samplers;
 
-        let normal_label = label.to_string() + " Normal";
-        let normal_map =
-            Texture::from_bytes(device, queue, normal_bytes, dimensions, &normal_label);
-
-        let roughness_metalness_label = label.to_string() + " Roughness, Metalness";
-        let roughness_metalness_map = Texture::from_bytes(
-            device,
-            queue,
-            roughness_metalness_bytes,
-            dimensions,
-            &roughness_metalness_label,
-        );
-
-        let sampler_label = label.to_string() + " Sampler";
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some(&sampler_label),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&(label.to_string() + " Uniform Buffer")),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
         Self {
             albedo_map,
             normal_map,
             roughness_metalness_map,
-            sampler,
+            occlusion_map,
+            emissive_map,
+            albedo_sampler,
+            normal_sampler,
+            roughness_metalness_sampler,
+            occlusion_sampler,
+            emissive_sampler,
+            uniform,
+            uniform_buffer,
         }
     }
+
+    // Any map may be missing, and each may have its own dimensions. Every map
+    // uses glTF's default sampler.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        samplers: &mut SamplerCache,
+        albedo: Option<TextureBytes>,
+        normal: Option<TextureBytes>,
+        roughness_metalness: Option<TextureBytes>,
+        uniform: MaterialUniform,
+        label: &str,
+    ) -> Result<Self> {
+        let maps = [
+            load_bytes(
+                device,
+                queue,
+                albedo,
+                DEFAULT_ALBEDO,
+                ColorSpace::Srgb,
+                &(label.to_string() + " Albedo"),
+            )?,
+            load_bytes(
+                device,
+                queue,
+                normal,
+                DEFAULT_NORMAL,
+                ColorSpace::Linear,
+                &(label.to_string() + " Normal"),
+            )?,
+            load_bytes(
+                device,
+                queue,
+                roughness_metalness,
+                DEFAULT_ROUGHNESS_METALNESS,
+                ColorSpace::Linear,
+                &(label.to_string() + " Roughness, Metalness"),
+            )?,
+            load_bytes(
+                device,
+                queue,
+                None,
+                DEFAULT_OCCLUSION,
+                ColorSpace::Linear,
+                &(label.to_string() + " Occlusion"),
+            )?,
+            load_bytes(
+                device,
+                queue,
+                None,
+                DEFAULT_EMISSIVE,
+                ColorSpace::Srgb,
+                &(label.to_string() + " Emissive"),
+            )?,
+        ];
+        let sampler = samplers.get(device, SamplerKey::default());
+        let samplers = [(); 5].map(|_| sampler.clone());
+
+        Ok(Self::new(device, maps, samplers, uniform, label))
+    }
+
+    // Builds each map from the texture the glTF material references, with its
+    // own image dimensions, UV set and sampler
+    pub fn from_gltf(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        samplers: &mut SamplerCache,
+        material: &gltf::Material,
+        images: &[gltf::image::Data],
+    ) -> Result<Self> {
+        let label = material.name().unwrap_or("Material");
+        let pbr = material.pbr_metallic_roughness();
+        let albedo = pbr
+            .base_color_texture()
+            .map(|info| (info.texture(), info.tex_coord()));
+        let normal_texture = material.normal_texture();
+        let normal_scale = normal_texture.as_ref().map_or(1.0, |info| info.scale());
+        let normal = normal_texture.map(|info| (info.texture(), info.tex_coord()));
+        let roughness_metalness = pbr
+            .metallic_roughness_texture()
+            .map(|info| (info.texture(), info.tex_coord()));
+        let occlusion_texture = material.occlusion_texture();
+        let occlusion_strength = occlusion_texture
+            .as_ref()
+            .map_or(1.0, |info| info.strength());
+        let occlusion = occlusion_texture.map(|info| (info.texture(), info.tex_coord()));
+        let emissive = material
+            .emissive_texture()
+            .map(|info| (info.texture(), info.tex_coord()));
+
+        let uniform = MaterialUniform {
+            base_color_factor: pbr.base_color_factor(),
+            emissive_factor: material.emissive_factor(),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            normal_scale,
+            occlusion_strength,
+            albedo_texcoord: texcoord_set(&albedo)?,
+            normal_texcoord: texcoord_set(&normal)?,
+            roughness_metalness_texcoord: texcoord_set(&roughness_metalness)?,
+            occlusion_texcoord: texcoord_set(&occlusion)?,
+            emissive_texcoord: texcoord_set(&emissive)?,
+        };
+
+        let maps = [
+            load_map(
+                device,
+                queue,
+                &albedo,
+                images,
+                DEFAULT_ALBEDO,
+                ColorSpace::Srgb,
+                &(label.to_string() + " Albedo"),
+            )?,
+            load_map(
+                device,
+                queue,
+                &normal,
+                images,
+                DEFAULT_NORMAL,
+                ColorSpace::Linear,
+                &(label.to_string() + " Normal"),
+            )?,
+            load_map(
+                device,
+                queue,
+                &roughness_metalness,
+                images,
+                DEFAULT_ROUGHNESS_METALNESS,
+                ColorSpace::Linear,
+                &(label.to_string() + " Roughness, Metalness"),
+            )?,
+            load_map(
+                device,
+                queue,
+                &occlusion,
+                images,
+                DEFAULT_OCCLUSION,
+                ColorSpace::Linear,
+                &(label.to_string() + " Occlusion"),
+            )?,
+            load_map(
+                device,
+                queue,
+                &emissive,
+                images,
+                DEFAULT_EMISSIVE,
+                ColorSpace::Srgb,
+                &(label.to_string() + " Emissive"),
+            )?,
+        ];
+        let samplers = [
+            &albedo,
+            &normal,
+            &roughness_metalness,
+            &occlusion,
+            &emissive,
+        ]
+        .map(|texture| samplers.get(device, sampler_key(texture)));
+
+        Ok(Self::new(device, maps, samplers, uniform, label))
+    }
+
+    // Uploads changes made to `uniform`
+    pub fn update_uniform(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
 }
@@ -2,19 +2,298 @@ use wgpu::util::DeviceExt;
 use wgpu::VertexAttribute;
 use wgpu::VertexFormat::{Float32x2, Float32x3, Float32x4};
 
-use crate::material::Material;
+use crate::accessor;
+use crate::camera::Camera;
+use crate::error::{Error, Result};
+use crate::light::{default_light, Light, LightKind, LightUniform};
+use crate::material::{Material, MaterialUniform};
+use crate::normals::{self, NormalMode};
+use crate::sampler::SamplerCache;
+use crate::scene::{InstanceRaw, Scene};
+use crate::shadow::ShadowConfig;
+use crate::tangents;
 
 extern crate nalgebra_glm as glm;
 
-pub struct Mesh {
-    pub buffer: wgpu::Buffer,
-    pub material: Material,
+// Axis aligned box around a set of points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Bounds {
+    pub fn from_points(points: impl IntoIterator<Item = glm::Vec3>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds, point| {
+            Some(match bounds {
+                Some(Bounds { min, max }) => Bounds {
+                    min: glm::min2(&min, &point),
+                    max: glm::max2(&max, &point),
+                },
+                None => Bounds {
+                    min: point,
+                    max: point,
+                },
+            })
+        })
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    // The box around this one's corners after `matrix` moves them
+    pub fn transform(&self, matrix: &glm::Mat4) -> Bounds {
+        let corners = (0..8).map(|corner| {
+            let pick = |bit: u32, min: f32, max: f32| if corner & bit == 0 { min } else { max };
+            let point = glm::vec4(
+                pick(1, self.min.x, self.max.x),
+                pick(2, self.min.y, self.max.y),
+                pick(4, self.min.z, self.max.z),
+                1.0,
+            );
+            (matrix * point).xyz()
+        });
+        Bounds::from_points(corners).unwrap()
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    // Radius of the sphere through the corners
+    pub fn radius(&self) -> f32 {
+        glm::distance(&self.min, &self.max) / 2.0
+    }
+}
+
+pub struct Primitive {
+    pub material_index: usize,
     pub index_count: u32,
-    index_range: (u64, u64),
+    pub vertex_count: u32,
+    index_range: Option<(u64, u64)>,
     position_range: (u64, u64),
     normal_range: (u64, u64),
     tangent_range: (u64, u64),
     texcoord_range: (u64, u64),
+    texcoord_1_range: (u64, u64),
+}
+
+impl Primitive {
+    pub fn index_range(&self) -> Option<std::ops::Range<u64>> {
+        self.index_range.map(|(start, end)| start..end)
+    }
+    pub fn position_range(&self) -> std::ops::Range<u64> {
+        self.position_range.0..self.position_range.1
+    }
+    pub fn normal_range(&self) -> std::ops::Range<u64> {
+        self.normal_range.0..self.normal_range.1
+    }
+    pub fn tangent_range(&self) -> std::ops::Range<u64> {
+        self.tangent_range.0..self.tangent_range.1
+    }
+    pub fn texcoord_range(&self) -> std::ops::Range<u64> {
+        self.texcoord_range.0..self.texcoord_range.1
+    }
+    pub fn texcoord_1_range(&self) -> std::ops::Range<u64> {
+        self.texcoord_1_range.0..self.texcoord_1_range.1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrimitiveData {
+    pub material_index: Option<usize>,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub texcoords: Vec<[f32; 2]>,
+    pub texcoords_1: Vec<[f32; 2]>,
+    pub indices: Option<Vec<u32>>,
+}
+
+impl PrimitiveData {
+    fn attribute<'a>(
+        mesh: &gltf::Mesh,
+        primitive: &gltf::Primitive<'a>,
+        semantic: gltf::Semantic,
+    ) -> Result<gltf::Accessor<'a>> {
+        primitive
+            .get(&semantic)
+            .ok_or_else(|| Error::MissingAttribute {
+                mesh: mesh.index(),
+                primitive: primitive.index(),
+                semantic: semantic.to_string(),
+            })
+    }
+
+    // Attribute accessors must have a value for every vertex
+    fn read_attribute<const N: usize>(
+        accessor: &gltf::Accessor,
+        buffers: &[gltf::buffer::Data],
+        default: [f32; N],
+        vertex_count: usize,
+    ) -> Result<Vec<[f32; N]>> {
+        let values = accessor::read_f32(accessor, buffers, default)?;
+        if values.len() != vertex_count {
+            return Err(Error::InvalidAccessor {
+                accessor: accessor.index(),
+            });
+        }
+        Ok(values)
+    }
+
+    // Decodes the primitive's accessors whatever their strides, offsets,
+    // component types and source buffers, and checks that every attribute has
+    // a value for each vertex and every index refers to one
+    pub fn from_gltf(
+        mesh: &gltf::Mesh,
+        primitive: &gltf::Primitive,
+        buffers: &[gltf::buffer::Data],
+        normal_mode: NormalMode,
+    ) -> Result<Self> {
+        let positions = accessor::read_f32(
+            &Self::attribute(mesh, primitive, gltf::Semantic::Positions)?,
+            buffers,
+            [0.0; 3],
+        )?;
+        let vertex_count = positions.len();
+        let normals = primitive
+            .get(&gltf::Semantic::Normals)
+            .map(|normals| Self::read_attribute(&normals, buffers, [0.0; 3], vertex_count))
+            .transpose()?;
+        let tangents = primitive
+            .get(&gltf::Semantic::Tangents)
+            .map(|tangents| {
+                Self::read_attribute(&tangents, buffers, [0.0, 0.0, 0.0, 1.0], vertex_count)
+            })
+            .transpose()?;
+        // Scanned and CAD meshes often come without UVs, which only matter
+        // once a map is sampled
+        let texcoords = primitive
+            .get(&gltf::Semantic::TexCoords(0))
+            .map(|texcoords| Self::read_attribute(&texcoords, buffers, [0.0; 2], vertex_count))
+            .transpose()?;
+        let has_texcoords = texcoords.is_some();
+        let texcoords = texcoords.unwrap_or_else(|| vec![[0.0; 2]; vertex_count]);
+        // Maps may sample a second UV set, which defaults to the first
+        let texcoords_1 = match primitive.get(&gltf::Semantic::TexCoords(1)) {
+            Some(texcoords_1) => {
+                Self::read_attribute(&texcoords_1, buffers, [0.0; 2], vertex_count)?
+            }
+            None => texcoords.clone(),
+        };
+        let indices = match primitive.indices() {
+            Some(accessor) => {
+                let indices = accessor::read_indices(&accessor, buffers)?;
+                if indices.iter().any(|&index| index as usize >= vertex_count) {
+                    return Err(Error::InvalidAccessor {
+                        accessor: accessor.index(),
+                    });
+                }
+                Some(indices)
+            }
+            None => None,
+        };
+
+        let generate_normals = normals.is_none();
+        let generate_tangents = tangents.is_none();
+        let mut data = Self {
+            material_index: primitive.material().index(),
+            positions,
+            normals: normals.unwrap_or_default(),
+            tangents: tangents.unwrap_or_default(),
+            texcoords,
+            texcoords_1,
+            indices,
+        };
+        let triangles = primitive.mode() == gltf::mesh::Mode::Triangles;
+        if generate_normals {
+            match triangles {
+                true => normals::generate(&mut data, normal_mode),
+                false => data.normals = vec![[0.0, 0.0, 1.0]; data.positions.len()],
+            }
+        }
+        if generate_tangents {
+            match triangles && has_texcoords {
+                true => tangents::generate(&mut data),
+                false => tangents::perpendicular(&mut data),
+            }
+        }
+        Ok(data)
+    }
+
+    // Gives every triangle corner its own vertex, dropping the index buffer
+    pub fn unweld(&mut self) {
+        fn expand<T: Copy>(values: &mut Vec<T>, indices: &[u32]) {
+            if !values.is_empty() {
+                *values = indices
+                    .iter()
+                    .map(|&index| values[index as usize])
+                    .collect();
+            }
+        }
+        if let Some(indices) = self.indices.take() {
+            expand(&mut self.positions, &indices);
+            expand(&mut self.normals, &indices);
+            expand(&mut self.tangents, &indices);
+            expand(&mut self.texcoords, &indices);
+            expand(&mut self.texcoords_1, &indices);
+        }
+    }
+
+    // Merges vertices whose attributes are bitwise identical and indexes the
+    // remaining ones
+    pub fn weld(&mut self) {
+        let vertex_count = self.positions.len();
+        let mut unique = std::collections::HashMap::new();
+        let mut kept = Vec::new();
+        let remap: Vec<u32> = (0..vertex_count)
+            .map(|vertex| {
+                let key: Vec<u32> = self.positions[vertex]
+                    .iter()
+                    .chain(self.normals.get(vertex).into_iter().flatten())
+                    .chain(self.tangents.get(vertex).into_iter().flatten())
+                    .chain(self.texcoords.get(vertex).into_iter().flatten())
+                    .chain(self.texcoords_1.get(vertex).into_iter().flatten())
+                    .map(|value| value.to_bits())
+                    .collect();
+                *unique.entry(key).or_insert_with(|| {
+                    kept.push(vertex);
+                    kept.len() as u32 - 1
+                })
+            })
+            .collect();
+
+        fn keep<T: Copy>(values: &mut Vec<T>, kept: &[usize]) {
+            if !values.is_empty() {
+                *values = kept.iter().map(|&vertex| values[vertex]).collect();
+            }
+        }
+        keep(&mut self.positions, &kept);
+        keep(&mut self.normals, &kept);
+        keep(&mut self.tangents, &kept);
+        keep(&mut self.texcoords, &kept);
+        keep(&mut self.texcoords_1, &kept);
+        self.indices = Some(match self.indices.take() {
+            Some(indices) => indices.iter().map(|&index| remap[index as usize]).collect(),
+            None => remap,
+        });
+    }
+}
+
+pub struct Mesh {
+    pub buffer: wgpu::Buffer,
+    pub materials: Vec<Material>,
+    pub primitives: Vec<Primitive>,
+    pub scene: Scene,
+    pub instance_buffer: wgpu::Buffer,
+    pub lights: Vec<Light>,
+    // Local bounds of each glTF mesh, `None` when it has no vertices
+    mesh_bounds: Vec<Option<Bounds>>,
+    primitive_ranges: Vec<(usize, usize)>,
 }
 
 impl Mesh {
@@ -63,99 +342,261 @@ impl Mesh {
         }
     }
 
-    pub fn index_range(&self) -> std::ops::Range<u64> {
-        self.index_range.0..self.index_range.1
-    }
-    pub fn position_range(&self) -> std::ops::Range<u64> {
-        self.position_range.0..self.position_range.1
+    pub fn texcoord_1_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: wgpu::VertexFormat::size(&Float32x2),
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[VertexAttribute {
+                offset: 0,
+                shader_location: 11,
+                format: Float32x2,
+            }],
+        }
     }
-    pub fn normal_range(&self) -> std::ops::Range<u64> {
-        self.normal_range.0..self.normal_range.1
+
+    pub fn instance_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [VertexAttribute; 7] = wgpu::vertex_attr_array![
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x3,
+            9 => Float32x3,
+            10 => Float32x3,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
     }
-    pub fn tangent_range(&self) -> std::ops::Range<u64> {
-        self.tangent_range.0..self.tangent_range.1
+
+    pub fn mesh_primitives(&self, mesh_index: usize) -> &[Primitive] {
+        let (start, end) = self.primitive_ranges[mesh_index];
+        &self.primitives[start..end]
     }
-    pub fn texcoord_range(&self) -> std::ops::Range<u64> {
-        self.texcoord_range.0..self.texcoord_range.1
+
+    // World space bounds of every mesh instance in the scene
+    pub fn world_bounds(&self) -> Option<Bounds> {
+        self.scene
+            .mesh_nodes()
+            .into_iter()
+            .filter_map(|index| {
+                let node = &self.scene.nodes[index];
+                self.mesh_bounds[node.mesh.unwrap()]
+                    .map(|bounds| bounds.transform(&node.world_matrix()))
+            })
+            .reduce(|bounds, other| bounds.union(&other))
     }
 
-    fn gltf_first_primitive(gltf: &gltf::Document) -> std::option::Option<gltf::Primitive> {
-        let mut first_primitive = None;
-        for mesh in gltf.meshes() {
-            if let Some(primitive) = mesh.primitives().next() {
-                first_primitive = Some(primitive);
-            }
+    // Every light placed in the scene, or the default light when there are
+    // none. Lights that cast shadows take consecutive shadow map layers, with
+    // projections that cover the scene, or the part `camera` sees of it for
+    // directional light cascades. Point lights take consecutive shadow cubes,
+    // nearest to the camera first, until the budget in `config` runs out.
+    pub fn light_uniforms(&self, camera: &Camera, config: &ShadowConfig) -> Vec<LightUniform> {
+        let default_light = default_light();
+        let mut placed: Vec<(&Light, glm::Mat4)> = self
+            .scene
+            .light_nodes()
+            .into_iter()
+            .map(|index| {
+                let node = &self.scene.nodes[index];
+                (&self.lights[node.light.unwrap()], node.world_matrix())
+            })
+            .collect();
+        if placed.is_empty() {
+            placed.push((&default_light.0, default_light.1));
         }
-        first_primitive
+
+        let camera_position = camera.extrinsics.position.xyz();
+        let mut point_shadows: Vec<usize> = (0..placed.len())
+            .filter(|&index| {
+                let (light, _) = placed[index];
+                light.kind == LightKind::Point && light.shadow.is_some()
+            })
+            .collect();
+        point_shadows.sort_by(|&a, &b| {
+            let distance =
+                |index: usize| glm::distance(&placed[index].1.column(3).xyz(), &camera_position);
+            distance(a).total_cmp(&distance(b))
+        });
+        point_shadows.truncate(config.max_point_shadows as usize);
+        let mut point_cubes = vec![None; placed.len()];
+        for (cube, &index) in point_shadows.iter().enumerate() {
+            point_cubes[index] = Some(cube as u32);
+        }
+
+        let bounds = self.world_bounds();
+        let mut shadow_layer = 0;
+        placed
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, (light, world_matrix))| match (&bounds, point_cubes[index]) {
+                    (Some(bounds), Some(cube)) => light.to_shadowed_uniform(
+                        &world_matrix,
+                        bounds,
+                        camera,
+                        config.map_size,
+                        cube,
+                    ),
+                    (Some(bounds), None) if light.kind != LightKind::Point => {
+                        let uniform = light.to_shadowed_uniform(
+                            &world_matrix,
+                            bounds,
+                            camera,
+                            config.map_size,
+                            shadow_layer,
+                        );
+                        shadow_layer += uniform.cascade_count;
+                        uniform
+                    }
+                    _ => light.to_uniform(&world_matrix),
+                },
+            )
+            .collect()
+    }
+
+    pub fn update_instances(&mut self, queue: &wgpu::Queue) -> Result<()> {
+        self.scene.update_world_matrices()?;
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&self.scene.instances()),
+        );
+        Ok(())
     }
 
-    fn index_range_and_count(primitive: &gltf::Primitive) -> ((u64, u64), u32) {
-        let accessor = primitive.indices().unwrap();
-        let view = accessor.view().unwrap();
-        let offset = view.offset() as u64;
-        let length = view.length() as u64;
-        let count = accessor.count() as u32;
-        ((offset, offset + length), count)
+    // Geometry of every primitive, grouped by the glTF mesh it belongs to
+    pub fn load_primitives(
+        gltf: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        normal_mode: NormalMode,
+    ) -> Result<Vec<Vec<PrimitiveData>>> {
+        gltf.meshes()
+            .map(|mesh| {
+                mesh.primitives()
+                    .map(|primitive| {
+                        PrimitiveData::from_gltf(&mesh, &primitive, buffers, normal_mode)
+                    })
+                    .collect()
+            })
+            .collect()
     }
-    fn attribute_range(primitive: &gltf::Primitive, attribute: &gltf::Semantic) -> (u64, u64) {
-        let accessor = primitive.get(attribute).unwrap();
-        let view = accessor.view().unwrap();
-        let offset = view.offset() as u64;
-        let length = view.length() as u64;
-        (offset, offset + length)
+
+    // Appends `data` to the packed mesh data and returns its byte range
+    fn push_range<T: bytemuck::Pod>(contents: &mut Vec<u8>, data: &[T]) -> (u64, u64) {
+        let start = contents.len() as u64;
+        contents.extend_from_slice(bytemuck::cast_slice(data));
+        (start, contents.len() as u64)
     }
 
-    pub fn from_gltf(path: &std::path::Path, device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
-        // Load data from file
-        if !path.try_exists().unwrap() {
-            panic!("Could not find gltf file {}", path.to_str().unwrap());
-        }
+    pub fn from_gltf(
+        path: &std::path::Path,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Self> {
+        Self::from_gltf_with_normals(path, device, queue, NormalMode::default())
+    }
 
-        let (gltf, buffers, images) = gltf::import(path).unwrap_or_else(|_| {
-            panic!("Something broken in gltf file '{}'", path.to_str().unwrap())
-        });
+    // Like `from_gltf`, choosing how normals are generated for primitives
+    // that don't have any
+    pub fn from_gltf_with_normals(
+        path: &std::path::Path,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        normal_mode: NormalMode,
+    ) -> Result<Self> {
+        // Load data from file
+        let (gltf, buffers, images) = gltf::import(path).map_err(|e| match e {
+            gltf::Error::Io(e) => Error::Io(e),
+            e => Error::Gltf(e),
+        })?;
 
-        // Load material
-        let albedo_bytes = &images[0].pixels;
-        let normal_bytes = &images[2].pixels;
-        let roughness_metalness_bytes = &images[1].pixels;
-        let dimensions = (images[0].width, images[0].height);
-        let material = Material::from_bytes(
+        // One material per glTF material, plus a default for primitives
+        // that don't reference any. Materials share identical samplers.
+        let mut samplers = SamplerCache::default();
+        let mut materials = gltf
+            .materials()
+            .map(|material| Material::from_gltf(device, queue, &mut samplers, &material, &images))
+            .collect::<Result<Vec<_>>>()?;
+        let default_material_index = materials.len();
+        materials.push(Material::from_bytes(
             device,
             queue,
-            albedo_bytes,
-            normal_bytes,
-            roughness_metalness_bytes,
-            dimensions,
-            "Material",
-        );
+            &mut samplers,
+            None,
+            None,
+            None,
+            MaterialUniform::default(),
+            "Default Material",
+        )?);
+
+        // Pack every primitive into one tightly packed f32/u32 layout
+        let mut contents = Vec::new();
+        let mut primitives = Vec::new();
+        let mut primitive_ranges = Vec::new();
+        let mut mesh_bounds = Vec::new();
+        for mesh_data in Mesh::load_primitives(&gltf, &buffers, normal_mode)? {
+            let first_primitive = primitives.len();
+            mesh_bounds.push(Bounds::from_points(
+                mesh_data
+                    .iter()
+                    .flat_map(|data| data.positions.iter().map(|&position| position.into())),
+            ));
+            for data in mesh_data {
+                primitives.push(Primitive {
+                    material_index: data.material_index.unwrap_or(default_material_index),
+                    index_count: data
+                        .indices
+                        .as_ref()
+                        .map_or(0, |indices| indices.len() as u32),
+                    vertex_count: data.positions.len() as u32,
+                    position_range: Mesh::push_range(&mut contents, &data.positions),
+                    normal_range: Mesh::push_range(&mut contents, &data.normals),
+                    tangent_range: Mesh::push_range(&mut contents, &data.tangents),
+                    texcoord_range: Mesh::push_range(&mut contents, &data.texcoords),
+                    texcoord_1_range: Mesh::push_range(&mut contents, &data.texcoords_1),
+                    index_range: data
+                        .indices
+                        .map(|indices| Mesh::push_range(&mut contents, &indices)),
+                });
+            }
+            primitive_ranges.push((first_primitive, primitives.len()));
+        }
 
         // Create buffer on gpu
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh Buffer"),
-            contents: bytemuck::cast_slice(&(buffers[0].0)),
+            contents: &contents,
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::INDEX,
         });
 
-        // Get buffer access information
-        let primitive = Mesh::gltf_first_primitive(&gltf).unwrap();
-        let position_range = Mesh::attribute_range(&primitive, &gltf::Semantic::Positions);
-        let normal_range = Mesh::attribute_range(&primitive, &gltf::Semantic::Normals);
-        let tangent_range = Mesh::attribute_range(&primitive, &gltf::Semantic::Tangents);
-        let texcoord_range = Mesh::attribute_range(&primitive, &gltf::Semantic::TexCoords(0));
+        // Place each mesh according to the node hierarchy
+        let scene = Scene::from_gltf(&gltf)?;
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            contents: bytemuck::cast_slice(&scene.instances()),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
 
-        let (index_range, index_count) = Mesh::index_range_and_count(&primitive);
+        // KHR_lights_punctual lights, placed by the nodes that refer to them
+        let lights = gltf
+            .lights()
+            .map(|lights| lights.map(|light| Light::from_gltf(&light)).collect())
+            .unwrap_or_default();
 
         // Return mesh object
-        Self {
+        Ok(Self {
             buffer,
-            material,
-            index_range,
-            index_count,
-            position_range,
-            normal_range,
-            tangent_range,
-            texcoord_range,
-        }
+            materials,
+            primitives,
+            scene,
+            instance_buffer,
+            lights,
+            mesh_bounds,
+            primitive_ranges,
+        })
     }
 }
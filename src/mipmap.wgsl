@@ -0,0 +1,28 @@
+// Downsamples one mip level into the next with a bilinear tap centered on
+// each 2x2 block of source texels
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) texcoords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // Full screen triangle
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    out.texcoords = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// The source view holds a single level, which is read explicitly rather than
+// through implicit derivatives, since some drivers sample nothing otherwise
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSampleLevel(t_source, s_source, in.texcoords, 0.0);
+}
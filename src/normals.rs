@@ -0,0 +1,87 @@
+extern crate nalgebra_glm as glm;
+
+use crate::mesh::PrimitiveData;
+
+// How to fill in normals for primitives that don't have any. The glTF spec
+// asks for flat normals, smooth normals suit scanned and CAD data better.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalMode {
+    #[default]
+    Flat,
+    // Faces meeting at more than `crease_angle` radians keep a hard edge
+    Smooth {
+        crease_angle: f32,
+    },
+}
+
+fn face_normal(corners: &[glm::Vec3]) -> glm::Vec3 {
+    let normal = (corners[1] - corners[0]).cross(&(corners[2] - corners[0]));
+    match normal.norm() {
+        length if length > 0.0 => normal / length,
+        _ => glm::Vec3::zeros(),
+    }
+}
+
+fn corner_angle(corners: &[glm::Vec3], corner: usize) -> f32 {
+    let position = corners[corner];
+    let next = corners[(corner + 1) % 3] - position;
+    let previous = corners[(corner + 2) % 3] - position;
+    match next.norm() * previous.norm() {
+        lengths if lengths > 0.0 => (next.dot(&previous) / lengths).clamp(-1.0, 1.0).acos(),
+        _ => 0.0,
+    }
+}
+
+// Fills in normals for a triangle list. Every corner gets its own normal and
+// corners that end up identical are welded back together, so the primitive is
+// always indexed afterwards.
+pub fn generate(data: &mut PrimitiveData, mode: NormalMode) {
+    data.unweld();
+    let positions: Vec<glm::Vec3> = data.positions.iter().map(|&p| p.into()).collect();
+    let face_normals: Vec<glm::Vec3> = positions.chunks_exact(3).map(face_normal).collect();
+    let corner_count = face_normals.len() * 3;
+
+    let normals: Vec<glm::Vec3> = match mode {
+        NormalMode::Flat => (0..corner_count)
+            .map(|corner| face_normals[corner / 3])
+            .collect(),
+        NormalMode::Smooth { crease_angle } => {
+            // Corners are grouped by position so vertices split for other
+            // attributes, like UV seams, are still smoothed across
+            let mut shared = std::collections::HashMap::<_, Vec<usize>>::new();
+            for (corner, position) in data.positions.iter().take(corner_count).enumerate() {
+                shared
+                    .entry(position.map(f32::to_bits))
+                    .or_default()
+                    .push(corner);
+            }
+            let min_cos = crease_angle.cos();
+            (0..corner_count)
+                .map(|corner| {
+                    let face_normal = face_normals[corner / 3];
+                    shared[&data.positions[corner].map(f32::to_bits)]
+                        .iter()
+                        .filter(|&&other| face_normals[other / 3].dot(&face_normal) >= min_cos)
+                        .map(|&other| {
+                            let face = other / 3 * 3;
+                            face_normals[other / 3]
+                                * corner_angle(&positions[face..face + 3], other - face)
+                        })
+                        .sum::<glm::Vec3>()
+                })
+                .collect()
+        }
+    };
+
+    // Degenerate triangles still need a usable normal
+    data.normals = normals
+        .iter()
+        .map(|normal| match normal.norm() {
+            length if length > 0.0 => (normal / length).into(),
+            _ => [0.0, 0.0, 1.0],
+        })
+        .collect();
+    // A trailing partial triangle is never drawn
+    data.normals.resize(data.positions.len(), [0.0, 0.0, 1.0]);
+    data.weld();
+}
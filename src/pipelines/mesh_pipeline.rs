@@ -5,11 +5,16 @@ pub fn create_render_pipeline(
     format: wgpu::TextureFormat,
     camera_bind_group_layout: &wgpu::BindGroupLayout,
     material_bind_group_layout: &wgpu::BindGroupLayout,
+    light_bind_group_layout: &wgpu::BindGroupLayout,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(wgpu::include_wgsl!("../shader.wgsl"));
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Render Pipeline Layout"),
-        bind_group_layouts: &[camera_bind_group_layout, material_bind_group_layout],
+        bind_group_layouts: &[
+            camera_bind_group_layout,
+            material_bind_group_layout,
+            light_bind_group_layout,
+        ],
         push_constant_ranges: &[],
     });
 
@@ -24,6 +29,8 @@ pub fn create_render_pipeline(
                 Mesh::normal_layout(),
                 Mesh::tangent_layout(),
                 Mesh::texcoord_layout(),
+                Mesh::texcoord_1_layout(),
+                Mesh::instance_layout(),
             ],
         },
         fragment: Some(wgpu::FragmentState {
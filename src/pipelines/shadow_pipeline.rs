@@ -0,0 +1,80 @@
+use crate::mesh::Mesh;
+use crate::shadow::SHADOW_MAP_FORMAT;
+
+// Depth only, so there's no fragment stage. Both faces are drawn, since
+// open meshes can cast shadows with either side.
+pub fn create_render_pipeline(
+    device: &wgpu::Device,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    create(
+        device,
+        camera_bind_group_layout,
+        "Shadow Pipeline",
+        "vs_main",
+        None,
+    )
+}
+
+// Writes each fragment's distance from the light as its depth, for point
+// light shadow cubes
+pub fn create_point_render_pipeline(
+    device: &wgpu::Device,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    create(
+        device,
+        camera_bind_group_layout,
+        "Point Shadow Pipeline",
+        "vs_point",
+        Some("fs_point"),
+    )
+}
+
+fn create(
+    device: &wgpu::Device,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+    label: &str,
+    vertex_entry_point: &str,
+    fragment_entry_point: Option<&str>,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::include_wgsl!("../shadow.wgsl"));
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Shadow Pipeline Layout"),
+        bind_group_layouts: &[camera_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: vertex_entry_point,
+            buffers: &[Mesh::position_layout(), Mesh::instance_layout()],
+        },
+        fragment: fragment_entry_point.map(|entry_point| wgpu::FragmentState {
+            module: &shader,
+            entry_point,
+            targets: &[],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: SHADOW_MAP_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
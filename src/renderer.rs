@@ -1,20 +1,36 @@
 use winit::{dpi::PhysicalSize, window::Window};
 
+use crate::error::{Error, Result};
+use crate::texture::Texture;
+
+// Used when the adapter has them, with fallbacks otherwise
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_FORMAT_16BIT_NORM
+    .union(wgpu::Features::TEXTURE_COMPRESSION_BC)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR);
+
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+enum RenderTarget {
+    Surface(wgpu::Surface),
+    Offscreen(Texture),
+}
+
 pub struct RendererState {
-    surface: wgpu::Surface,
+    target: RenderTarget,
     pub surface_config: wgpu::SurfaceConfiguration,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
 }
 
 impl RendererState {
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window) -> Result<Self> {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
         });
 
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let surface = unsafe { instance.create_surface(&window) }?;
 
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
@@ -23,47 +39,177 @@ impl RendererState {
                 compatible_surface: Some(&surface),
             })
             .await
-            .unwrap();
+            .ok_or(Error::NoAdapter)?;
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("device"),
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let (device, queue) = Self::request_device(&adapter, wgpu::Limits::default()).await?;
 
         let size = window.inner_size();
         let surface_config = surface
             .get_default_config(&adapter, size.width, size.height)
-            .unwrap();
+            .ok_or(Error::IncompatibleSurface)?;
         surface.configure(&device, &surface_config);
 
-        Self {
-            surface,
+        Ok(Self {
+            target: RenderTarget::Surface(surface),
+            surface_config,
+            device,
+            queue,
+        })
+    }
+
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self> {
+        Self::new_headless_with_backends(width, height, wgpu::Backends::all()).await
+    }
+
+    pub async fn new_headless_with_backends(
+        width: u32,
+        height: u32,
+        backends: wgpu::Backends,
+    ) -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+        });
+
+        // Prefer real hardware, but fall back to a software adapter on
+        // machines without a GPU (CI, build boxes).
+        let adapter = match instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+        {
+            Some(adapter) => adapter,
+            None => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::LowPower,
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                })
+                .await
+                .ok_or(Error::NoAdapter)?,
+        };
+
+        let limits = wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits());
+        let (device, queue) = Self::request_device(&adapter, limits).await?;
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: OFFSCREEN_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let target = Texture::create_render_target(
+            &device,
+            width,
+            height,
+            surface_config.format,
+            "Offscreen Color Texture",
+        );
+
+        Ok(Self {
+            target: RenderTarget::Offscreen(target),
             surface_config,
             device,
             queue,
+        })
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        limits: wgpu::Limits,
+    ) -> Result<(wgpu::Device, wgpu::Queue)> {
+        let device_and_queue = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("device"),
+                    features: adapter.features() & OPTIONAL_FEATURES,
+                    limits,
+                },
+                None,
+            )
+            .await?;
+        Ok(device_and_queue)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen(_))
+    }
+
+    pub fn offscreen_texture(&self) -> Option<&Texture> {
+        match &self.target {
+            RenderTarget::Offscreen(texture) => Some(texture),
+            RenderTarget::Surface(_) => None,
         }
     }
 
+    pub fn read_offscreen_image(&self) -> Result<Option<image::DynamicImage>> {
+        self.offscreen_texture()
+            .map(|texture| {
+                crate::screenshot::read_color_image(&self.device, &self.queue, &texture.texture)
+            })
+            .transpose()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn render(
         &mut self,
         pipeline: &wgpu::RenderPipeline,
         camera_bind_group: &wgpu::BindGroup,
-        material_bind_group: &wgpu::BindGroup,
+        material_bind_groups: &[wgpu::BindGroup],
+        light_bind_group: &wgpu::BindGroup,
         mesh: &crate::mesh::Mesh,
         depth_texture_view: &wgpu::TextureView,
-    ) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    ) -> std::result::Result<(), wgpu::SurfaceError> {
+        match &self.target {
+            RenderTarget::Surface(surface) => {
+                let output = surface.get_current_texture()?;
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                self.render_to_view(
+                    &view,
+                    pipeline,
+                    camera_bind_group,
+                    material_bind_groups,
+                    light_bind_group,
+                    mesh,
+                    depth_texture_view,
+                );
+                output.present();
+            }
+            RenderTarget::Offscreen(texture) => {
+                self.render_to_view(
+                    &texture.view,
+                    pipeline,
+                    camera_bind_group,
+                    material_bind_groups,
+                    light_bind_group,
+                    mesh,
+                    depth_texture_view,
+                );
+            }
+        }
+
+        Ok(())
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_to_view(
+        &self,
+        view: &wgpu::TextureView,
+        pipeline: &wgpu::RenderPipeline,
+        camera_bind_group: &wgpu::BindGroup,
+        material_bind_groups: &[wgpu::BindGroup],
+        light_bind_group: &wgpu::BindGroup,
+        mesh: &crate::mesh::Mesh,
+        depth_texture_view: &wgpu::TextureView,
+    ) {
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -73,7 +219,7 @@ impl RendererState {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -97,33 +243,61 @@ impl RendererState {
         render_pass.set_pipeline(pipeline);
 
         render_pass.set_bind_group(0, camera_bind_group, &[]);
-        render_pass.set_bind_group(1, material_bind_group, &[]);
+        render_pass.set_bind_group(2, light_bind_group, &[]);
 
-        render_pass.set_vertex_buffer(0, mesh.buffer.slice(mesh.position_range()));
-        render_pass.set_vertex_buffer(1, mesh.buffer.slice(mesh.normal_range()));
-        render_pass.set_vertex_buffer(2, mesh.buffer.slice(mesh.tangent_range()));
-        render_pass.set_vertex_buffer(3, mesh.buffer.slice(mesh.texcoord_range()));
+        render_pass.set_vertex_buffer(5, mesh.instance_buffer.slice(..));
 
-        render_pass.set_index_buffer(
-            mesh.buffer.slice(mesh.index_range()),
-            wgpu::IndexFormat::Uint16,
-        );
+        for (instance, node_index) in (0u32..).zip(mesh.scene.mesh_nodes()) {
+            let mesh_index = mesh.scene.nodes[node_index].mesh.unwrap();
+            for primitive in mesh.mesh_primitives(mesh_index) {
+                render_pass.set_bind_group(1, &material_bind_groups[primitive.material_index], &[]);
 
-        render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+                render_pass.set_vertex_buffer(0, mesh.buffer.slice(primitive.position_range()));
+                render_pass.set_vertex_buffer(1, mesh.buffer.slice(primitive.normal_range()));
+                render_pass.set_vertex_buffer(2, mesh.buffer.slice(primitive.tangent_range()));
+                render_pass.set_vertex_buffer(3, mesh.buffer.slice(primitive.texcoord_range()));
+                render_pass.set_vertex_buffer(4, mesh.buffer.slice(primitive.texcoord_1_range()));
+
+                match primitive.index_range() {
+                    Some(index_range) => {
+                        render_pass.set_index_buffer(
+                            mesh.buffer.slice(index_range),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        render_pass.draw_indexed(
+                            0..primitive.index_count,
+                            0,
+                            instance..instance + 1,
+                        );
+                    }
+                    None => render_pass.draw(0..primitive.vertex_count, instance..instance + 1),
+                }
+            }
+        }
 
         drop(render_pass);
 
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.surface_config.width = new_size.width;
             self.surface_config.height = new_size.height;
-            self.surface.configure(&self.device, &self.surface_config);
+            match &mut self.target {
+                RenderTarget::Surface(surface) => {
+                    surface.configure(&self.device, &self.surface_config)
+                }
+                RenderTarget::Offscreen(texture) => {
+                    *texture = Texture::create_render_target(
+                        &self.device,
+                        new_size.width,
+                        new_size.height,
+                        self.surface_config.format,
+                        "Offscreen Color Texture",
+                    )
+                }
+            }
         }
     }
 }
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::texture::Mipmaps;
+
+// Everything a glTF sampler can set. Identical keys share one `wgpu::Sampler`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerKey {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+}
+
+// glTF leaves filtering up to the renderer when unset, and repeats by default
+impl Default for SamplerKey {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+fn address_mode(mode: gltf::texture::WrappingMode) -> wgpu::AddressMode {
+    use gltf::texture::WrappingMode;
+
+    match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    }
+}
+
+impl SamplerKey {
+    pub fn from_gltf(sampler: &gltf::texture::Sampler) -> Self {
+        use gltf::texture::{MagFilter, MinFilter};
+
+        let default = Self::default();
+        let mag_filter = match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+            Some(MagFilter::Linear) => wgpu::FilterMode::Linear,
+            None => default.mag_filter,
+        };
+        let (min_filter, mipmap_filter) = match sampler.min_filter() {
+            Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest) => {
+                (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+            }
+            Some(MinFilter::Linear | MinFilter::LinearMipmapNearest) => {
+                (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest)
+            }
+            Some(MinFilter::NearestMipmapLinear) => {
+                (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear)
+            }
+            Some(MinFilter::LinearMipmapLinear) => {
+                (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+            }
+            None => (default.min_filter, default.mipmap_filter),
+        };
+
+        Self {
+            address_mode_u: address_mode(sampler.wrap_s()),
+            address_mode_v: address_mode(sampler.wrap_t()),
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+        }
+    }
+}
+
+// NEAREST and LINEAR minification filters only ever read the base level, so
+// textures sampled with them don't need a mip chain
+pub fn gltf_mipmaps(sampler: &gltf::texture::Sampler) -> Mipmaps {
+    use gltf::texture::MinFilter;
+
+    match sampler.min_filter() {
+        Some(MinFilter::Nearest | MinFilter::Linear) => Mipmaps::Skip,
+        _ => Mipmaps::Generate,
+    }
+}
+
+// Samplers created so far, keyed by their settings
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerKey, Arc<wgpu::Sampler>>,
+}
+
+impl SamplerCache {
+    pub fn get(&mut self, device: &wgpu::Device, key: SamplerKey) -> Arc<wgpu::Sampler> {
+        self.samplers
+            .entry(key)
+            .or_insert_with(|| {
+                // Anisotropic filtering only applies when every filter is
+                // linear, and is ignored by devices without support for it
+                let linear = [key.mag_filter, key.min_filter, key.mipmap_filter]
+                    .iter()
+                    .all(|&filter| filter == wgpu::FilterMode::Linear);
+                Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Material Sampler"),
+                    address_mode_u: key.address_mode_u,
+                    address_mode_v: key.address_mode_v,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: key.mag_filter,
+                    min_filter: key.min_filter,
+                    mipmap_filter: key.mipmap_filter,
+                    anisotropy_clamp: if linear {
+                        std::num::NonZeroU8::new(16)
+                    } else {
+                        None
+                    },
+                    ..Default::default()
+                }))
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
@@ -0,0 +1,151 @@
+extern crate nalgebra_glm as glm;
+
+use crate::error::{Error, Result};
+
+pub struct Node {
+    pub name: Option<String>,
+    pub mesh: Option<usize>,
+    pub light: Option<usize>,
+    pub children: Vec<usize>,
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+    world_matrix: glm::Mat4,
+}
+
+impl Node {
+    pub fn local_matrix(&self) -> glm::Mat4 {
+        glm::translation(&self.translation)
+            * glm::quat_to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+
+    pub fn world_matrix(&self) -> glm::Mat4 {
+        self.world_matrix
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    pub normal: [[f32; 3]; 3],
+}
+
+impl InstanceRaw {
+    pub fn from_world_matrix(world_matrix: &glm::Mat4) -> Self {
+        let normal_matrix = glm::mat4_to_mat3(world_matrix)
+            .try_inverse()
+            .unwrap_or_else(glm::Mat3::identity)
+            .transpose();
+        Self {
+            model: (*world_matrix).into(),
+            normal: normal_matrix.into(),
+        }
+    }
+}
+
+pub struct Scene {
+    pub nodes: Vec<Node>,
+    pub roots: Vec<usize>,
+}
+
+impl Scene {
+    pub fn from_gltf(gltf: &gltf::Document) -> Result<Self> {
+        let nodes = gltf
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                Node {
+                    name: node.name().map(str::to_string),
+                    mesh: node.mesh().map(|mesh| mesh.index()),
+                    light: node.light().map(|light| light.index()),
+                    children: node.children().map(|child| child.index()).collect(),
+                    translation: translation.into(),
+                    rotation: glm::Quat::new(rotation[3], rotation[0], rotation[1], rotation[2]),
+                    scale: scale.into(),
+                    world_matrix: glm::Mat4::identity(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Documents without a scene still get drawn, starting from every
+        // node that isn't some other node's child
+        let roots = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => {
+                let mut is_child = vec![false; nodes.len()];
+                for node in &nodes {
+                    for &child in &node.children {
+                        is_child[child] = true;
+                    }
+                }
+                (0..nodes.len()).filter(|&index| !is_child[index]).collect()
+            }
+        };
+
+        let mut scene = Self { nodes, roots };
+        scene.update_world_matrices()?;
+        Ok(scene)
+    }
+
+    // Fails if a node is reached twice, since a node graph with cycles or
+    // shared children has no well defined world matrices
+    pub fn update_world_matrices(&mut self) -> Result<()> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack: Vec<(usize, glm::Mat4)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, glm::Mat4::identity()))
+            .collect();
+        while let Some((index, parent_matrix)) = stack.pop() {
+            if std::mem::replace(&mut visited[index], true) {
+                return Err(Error::InvalidNodeHierarchy { node: index });
+            }
+            let node = &mut self.nodes[index];
+            node.world_matrix = parent_matrix * node.local_matrix();
+            for &child in &node.children {
+                stack.push((child, node.world_matrix));
+            }
+        }
+        Ok(())
+    }
+
+    // Nodes matching `filter` that are reachable from the roots, depth first.
+    // Nodes reached again are skipped, so a hierarchy edited into a cycle
+    // after loading can't hang the traversal.
+    fn reachable_nodes(&self, filter: impl Fn(&Node) -> bool) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = self.roots.iter().rev().copied().collect();
+        while let Some(index) = stack.pop() {
+            if std::mem::replace(&mut visited[index], true) {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if filter(node) {
+                found.push(index);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        found
+    }
+
+    // Nodes with a mesh that are reachable from the roots, in the order their
+    // instances are laid out in the instance buffer
+    pub fn mesh_nodes(&self) -> Vec<usize> {
+        self.reachable_nodes(|node| node.mesh.is_some())
+    }
+
+    // Nodes with a light that are reachable from the roots
+    pub fn light_nodes(&self) -> Vec<usize> {
+        self.reachable_nodes(|node| node.light.is_some())
+    }
+
+    pub fn instances(&self) -> Vec<InstanceRaw> {
+        self.mesh_nodes()
+            .into_iter()
+            .map(|index| InstanceRaw::from_world_matrix(&self.nodes[index].world_matrix))
+            .collect()
+    }
+}
@@ -0,0 +1,241 @@
+use std::io::Write;
+
+use image::{DynamicImage, ImageBuffer, Luma};
+
+use crate::error::{Error, Result};
+use crate::pipelines::depth_readback_pipeline;
+use crate::texture::Texture;
+
+pub type DepthImage = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+fn padded_bytes_per_row(unpadded_bytes_per_row: u32) -> u32 {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded_bytes_per_row.div_ceil(alignment) * alignment
+}
+
+fn bytes_to_f32(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|float| f32::from_le_bytes([float[0], float[1], float[2], float[3]]))
+        .collect()
+}
+
+pub fn read_texture_bytes(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    aspect: wgpu::TextureAspect,
+) -> Result<Vec<u8>> {
+    let block_size = texture.format().describe().block_size as u32;
+    let (width, height) = (texture.width(), texture.height());
+    let unpadded_bytes_per_row = width * block_size;
+    let padded_bytes_per_row = padded_bytes_per_row(unpadded_bytes_per_row);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: std::num::NonZeroU32::new(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        sender.send(result).unwrap();
+    });
+    device.poll(wgpu::Maintain::Wait);
+    // A dropped callback never reports, which is as good as a failed mapping
+    receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+    // Strip the row padding required by the copy so the result is tightly packed
+    let mut bytes = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let padded = slice.get_mapped_range();
+        for row in padded.chunks(padded_bytes_per_row as usize) {
+            bytes.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+    Ok(bytes)
+}
+
+pub fn read_color_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<DynamicImage> {
+    let (width, height) = (texture.width(), texture.height());
+    let format = texture.format();
+    let mut bytes = read_texture_bytes(device, queue, texture, wgpu::TextureAspect::All)?;
+    let image = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+            DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, bytes).unwrap())
+        }
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            for pixel in bytes.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+            DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, bytes).unwrap())
+        }
+        wgpu::TextureFormat::Rgba16Float => {
+            let floats = bytes
+                .chunks_exact(2)
+                .map(|half| half::f16::from_le_bytes([half[0], half[1]]).to_f32())
+                .collect();
+            DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, floats).unwrap())
+        }
+        wgpu::TextureFormat::Rgba32Float => {
+            let floats = bytes_to_f32(&bytes);
+            DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, floats).unwrap())
+        }
+        format => {
+            return Err(Error::UnsupportedFormat(format!(
+                "readback of {:?} textures",
+                format
+            )))
+        }
+    };
+    Ok(image)
+}
+
+pub fn read_depth_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<DepthImage> {
+    if texture.format() != wgpu::TextureFormat::Depth32Float {
+        return Err(Error::UnsupportedFormat(format!(
+            "depth readback of {:?} textures",
+            texture.format()
+        )));
+    }
+
+    // Not every backend can copy depth textures to buffers, so draw the depth
+    // values into a float color texture first and read that back instead
+    let target = Texture::create_render_target(
+        device,
+        texture.width(),
+        texture.height(),
+        depth_readback_pipeline::FORMAT,
+        "Depth Readback Texture",
+    );
+    let depth_view = texture.create_view(&wgpu::TextureViewDescriptor {
+        aspect: wgpu::TextureAspect::DepthOnly,
+        ..Default::default()
+    });
+    let bind_group_layout = depth_readback_pipeline::create_bind_group_layout(device);
+    let bind_group =
+        depth_readback_pipeline::create_bind_group(device, &bind_group_layout, &depth_view);
+    let pipeline = depth_readback_pipeline::create_render_pipeline(device, &bind_group_layout);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Depth Readback Encoder"),
+    });
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Depth Readback Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &target.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(&pipeline);
+    render_pass.set_bind_group(0, &bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+    drop(render_pass);
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let bytes = read_texture_bytes(device, queue, &target.texture, wgpu::TextureAspect::All)?;
+    let depths = bytes_to_f32(&bytes);
+    Ok(ImageBuffer::from_raw(texture.width(), texture.height(), depths).unwrap())
+}
+
+pub fn save_color_image(image: &DynamicImage, path: &std::path::Path) -> Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "exr" => DynamicImage::ImageRgba32F(image.to_rgba32f()).save(path)?,
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()).save(path)?,
+    }
+    Ok(())
+}
+
+// Depth is kept as floats in EXR and PFM files. PNG can't hold floats, so
+// depths in [0, 1] are stored as 16 bit luma instead; other formats would lose
+// too much precision and are rejected.
+pub fn save_depth_image(image: &DepthImage, path: &std::path::Path) -> Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "pfm" => save_pfm(image, path)?,
+        "exr" => {
+            let rgb = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                let depth = image.get_pixel(x, y)[0];
+                image::Rgb([depth, depth, depth])
+            });
+            DynamicImage::ImageRgb32F(rgb).save(path)?
+        }
+        "png" => {
+            let luma = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                let depth = image.get_pixel(x, y)[0].clamp(0.0, 1.0);
+                Luma([(depth * 65535.0).round() as u16])
+            });
+            DynamicImage::ImageLuma16(luma).save(path)?
+        }
+        _ => {
+            return Err(Error::UnsupportedFormat(format!(
+                "depth images saved as '{}', use .exr, .pfm or .png",
+                path.display()
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn save_pfm(image: &DepthImage, path: &std::path::Path) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    // Grayscale PFM, negative scale marks little-endian, rows stored bottom to top
+    write!(file, "Pf\n{} {}\n-1.0\n", image.width(), image.height())?;
+    for row in image.rows().rev() {
+        for pixel in row {
+            file.write_all(&pixel[0].to_le_bytes())?;
+        }
+    }
+    file.flush()
+}
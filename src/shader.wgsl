@@ -1,6 +1,7 @@
 // Vertex shader
 struct CameraUniform {
     view_proj: mat4x4<f32>,
+    position: vec4<f32>,
 };
 
 @group(0) @binding(0)
@@ -11,6 +12,17 @@ struct VertexInput {
     @location(1) normal: vec3<f32>,
     @location(2) tangent: vec4<f32>,
     @location(3) texcoords: vec2<f32>,
+    @location(11) texcoords_1: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(4) model_0: vec4<f32>,
+    @location(5) model_1: vec4<f32>,
+    @location(6) model_2: vec4<f32>,
+    @location(7) model_3: vec4<f32>,
+    @location(8) normal_0: vec3<f32>,
+    @location(9) normal_1: vec3<f32>,
+    @location(10) normal_2: vec3<f32>,
 };
 
 struct VertexOutput {
@@ -19,20 +31,51 @@ struct VertexOutput {
     @location(1) normal: vec3<f32>,
     @location(2) tangent: vec4<f32>,
     @location(3) texcoords: vec2<f32>,
+    @location(4) texcoords_1: vec2<f32>,
 };
 
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_0,
+        instance.normal_1,
+        instance.normal_2,
+    );
+    let model_3x3 = mat3x3<f32>(model_matrix[0].xyz, model_matrix[1].xyz, model_matrix[2].xyz);
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
     var out: VertexOutput;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
-    out.position = model.position;
-    out.normal = model.normal;
-    out.tangent = model.tangent;
+    out.clip_position = camera.view_proj * world_position;
+    out.position = world_position.xyz;
+    out.normal = normalize(normal_matrix * model.normal);
+    out.tangent = vec4<f32>(normalize(model_3x3 * model.tangent.xyz), model.tangent.w);
     out.texcoords = model.texcoords;
+    out.texcoords_1 = model.texcoords_1;
     return out;
 }
 
 // Fragment shader
+struct MaterialUniform {
+    base_color_factor: vec4<f32>,
+    emissive_factor: vec3<f32>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    albedo_texcoord: u32,
+    normal_texcoord: u32,
+    roughness_metalness_texcoord: u32,
+    occlusion_texcoord: u32,
+    emissive_texcoord: u32,
+};
+
 @group(1) @binding(0)
 var t_albedo: texture_2d<f32>;
 @group(1) @binding(1)
@@ -40,19 +83,333 @@ var t_normal: texture_2d<f32>;
 @group(1) @binding(2)
 var t_roughness_metalness: texture_2d<f32>;
 @group(1) @binding(3)
-var s_material: sampler;
+var s_albedo: sampler;
+@group(1) @binding(4)
+var t_occlusion: texture_2d<f32>;
+@group(1) @binding(5)
+var t_emissive: texture_2d<f32>;
+@group(1) @binding(6)
+var<uniform> material: MaterialUniform;
+@group(1) @binding(7)
+var s_normal: sampler;
+@group(1) @binding(8)
+var s_roughness_metalness: sampler;
+@group(1) @binding(9)
+var s_occlusion: sampler;
+@group(1) @binding(10)
+var s_emissive: sampler;
+
+fn texcoords(in: VertexOutput, texcoord_set: u32) -> vec2<f32> {
+    return select(in.texcoords, in.texcoords_1, texcoord_set == 1u);
+}
+
+// Punctual lights in world space, matching `LightUniform` in light.rs
+struct Light {
+    position: vec3<f32>,
+    // Zero when the light reaches any distance
+    range: f32,
+    direction: vec3<f32>,
+    kind: u32,
+    color: vec3<f32>,
+    intensity: f32,
+    angle_scale: f32,
+    angle_offset: f32,
+    // First of `cascade_count` layers, or the shadow cube of point lights.
+    // Negative when the light casts no shadow.
+    shadow_layer: i32,
+    depth_bias: f32,
+    normal_bias: f32,
+    pcf_radius: f32,
+    cascade_count: u32,
+    // Distance stored as depth 1 in point light shadow cubes
+    shadow_far: f32,
+    // Width each cascade covers, at unit distance for spot lights
+    shadow_extents: vec4<f32>,
+    shadow_view_projs: array<mat4x4<f32>, 4>,
+};
+
+struct Lights {
+    count: u32,
+    // Non-zero to tint surfaces by their directional shadow cascade
+    debug_cascades: u32,
+    lights: array<Light>,
+};
+
+@group(2) @binding(0)
+var<storage, read> lights: Lights;
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d_array;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+// Faces of point light shadow cubes, six layers to a cube. Faces are picked
+// in the shader, as not every backend can compare against cube arrays.
+@group(2) @binding(3)
+var t_shadow_cube_faces: texture_depth_2d_array;
+
+const LIGHT_DIRECTIONAL: u32 = 0u;
+const LIGHT_POINT: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+// A constant stand-in for image based lighting
+const AMBIENT_INTENSITY: f32 = 0.1;
+
+const PI: f32 = 3.14159265359;
+
+// The metallic-roughness BRDF from appendix B of the glTF 2.0 specification,
+// written as in the Khronos glTF Sample Viewer. `alpha` is the perceptual
+// roughness squared.
+fn fresnel_schlick(f0: vec3<f32>, v_dot_h: f32) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - v_dot_h, 0.0, 1.0), 5.0);
+}
+
+// Height-correlated Smith visibility, which folds the specular BRDF's
+// 4 * N.L * N.V denominator into the geometry term
+fn visibility_smith_ggx(n_dot_l: f32, n_dot_v: f32, alpha: f32) -> f32 {
+    let alpha_squared = alpha * alpha;
+    let ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared);
+    let ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared);
+    let ggx = ggx_v + ggx_l;
+    return select(0.0, 0.5 / ggx, ggx > 0.0);
+}
+
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha_squared = alpha * alpha;
+    let f = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (PI * f * f);
+}
+
+// Radiance reflected towards `v` from a light of `intensity` arriving from `l`
+fn brdf(
+    n: vec3<f32>,
+    v: vec3<f32>,
+    l: vec3<f32>,
+    intensity: vec3<f32>,
+    diffuse_color: vec3<f32>,
+    f0: vec3<f32>,
+    alpha: f32,
+) -> vec3<f32> {
+    let h = normalize(l + v);
+    let n_dot_l = clamp(dot(n, l), 0.0, 1.0);
+    let n_dot_v = clamp(dot(n, v), 0.0, 1.0);
+    let n_dot_h = clamp(dot(n, h), 0.0, 1.0);
+    let v_dot_h = clamp(dot(v, h), 0.0, 1.0);
+    if n_dot_l <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+
+    let f = fresnel_schlick(f0, v_dot_h);
+    let diffuse = (1.0 - f) * diffuse_color / PI;
+    let specular = f * visibility_smith_ggx(n_dot_l, n_dot_v, alpha) * distribution_ggx(n_dot_h, alpha);
+    return intensity * n_dot_l * (diffuse + specular);
+}
+
+// The KHR_lights_punctual falloff: inverse square, smoothly windowed to
+// zero at the light's range
+fn range_attenuation(range: f32, distance: f32) -> f32 {
+    let inverse_square = 1.0 / max(distance * distance, 0.0001);
+    if range <= 0.0 {
+        return inverse_square;
+    }
+    let ratio = distance / range;
+    return clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0) * inverse_square;
+}
+
+fn spot_attenuation(light: Light, l: vec3<f32>) -> f32 {
+    let cos_angle = dot(light.direction, -l);
+    let attenuation = clamp(cos_angle * light.angle_scale + light.angle_offset, 0.0, 1.0);
+    return attenuation * attenuation;
+}
+
+// The direction towards a light, and the light arriving from it
+struct IncidentLight {
+    l: vec3<f32>,
+    intensity: vec3<f32>,
+};
+
+fn incident_light(light: Light, position: vec3<f32>) -> IncidentLight {
+    var intensity = light.color * light.intensity;
+    if light.kind == LIGHT_DIRECTIONAL {
+        return IncidentLight(-light.direction, intensity);
+    }
+    let to_light = light.position - position;
+    let l = normalize(to_light);
+    intensity *= range_attenuation(light.range, length(to_light));
+    if light.kind == LIGHT_SPOT {
+        intensity *= spot_attenuation(light, l);
+    }
+    return IncidentLight(l, intensity);
+}
+
+struct ShadowSample {
+    lit: f32,
+    // Negative when no shadow map covers the position
+    cascade: i32,
+};
+
+struct CubeFaceCoords {
+    face: i32,
+    uv: vec2<f32>,
+};
+
+// The face of a cube `direction` points at and where it lands on it, with
+// faces ordered +X, -X, +Y, -Y, +Z, -Z and oriented as in Vulkan and D3D
+fn cube_face_coords(direction: vec3<f32>) -> CubeFaceCoords {
+    let size = abs(direction);
+    var face: i32;
+    var coords: vec2<f32>;
+    var major: f32;
+    if size.x >= size.y && size.x >= size.z {
+        face = select(1, 0, direction.x > 0.0);
+        coords = vec2<f32>(-sign(direction.x) * direction.z, -direction.y);
+        major = size.x;
+    } else if size.y >= size.z {
+        face = select(3, 2, direction.y > 0.0);
+        coords = vec2<f32>(direction.x, sign(direction.y) * direction.z);
+        major = size.y;
+    } else {
+        face = select(5, 4, direction.z > 0.0);
+        coords = vec2<f32>(sign(direction.z) * direction.x, -direction.y);
+        major = size.z;
+    }
+    return CubeFaceCoords(face, 0.5 * coords / major + 0.5);
+}
+
+// The fraction of a point light reaching `position`, comparing its distance
+// from the light against the distances in the light's shadow cube. Like other
+// shadows, it's averaged over a 3x3 grid of comparisons, after pushing the
+// position out along the normal. The grid stays on one face, clamping at
+// its edges.
+fn point_shadow_factor(light: Light, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let size = f32(textureDimensions(t_shadow_cube_faces).x);
+    let texel_size = light.shadow_extents.x * distance(light.position, position) / size;
+    let direction = position + normal * light.normal_bias * texel_size - light.position;
+    let depth = length(direction) / light.shadow_far - light.depth_bias;
+    // Nothing beyond the cube's far distance casts a shadow
+    if depth > 1.0 {
+        return 1.0;
+    }
+
+    let coords = cube_face_coords(direction);
+    let layer = 6 * light.shadow_layer + coords.face;
+    let step = light.pcf_radius / size;
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * step;
+            lit += textureSampleCompareLevel(t_shadow_cube_faces, s_shadow, coords.uv + offset, layer, depth);
+        }
+    }
+    return lit / 9.0;
+}
+
+// The fraction of the light at `index` reaching `position`, averaged over a
+// 3x3 grid of comparisons, and the cascade it was read from. Cascades are
+// tried nearest first, and the first one covering the whole grid is used.
+// The position is first pushed out along the geometric normal by a few shadow
+// map texels, and its depth towards the light.
+fn shadow_factor(index: u32, position: vec3<f32>, normal: vec3<f32>) -> ShadowSample {
+    let light = &lights.lights[index];
+    if (*light).shadow_layer < 0 {
+        return ShadowSample(1.0, -1);
+    }
+    if (*light).kind == LIGHT_POINT {
+        return ShadowSample(point_shadow_factor(*light, position, normal), -1);
+    }
+    let size = f32(textureDimensions(t_shadow).x);
+    let step = (*light).pcf_radius / size;
+    let margin = 2.0 * (step + 1.0 / size);
+    for (var cascade = 0u; cascade < (*light).cascade_count; cascade += 1u) {
+        var texel_size = (*light).shadow_extents[cascade] / size;
+        if (*light).kind == LIGHT_SPOT {
+            texel_size *= distance((*light).position, position);
+        }
+        let offset_position = position + normal * (*light).normal_bias * texel_size;
+        let clip = (*light).shadow_view_projs[cascade] * vec4<f32>(offset_position, 1.0);
+        let ndc = clip.xyz / clip.w;
+        if clip.w <= 0.0 || any(abs(ndc.xy) > vec2<f32>(1.0 - margin)) || ndc.z > 1.0 {
+            continue;
+        }
+
+        let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+        let depth = ndc.z - (*light).depth_bias;
+        let layer = (*light).shadow_layer + i32(cascade);
+        var lit = 0.0;
+        for (var y = -1; y <= 1; y += 1) {
+            for (var x = -1; x <= 1; x += 1) {
+                let offset = vec2<f32>(f32(x), f32(y)) * step;
+                lit += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, layer, depth);
+            }
+        }
+        return ShadowSample(lit / 9.0, i32(cascade));
+    }
+    // Nothing outside the shadow maps' frustums casts a shadow
+    return ShadowSample(1.0, -1);
+}
+
+fn cascade_tint(cascade: i32) -> vec3<f32> {
+    switch cascade {
+        case 0 {
+            return vec3<f32>(1.0, 0.3, 0.3);
+        }
+        case 1 {
+            return vec3<f32>(0.3, 1.0, 0.3);
+        }
+        case 2 {
+            return vec3<f32>(0.3, 0.3, 1.0);
+        }
+        default {
+            return vec3<f32>(1.0, 1.0, 0.3);
+        }
+    }
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let tangent = normalize(in.tangent.xyz - dot(in.tangent.xyz, in.normal) * in.normal);
-    let bitangent = cross(tangent, in.normal);
+    // glTF tangents carry the bitangent's handedness in w, which flips on
+    // mirrored UVs
+    let bitangent = cross(in.normal, tangent) * in.tangent.w;
     let tbn_matrix = mat3x3<f32>(tangent, bitangent, in.normal);
-    let normal_map = 2.0 * textureSample(t_normal, s_material, in.texcoords).rgb - 1.0;
+    var normal_map = 2.0 * textureSample(t_normal, s_normal, texcoords(in, material.normal_texcoord)).rgb - 1.0;
+    normal_map = vec3<f32>(normal_map.xy * material.normal_scale, normal_map.z);
     let normal = normalize(tbn_matrix * normal_map);
 
-    //let roughness_metalness = textureSample(t_roughness_metalness, s_material, in.texcoords).rg;
+    // Albedo and emissive maps are sRGB, and are decoded when sampled
+    let albedo = material.base_color_factor
+        * textureSample(t_albedo, s_albedo, texcoords(in, material.albedo_texcoord));
+    // Roughness is stored in green and metalness in blue
+    let roughness_metalness = textureSample(t_roughness_metalness, s_roughness_metalness, texcoords(in, material.roughness_metalness_texcoord));
+    let roughness = clamp(material.roughness_factor * roughness_metalness.g, 0.0, 1.0);
+    let metalness = clamp(material.metallic_factor * roughness_metalness.b, 0.0, 1.0);
+    let occlusion = textureSample(t_occlusion, s_occlusion, texcoords(in, material.occlusion_texcoord)).r;
+    let emissive = material.emissive_factor
+        * textureSample(t_emissive, s_emissive, texcoords(in, material.emissive_texcoord)).rgb;
+
+    // Dielectrics reflect 4% at normal incidence, metals tint their
+    // reflections and have no diffuse term
+    let diffuse_color = mix(albedo.rgb, vec3<f32>(0.0), metalness);
+    let f0 = mix(vec3<f32>(0.04), albedo.rgb, metalness);
+    let alpha = roughness * roughness;
+
+    let v = normalize(camera.position.xyz - in.position);
+    var color = vec3<f32>(0.0);
+    var cascade = -1;
+    for (var index = 0u; index < lights.count; index += 1u) {
+        let incident = incident_light(lights.lights[index], in.position);
+        let shadow = shadow_factor(index, in.position, normalize(in.normal));
+        color += shadow.lit * brdf(normal, v, incident.l, incident.intensity, diffuse_color, f0, alpha);
+        if cascade < 0 && lights.lights[index].kind == LIGHT_DIRECTIONAL {
+            cascade = shadow.cascade;
+        }
+    }
 
-    let object_color = 0.5 * (normal + 1.0);
+    // Occlusion only darkens indirect light
+    let ambient = AMBIENT_INTENSITY * (diffuse_color + f0);
+    color += mix(ambient, ambient * occlusion, material.occlusion_strength);
+    color += emissive;
 
-    return vec4<f32>(object_color, 1.0);
+    if lights.debug_cascades != 0u && cascade >= 0 {
+        color *= cascade_tint(cascade);
+    }
+    return vec4<f32>(color, albedo.a);
 }
@@ -0,0 +1,368 @@
+use bytemuck::Zeroable;
+use wgpu::util::DeviceExt;
+
+use crate::bind_groups::camera_bind_group;
+use crate::camera::{Camera, CameraIntrinsics, CameraUniform};
+use crate::light::{LightUniform, LIGHT_POINT};
+use crate::mesh::{Bounds, Mesh};
+use crate::texture::Texture;
+
+extern crate nalgebra_glm as glm;
+
+pub const SHADOW_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+pub const DEFAULT_SHADOW_MAP_SIZE: u32 = 2048;
+pub const DEFAULT_SHADOW_CUBE_SIZE: u32 = 512;
+pub const DEFAULT_MAX_POINT_SHADOWS: u32 = 4;
+// Matches the length of `Light::shadow_view_projs` in shader.wgsl
+pub const MAX_CASCADES: usize = 4;
+// How far cascade splits lean from uniform (0) to logarithmic (1) spacing
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+
+// Layers needed for every directional and spot light in `lights` that casts
+// a shadow
+pub fn layer_count(lights: &[LightUniform]) -> u32 {
+    lights
+        .iter()
+        .filter(|light| light.kind != LIGHT_POINT && light.shadow_layer >= 0)
+        .map(|light| light.cascade_count)
+        .sum()
+}
+
+// The far distance of each of `count` cascades dividing the view between the
+// camera's near and far planes. Logarithmic splits keep shadow texels the
+// same size on screen at any distance, but leave the nearest cascades very
+// thin, so they are blended with uniform ones.
+pub fn cascade_splits(intrinsics: &CameraIntrinsics, count: usize) -> Vec<f32> {
+    let (near, far) = (intrinsics.near, intrinsics.far);
+    (1..=count)
+        .map(|index| {
+            let fraction = index as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(fraction);
+            let uniform = near + (far - near) * fraction;
+            CASCADE_SPLIT_LAMBDA * logarithmic + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform
+        })
+        .collect()
+}
+
+// Any vector not parallel to `direction`, to orient a view looking along it
+pub fn up_vector(direction: &glm::Vec3) -> glm::Vec3 {
+    if direction.y.abs() > 0.99 {
+        glm::Vec3::z()
+    } else {
+        glm::Vec3::y()
+    }
+}
+
+// An orthographic projection looking along `direction` at the slice of the
+// camera's view between `near` and `far`, deep enough to take in every caster
+// in `bounds`, and the width it covers. The slice is enclosed in a sphere, so
+// the width stays the same as the camera turns, and the sphere's center is
+// snapped to whole shadow map texels, so shadow edges don't shimmer as the
+// camera moves.
+pub fn cascade_view_proj(
+    direction: &glm::Vec3,
+    camera: &Camera,
+    near: f32,
+    far: f32,
+    bounds: &Bounds,
+    map_size: u32,
+) -> (glm::Mat4, f32) {
+    let intrinsics = &camera.intrinsics;
+    let half_height = (intrinsics.fovy / 2.0).tan();
+    let half_width = half_height * intrinsics.aspect;
+    let center_depth = (near + far) / 2.0;
+    let radius = [near, far]
+        .iter()
+        .map(|depth| {
+            let corner = glm::vec3(
+                depth * half_width,
+                depth * half_height,
+                depth - center_depth,
+            );
+            glm::length(&corner)
+        })
+        .fold(0.0, f32::max);
+    let center = glm::inverse(&camera.view_matrix) * glm::vec4(0.0, 0.0, -center_depth, 1.0);
+
+    let view = glm::look_at_rh(&glm::Vec3::zeros(), direction, &up_vector(direction));
+    let texel_size = 2.0 * radius / map_size as f32;
+    let center = (view * center)
+        .xy()
+        .map(|x| (x / texel_size).floor() * texel_size);
+    let scene_depth = -(view * bounds.center().push(1.0)).z;
+    let scene_radius = bounds.radius();
+    let proj = glm::ortho_rh_zo(
+        center.x - radius,
+        center.x + radius,
+        center.y - radius,
+        center.y + radius,
+        scene_depth - scene_radius,
+        scene_depth + scene_radius,
+    );
+    (proj * view, 2.0 * radius)
+}
+
+// How many shadow maps are rendered, and how large
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowConfig {
+    // Width and height of directional and spot light shadow maps
+    pub map_size: u32,
+    // Width and height of each face of point light shadow cubes
+    pub cube_size: u32,
+    // Point lights nearest the camera cast shadows, up to this many, since
+    // each one renders the scene six times
+    pub max_point_shadows: u32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            map_size: DEFAULT_SHADOW_MAP_SIZE,
+            cube_size: DEFAULT_SHADOW_CUBE_SIZE,
+            max_point_shadows: DEFAULT_MAX_POINT_SHADOWS,
+        }
+    }
+}
+
+// Cubes needed for every point light in `lights` that casts a shadow
+pub fn cube_count(lights: &[LightUniform]) -> u32 {
+    lights
+        .iter()
+        .filter(|light| light.kind == LIGHT_POINT && light.shadow_layer >= 0)
+        .count() as u32
+}
+
+// The projection of each face of a point light's shadow cube, ordered +X,
+// -X, +Y, -Y, +Z, -Z and oriented as in Vulkan and D3D. Cube faces are
+// indexed with rows running down, so rows are flipped to match.
+pub fn cube_face_view_projs(position: &glm::Vec3, far: f32) -> [glm::Mat4; 6] {
+    let faces = [
+        (glm::Vec3::x(), -glm::Vec3::y()),
+        (-glm::Vec3::x(), -glm::Vec3::y()),
+        (glm::Vec3::y(), glm::Vec3::z()),
+        (-glm::Vec3::y(), -glm::Vec3::z()),
+        (glm::Vec3::z(), -glm::Vec3::y()),
+        (-glm::Vec3::z(), -glm::Vec3::y()),
+    ];
+    let flip_rows = glm::scaling(&glm::vec3(1.0, -1.0, 1.0));
+    let proj = flip_rows * glm::perspective_zo(1.0, std::f32::consts::FRAC_PI_2, far * 0.001, far);
+    faces.map(|(forward, up)| proj * glm::look_at_rh(position, &(position + forward), &up))
+}
+
+// The GL backend creates square textures with a multiple of six layers as
+// cube maps, which can't be sampled as arrays, so those get a spare layer.
+// There's always at least one layer to bind.
+fn array_layer_count(layer_count: u32) -> u32 {
+    match layer_count.max(1) {
+        count if count.is_multiple_of(6) => count + 1,
+        count => count,
+    }
+}
+
+struct ShadowLayer {
+    view: wgpu::TextureView,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+}
+
+impl ShadowLayer {
+    fn new(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        view: wgpu::TextureView,
+    ) -> Self {
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group =
+            camera_bind_group::create_bind_group(device, camera_bind_group_layout, &camera_buffer);
+        Self {
+            view,
+            camera_buffer,
+            camera_bind_group,
+        }
+    }
+
+    fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        pipeline: &wgpu::RenderPipeline,
+        mesh: &Mesh,
+        camera: CameraUniform,
+    ) {
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera]));
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Render Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, mesh.instance_buffer.slice(..));
+        draw_depth(&mut render_pass, mesh);
+    }
+}
+
+// Square depth maps in the layers of a 2D array, and the faces of shadow cubes
+// in another, read through a comparison sampler. Each shadow casting light
+// takes a layer per cascade, except point lights, which take six faces each.
+// Faces are kept in a plain array, since not every backend can compare
+// against cube arrays.
+pub struct ShadowMaps {
+    pub texture: Texture,
+    pub cube_texture: Texture,
+    pub sampler: wgpu::Sampler,
+    layers: Vec<ShadowLayer>,
+    cube_faces: Vec<ShadowLayer>,
+}
+
+impl ShadowMaps {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        config: &ShadowConfig,
+        layer_count: u32,
+        cube_count: u32,
+    ) -> Self {
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let texture = Texture::create_2d_array(
+            device,
+            (config.map_size, config.map_size),
+            array_layer_count(layer_count),
+            SHADOW_MAP_FORMAT,
+            1,
+            usage,
+            "Shadow Maps",
+        );
+        let cube_texture = Texture::create_2d_array(
+            device,
+            (config.cube_size, config.cube_size),
+            array_layer_count(cube_count.max(1) * 6),
+            SHADOW_MAP_FORMAT,
+            1,
+            usage,
+            "Shadow Cubes",
+        );
+        // Linear filtering blends neighboring comparisons, on top of PCF
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let layers = (0..layer_count)
+            .map(|layer| {
+                ShadowLayer::new(
+                    device,
+                    camera_bind_group_layout,
+                    texture.layer_view(layer, 0),
+                )
+            })
+            .collect();
+        let cube_faces = (0..cube_count * 6)
+            .map(|face| {
+                ShadowLayer::new(
+                    device,
+                    camera_bind_group_layout,
+                    cube_texture.layer_view(face, 0),
+                )
+            })
+            .collect();
+        Self {
+            texture,
+            cube_texture,
+            sampler,
+            layers,
+            cube_faces,
+        }
+    }
+
+    // Renders the depth of `mesh` into the layers of each light that casts a
+    // shadow. Point lights render the distance to them instead, with
+    // `point_pipeline`, once for each face of their cube.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipeline: &wgpu::RenderPipeline,
+        point_pipeline: &wgpu::RenderPipeline,
+        mesh: &Mesh,
+        lights: &[LightUniform],
+    ) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Shadow Encoder"),
+        });
+        for light in lights.iter().filter(|light| light.shadow_layer >= 0) {
+            let position = light.position;
+            if light.kind == LIGHT_POINT {
+                let view_projs = cube_face_view_projs(&position.into(), light.shadow_far);
+                let first_face = light.shadow_layer as usize * 6;
+                let Some(faces) = self.cube_faces.get(first_face..first_face + 6) else {
+                    log::warn!(
+                        "Point light needs shadow cube {} but only {} were created, see cube_count",
+                        light.shadow_layer,
+                        self.cube_faces.len() / 6
+                    );
+                    continue;
+                };
+                for (face, view_proj) in faces.iter().zip(view_projs) {
+                    // The position's w holds the distance stored as depth 1
+                    let camera = CameraUniform {
+                        view_proj: view_proj.into(),
+                        position: [position[0], position[1], position[2], light.shadow_far],
+                    };
+                    face.render(&mut encoder, queue, point_pipeline, mesh, camera);
+                }
+                continue;
+            }
+
+            for cascade in 0..light.cascade_count as usize {
+                let Some(layer) = self.layers.get(light.shadow_layer as usize + cascade) else {
+                    log::warn!(
+                        "Light needs shadow layer {} but only {} were created, see layer_count",
+                        light.shadow_layer as usize + cascade,
+                        self.layers.len()
+                    );
+                    continue;
+                };
+                let camera = CameraUniform {
+                    view_proj: light.shadow_view_projs[cascade],
+                    position: [position[0], position[1], position[2], 1.0],
+                };
+                layer.render(&mut encoder, queue, pipeline, mesh, camera);
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+// Draws every primitive of every mesh instance with positions alone
+fn draw_depth<'a>(render_pass: &mut wgpu::RenderPass<'a>, mesh: &'a Mesh) {
+    for (instance, node_index) in (0u32..).zip(mesh.scene.mesh_nodes()) {
+        let mesh_index = mesh.scene.nodes[node_index].mesh.unwrap();
+        for primitive in mesh.mesh_primitives(mesh_index) {
+            render_pass.set_vertex_buffer(0, mesh.buffer.slice(primitive.position_range()));
+            match primitive.index_range() {
+                Some(index_range) => {
+                    render_pass.set_index_buffer(
+                        mesh.buffer.slice(index_range),
+                        wgpu::IndexFormat::Uint32,
+                    );
+                    render_pass.draw_indexed(0..primitive.index_count, 0, instance..instance + 1);
+                }
+                None => render_pass.draw(0..primitive.vertex_count, instance..instance + 1),
+            }
+        }
+    }
+}
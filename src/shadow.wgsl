@@ -0,0 +1,52 @@
+// Renders the depth of every mesh instance as seen from a light, or for
+// point lights its distance from the light
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> light_camera: CameraUniform;
+
+struct InstanceInput {
+    @location(4) model_0: vec4<f32>,
+    @location(5) model_1: vec4<f32>,
+    @location(6) model_2: vec4<f32>,
+    @location(7) model_3: vec4<f32>,
+};
+
+fn to_world(position: vec3<f32>, instance: InstanceInput) -> vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    return model_matrix * vec4<f32>(position, 1.0);
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    return light_camera.view_proj * to_world(position, instance);
+}
+
+struct PointVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_point(@location(0) position: vec3<f32>, instance: InstanceInput) -> PointVertexOutput {
+    let world_position = to_world(position, instance);
+    var out: PointVertexOutput;
+    out.clip_position = light_camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+// Point lights keep the distance they reach in the position's w, which is
+// stored as depth 1
+@fragment
+fn fs_point(in: PointVertexOutput) -> @builtin(frag_depth) f32 {
+    return distance(in.world_position, light_camera.position.xyz) / light_camera.position.w;
+}
@@ -0,0 +1,65 @@
+extern crate nalgebra_glm as glm;
+
+use crate::mesh::PrimitiveData;
+
+// MikkTSpace works on face corners, so a corner may get a different tangent
+// than another corner sharing the same vertex (at UV seams and mirrors)
+struct Corners<'a>(&'a mut PrimitiveData);
+
+impl mikktspace::Geometry for Corners<'_> {
+    fn num_faces(&self) -> usize {
+        self.0.positions.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.0.positions[face * 3 + vert]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.0.normals[face * 3 + vert]
+    }
+
+    // glTF puts the UV origin at the top left, MikkTSpace (like Blender)
+    // expects it at the bottom left
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let [u, v] = self.0.texcoords[face * 3 + vert];
+        [u, 1.0 - v]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.0.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+// Fills in MikkTSpace tangents for a triangle list. Corners that end up
+// identical are welded back together, so the primitive is always indexed
+// afterwards.
+pub fn generate(data: &mut PrimitiveData) {
+    data.unweld();
+    // Degenerate geometry keeps these fallback tangents
+    data.tangents = vec![[1.0, 0.0, 0.0, 1.0]; data.positions.len()];
+    mikktspace::generate_tangents(&mut Corners(data));
+    data.weld();
+}
+
+// Without UVs (or triangles) there's no tangent space to follow, so every
+// vertex just gets some unit tangent perpendicular to its normal
+pub fn perpendicular(data: &mut PrimitiveData) {
+    data.tangents = data
+        .normals
+        .iter()
+        .map(|&normal| {
+            let normal = glm::Vec3::from(normal);
+            let axis = match normal.x.abs() < 0.9 {
+                true => glm::Vec3::x(),
+                false => glm::Vec3::y(),
+            };
+            let tangent = (axis - normal * normal.dot(&axis)).normalize();
+            [tangent.x, tangent.y, tangent.z, 1.0]
+        })
+        .collect();
+}
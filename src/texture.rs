@@ -1,9 +1,180 @@
+use std::io::Read;
+
+use crate::basis;
+use crate::decompress::decompress;
+use crate::error::{Error, Result};
+use crate::pipelines::{equirectangular_pipeline, mipmap_pipeline};
+use wgpu::util::DeviceExt;
+
+// Color maps (albedo, emissive) are stored sRGB encoded, data maps (normal,
+// occlusion, metallic-roughness) are linear
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn rgba8_format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+// Loaded textures get a full mip chain unless they opt out, e.g. for UI
+// images drawn at their native size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mipmaps {
+    #[default]
+    Generate,
+    Skip,
+}
+
+// Levels in a full chain down to 1x1
+pub fn mip_level_count(dimensions: (u32, u32)) -> u32 {
+    32 - dimensions.0.max(dimensions.1).max(1).leading_zeros()
+}
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
+    pub view_dimension: wgpu::TextureViewDimension,
 }
 
 impl Texture {
+    // Cube maps hold six faces per cube, in +X, -X, +Y, -Y, +Z, -Z order, and
+    // are viewed as a cube array when there's more than one
+    pub fn create_cube(
+        device: &wgpu::Device,
+        face_size: u32,
+        cube_count: u32,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        usage: wgpu::TextureUsages,
+        label: &str,
+    ) -> Self {
+        let view_dimension = match cube_count {
+            1 => wgpu::TextureViewDimension::Cube,
+            _ => wgpu::TextureViewDimension::CubeArray,
+        };
+        Self::create(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: face_size,
+                    height: face_size,
+                    depth_or_array_layers: 6 * cube_count,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            },
+            view_dimension,
+        )
+    }
+
+    // Viewed as an array even with a single layer, so shaders declare the
+    // same binding whatever the layer count
+    pub fn create_2d_array(
+        device: &wgpu::Device,
+        dimensions: (u32, u32),
+        layers: u32,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        usage: wgpu::TextureUsages,
+        label: &str,
+    ) -> Self {
+        Self::create(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: dimensions.0,
+                    height: dimensions.1,
+                    depth_or_array_layers: layers,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            },
+            wgpu::TextureViewDimension::D2Array,
+        )
+    }
+
+    pub fn create_3d(
+        device: &wgpu::Device,
+        size: wgpu::Extent3d,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        usage: wgpu::TextureUsages,
+        label: &str,
+    ) -> Self {
+        Self::create(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format,
+                usage,
+                view_formats: &[],
+            },
+            wgpu::TextureViewDimension::D3,
+        )
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        descriptor: &wgpu::TextureDescriptor,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> Self {
+        let texture = device.create_texture(descriptor);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(view_dimension),
+            ..Default::default()
+        });
+        Self {
+            texture,
+            view,
+            view_dimension,
+        }
+    }
+
+    // A 2D view of one level of an array layer or cube face, to render into
+    // or sample on its own
+    pub fn layer_view(&self, layer: u32, level: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Texture Layer View"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: std::num::NonZeroU32::new(1),
+            base_array_layer: layer,
+            array_layer_count: std::num::NonZeroU32::new(1),
+            ..Default::default()
+        })
+    }
+
+    // How `view` is declared in a bind group layout. Depth formats bind as
+    // depth textures, to be read with comparison or non-filtering samplers.
+    pub fn binding_type(&self) -> wgpu::BindingType {
+        wgpu::BindingType::Texture {
+            multisampled: self.texture.sample_count() > 1,
+            view_dimension: self.view_dimension,
+            sample_type: self.texture.format().describe().sample_type,
+        }
+    }
+
     pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> Self {
         let size = wgpu::Extent3d {
             width,
@@ -17,23 +188,27 @@ impl Texture {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        Self { texture, view }
+        Self {
+            texture,
+            view,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
     }
 
-    pub fn from_bytes(
+    pub fn create_render_target(
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        albedo_bytes: &[u8],
-        dimensions: (u32, u32),
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -42,26 +217,1040 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         });
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            texture,
+            view,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        dimensions: (u32, u32),
+        color_space: ColorSpace,
+        mipmaps: Mipmaps,
+        label: &str,
+    ) -> Result<Self> {
+        Self::from_texels(
+            device,
+            queue,
+            bytes,
+            dimensions,
+            color_space.rgba8_format(),
+            mipmaps,
+            label,
+        )
+    }
+
+    // Uploads tightly packed texels of any uncompressed `format`. The mip chain
+    // is rendered on the GPU when the format can be a render target, and box
+    // filtered on the CPU otherwise; formats neither path handles get a
+    // single level.
+    pub fn from_texels(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        dimensions: (u32, u32),
+        format: wgpu::TextureFormat,
+        mipmaps: Mipmaps,
+        label: &str,
+    ) -> Result<Self> {
+        let expected = level_size(format, dimensions);
+        if bytes.len() != expected {
+            return Err(Error::InvalidImageData {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let format_info = format.describe();
+        let gpu_mipmaps = format_info
+            .guaranteed_format_features
+            .allowed_usages
+            .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            && format_info.sample_type == wgpu::TextureSampleType::Float { filterable: true };
+        let mip_level_count = match mipmaps {
+            Mipmaps::Generate if gpu_mipmaps || box_filter_supports(format) => {
+                mip_level_count(dimensions)
+            }
+            _ => 1,
+        };
+        let gpu_mipmaps = gpu_mipmaps && mip_level_count > 1;
+
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if gpu_mipmaps {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
             },
-            albedo_bytes,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        write_mip_level(queue, &texture, 0, bytes, dimensions, 1);
+
+        if gpu_mipmaps {
+            render_mipmaps(device, queue, &texture, format, mip_level_count);
+        } else {
+            let mut level_bytes = bytes.to_vec();
+            let mut level_dimensions = dimensions;
+            for level in 1..mip_level_count {
+                level_bytes = box_filter(&level_bytes, level_dimensions, format)?;
+                level_dimensions = (
+                    (level_dimensions.0 / 2).max(1),
+                    (level_dimensions.1 / 2).max(1),
+                );
+                write_mip_level(queue, &texture, level, &level_bytes, level_dimensions, 1);
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(Self {
+            texture,
+            view,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        })
+    }
+
+    // Uploads a complete chain of mip levels, largest first, as stored. Each
+    // level holds every array layer or cube face of that level in turn, or
+    // every depth slice of a 3D texture, which halve along with the other
+    // dimensions.
+    pub fn from_levels(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        levels: &[Vec<u8>],
+        size: wgpu::Extent3d,
+        format: wgpu::TextureFormat,
+        view_dimension: wgpu::TextureViewDimension,
+        label: &str,
+    ) -> Result<Self> {
+        let dimensions = (size.width, size.height);
+        let volume = view_dimension == wgpu::TextureViewDimension::D3;
+        let level_layers = |level: u32| {
+            if volume {
+                (size.depth_or_array_layers >> level).max(1)
+            } else {
+                size.depth_or_array_layers
+            }
+        };
+        let full_chain = if volume {
+            mip_level_count((size.width, size.height.max(size.depth_or_array_layers)))
+        } else {
+            mip_level_count(dimensions)
+        };
+        if levels.is_empty() || levels.len() as u32 > full_chain {
+            return Err(Error::UnsupportedFormat(format!(
+                "{} mip levels for a {}x{}x{} texture",
+                levels.len(),
+                size.width,
+                size.height,
+                size.depth_or_array_layers
+            )));
+        }
+        for (level, bytes) in levels.iter().enumerate() {
+            let level = level as u32;
+            let expected = level_layers(level) as usize
+                * level_size(format, level_dimensions(dimensions, level));
+            if bytes.len() != expected {
+                return Err(Error::InvalidImageData {
+                    expected,
+                    actual: bytes.len(),
+                });
+            }
+        }
+
+        let texture = Self::create(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: levels.len() as u32,
+                sample_count: 1,
+                dimension: if volume {
+                    wgpu::TextureDimension::D3
+                } else {
+                    wgpu::TextureDimension::D2
+                },
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
             },
-            size,
+            view_dimension,
         );
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        Self { texture, view }
+        for (level, bytes) in levels.iter().enumerate() {
+            let level = level as u32;
+            write_mip_level(
+                queue,
+                &texture.texture,
+                level,
+                bytes,
+                level_dimensions(dimensions, level),
+                level_layers(level),
+            );
+        }
+        Ok(texture)
+    }
+
+    // Uploads a KTX2 texture's mip levels as stored when the device supports
+    // its format, and decompressed to RGBA8 on the CPU otherwise. Basis
+    // Universal levels are transcoded to a format the device supports.
+    // Containers that leave their levels out get a generated chain.
+    pub fn from_ktx2(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            return Err(Error::UnsupportedFormat(
+                "KTX2 3D, array or cube map textures".to_string(),
+            ));
+        }
+        let dimensions = (header.pixel_width, header.pixel_height.max(1));
+        let mut levels = reader
+            .levels()
+            .map(|level| supercompressed_level(header.supercompression_scheme, level))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut format = match header.format {
+            Some(format) => ktx2_format(format)
+                .ok_or_else(|| Error::UnsupportedFormat(format!("KTX2 {:?} textures", format)))?,
+            // Basis Universal payloads leave the format to the transcoder
+            None => {
+                let format;
+                (levels, format) =
+                    basis::transcode(&reader, &levels, dimensions, device.features())?;
+                format
+            }
+        };
+
+        if !device
+            .features()
+            .contains(format.describe().required_features)
+        {
+            let compressed_format = format;
+            for (level, bytes) in levels.iter_mut().enumerate() {
+                let level_dimensions = level_dimensions(dimensions, level as u32);
+                (*bytes, format) = decompress(bytes, level_dimensions, compressed_format)?;
+            }
+        }
+
+        if header.level_count == 0 {
+            Self::from_texels(
+                device,
+                queue,
+                &levels[0],
+                dimensions,
+                format,
+                Mipmaps::Generate,
+                label,
+            )
+        } else {
+            let size = wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            };
+            let view_dimension = wgpu::TextureViewDimension::D2;
+            Self::from_levels(device, queue, &levels, size, format, view_dimension, label)
+        }
+    }
+
+    // Uploads a DDS texture's mip levels, array layers and cube faces as
+    // stored when the device supports its format, and decompressed on the CPU
+    // otherwise. Legacy headers can't say whether texels are sRGB encoded, so
+    // `color_space` picks the format for them and for typeless DX10 formats.
+    pub fn from_dds(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        color_space: ColorSpace,
+        label: &str,
+    ) -> Result<Self> {
+        let dds = ddsfile::Dds::read(bytes)?;
+        let header10 = dds.header10.as_ref();
+        let volume = dds.header.caps2.contains(ddsfile::Caps2::VOLUME)
+            || header10.is_some_and(|header10| {
+                header10.resource_dimension == ddsfile::D3D10ResourceDimension::Texture3D
+            });
+        if volume {
+            return Err(Error::UnsupportedFormat("DDS volume textures".to_string()));
+        }
+        let compressed_format = dds_format(&dds, color_space)?;
+
+        let cube = match header10 {
+            Some(header10) => header10.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE),
+            None => dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP),
+        };
+        if cube
+            && header10.is_none()
+            && !dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP_ALLFACES)
+        {
+            return Err(Error::UnsupportedFormat(
+                "DDS cube maps without all six faces".to_string(),
+            ));
+        }
+        // DX10 headers count whole cubes rather than faces
+        let faces = if cube { 6 } else { 1 };
+        let layers = header10.map_or(1, |header10| header10.array_size.max(1)) * faces;
+        let dimensions = (dds.header.width, dds.header.height.max(1));
+        let level_count = dds
+            .get_num_mipmap_levels()
+            .clamp(1, mip_level_count(dimensions));
+
+        // DDS stores every level of one layer before the next layer, while
+        // levels are uploaded with all of their layers together
+        let layer_sizes: Vec<usize> = (0..level_count)
+            .map(|level| level_size(compressed_format, level_dimensions(dimensions, level)))
+            .collect();
+        let expected = layers as usize * layer_sizes.iter().sum::<usize>();
+        if dds.data.len() < expected {
+            return Err(Error::InvalidImageData {
+                expected,
+                actual: dds.data.len(),
+            });
+        }
+        let mut levels = vec![Vec::new(); level_count as usize];
+        let mut data = dds.data.as_slice();
+        for _ in 0..layers {
+            for (level, &layer_size) in levels.iter_mut().zip(&layer_sizes) {
+                let (layer, rest) = data.split_at(layer_size);
+                level.extend_from_slice(layer);
+                data = rest;
+            }
+        }
+
+        let mut format = compressed_format;
+        if !device
+            .features()
+            .contains(format.describe().required_features)
+        {
+            for (level, bytes) in levels.iter_mut().enumerate() {
+                let level_dimensions = level_dimensions(dimensions, level as u32);
+                let mut texels = Vec::new();
+                for layer in bytes.chunks_exact(layer_sizes[level]) {
+                    let (layer, decompressed_format) =
+                        decompress(layer, level_dimensions, compressed_format)?;
+                    texels.extend_from_slice(&layer);
+                    format = decompressed_format;
+                }
+                *bytes = texels;
+            }
+        }
+
+        let size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: layers,
+        };
+        let view_dimension = match (cube, layers) {
+            (true, 6) => wgpu::TextureViewDimension::Cube,
+            (true, _) => wgpu::TextureViewDimension::CubeArray,
+            (false, 1) => wgpu::TextureViewDimension::D2,
+            (false, _) => wgpu::TextureViewDimension::D2Array,
+        };
+        Self::from_levels(device, queue, &levels, size, format, view_dimension, label)
+    }
+
+    // Decodes a Radiance HDR or OpenEXR image into a linear `Rgba16Float` or
+    // `Rgba32Float` texture
+    pub fn from_hdr(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        format: wgpu::TextureFormat,
+        mipmaps: Mipmaps,
+        label: &str,
+    ) -> Result<Self> {
+        // `image` tone maps Radiance images to 8 bits unless their float
+        // texels are read directly
+        let (values, dimensions): (Vec<f32>, _) = match image::guess_format(bytes)? {
+            image::ImageFormat::Hdr => {
+                let decoder = image::codecs::hdr::HdrDecoder::new(bytes)?;
+                let metadata = decoder.metadata();
+                let values = decoder
+                    .read_image_hdr()?
+                    .into_iter()
+                    .flat_map(|image::Rgb([red, green, blue])| [red, green, blue, 1.0])
+                    .collect();
+                (values, (metadata.width, metadata.height))
+            }
+            image::ImageFormat::OpenExr => {
+                let image =
+                    image::load_from_memory_with_format(bytes, image::ImageFormat::OpenExr)?
+                        .into_rgba32f();
+                let dimensions = image.dimensions();
+                (image.into_raw(), dimensions)
+            }
+            image_format => {
+                return Err(Error::UnsupportedFormat(format!(
+                    "{:?} images as HDR textures",
+                    image_format
+                )))
+            }
+        };
+        let values = values.into_iter();
+        let texels = match format {
+            wgpu::TextureFormat::Rgba16Float => to_half_floats(values),
+            wgpu::TextureFormat::Rgba32Float => values.flat_map(f32::to_le_bytes).collect(),
+            _ => {
+                return Err(Error::UnsupportedFormat(format!(
+                    "{:?} HDR textures",
+                    format
+                )))
+            }
+        };
+        Self::from_texels(device, queue, &texels, dimensions, format, mipmaps, label)
+    }
+
+    // Renders an `Rgba16Float` cube map from an equirectangular panorama, such
+    // as one loaded with `from_hdr`. Each level of the cube is resampled from
+    // the panorama level closest to its resolution.
+    pub fn cube_from_equirectangular(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        panorama: &Texture,
+        face_size: u32,
+        label: &str,
+    ) -> Self {
+        let format = wgpu::TextureFormat::Rgba16Float;
+        let mip_level_count = mip_level_count((face_size, face_size));
+        let cube = Self::create_cube(
+            device,
+            face_size,
+            1,
+            format,
+            mip_level_count,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            label,
+        );
+
+        let filterable = panorama.texture.format().describe().sample_type
+            == wgpu::TextureSampleType::Float { filterable: true };
+        let filter = if filterable {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+        let bind_group_layout =
+            equirectangular_pipeline::create_bind_group_layout(device, filterable);
+        let render_pipeline =
+            equirectangular_pipeline::create_render_pipeline(device, &bind_group_layout, format);
+        // Longitude wraps around, latitude stops at the poles
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Equirectangular Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Equirectangular Encoder"),
+        });
+        for level in 0..mip_level_count {
+            // A face spans a quarter of the panorama's width
+            let level_size = (face_size >> level).max(1);
+            let lod = (panorama.texture.width() as f32 / (4 * level_size) as f32)
+                .log2()
+                .max(0.0);
+            for face in 0..6 {
+                let face_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Equirectangular Face Buffer"),
+                    contents: bytemuck::cast_slice(&[CubeFace {
+                        index: face,
+                        lod,
+                        _padding: [0; 2],
+                    }]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                let bind_group = equirectangular_pipeline::create_bind_group(
+                    device,
+                    &bind_group_layout,
+                    &panorama.view,
+                    &sampler,
+                    &face_buffer,
+                );
+                let view = cube.layer_view(face, level);
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Equirectangular Render Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+                render_pass.set_pipeline(&render_pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        cube
+    }
+
+    // Uploads an image decoded by `gltf::import`
+    pub fn from_gltf_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &gltf::image::Data,
+        color_space: ColorSpace,
+        mipmaps: Mipmaps,
+        label: &str,
+    ) -> Result<Self> {
+        let (bytes, format) = convert_gltf_image(image, color_space, device.features());
+        Self::from_texels(
+            device,
+            queue,
+            &bytes,
+            (image.width, image.height),
+            format,
+            mipmaps,
+            label,
+        )
+    }
+}
+
+// Which cube face `equirectangular.wgsl` renders, and the panorama level it
+// samples
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CubeFace {
+    index: u32,
+    lod: f32,
+    _padding: [u32; 2],
+}
+
+pub(crate) fn level_dimensions(dimensions: (u32, u32), level: u32) -> (u32, u32) {
+    (
+        (dimensions.0 >> level).max(1),
+        (dimensions.1 >> level).max(1),
+    )
+}
+
+// Undoes KTX2 supercompression of one mip level
+fn supercompressed_level(
+    scheme: Option<ktx2::SupercompressionScheme>,
+    level: &[u8],
+) -> Result<Vec<u8>> {
+    use ktx2::SupercompressionScheme;
+
+    let mut bytes = Vec::new();
+    match scheme {
+        // BasisLZ levels are decoded with the texture's global data
+        None | Some(SupercompressionScheme::BasisLZ) => bytes.extend_from_slice(level),
+        Some(SupercompressionScheme::Zstandard) => {
+            ruzstd::StreamingDecoder::new(level)
+                .map_err(|e| {
+                    Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        e.to_string(),
+                    ))
+                })?
+                .read_to_end(&mut bytes)?;
+        }
+        Some(SupercompressionScheme::ZLIB) => {
+            flate2::read::ZlibDecoder::new(level).read_to_end(&mut bytes)?;
+        }
+        Some(scheme) => {
+            return Err(Error::UnsupportedFormat(format!(
+                "KTX2 {:?} supercompression",
+                scheme
+            )))
+        }
+    }
+    Ok(bytes)
+}
+
+// The filterable formats a KTX2 texture can store its levels in
+fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format;
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat};
+
+    Some(match format {
+        Format::R8_UNORM => TextureFormat::R8Unorm,
+        Format::R8G8_UNORM => TextureFormat::Rg8Unorm,
+        Format::R8G8B8A8_UNORM => TextureFormat::Rgba8Unorm,
+        Format::R8G8B8A8_SRGB => TextureFormat::Rgba8UnormSrgb,
+        Format::B8G8R8A8_UNORM => TextureFormat::Bgra8Unorm,
+        Format::B8G8R8A8_SRGB => TextureFormat::Bgra8UnormSrgb,
+        Format::R16G16B16A16_SFLOAT => TextureFormat::Rgba16Float,
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK => TextureFormat::Bc1RgbaUnorm,
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => TextureFormat::Bc1RgbaUnormSrgb,
+        Format::BC2_UNORM_BLOCK => TextureFormat::Bc2RgbaUnorm,
+        Format::BC2_SRGB_BLOCK => TextureFormat::Bc2RgbaUnormSrgb,
+        Format::BC3_UNORM_BLOCK => TextureFormat::Bc3RgbaUnorm,
+        Format::BC3_SRGB_BLOCK => TextureFormat::Bc3RgbaUnormSrgb,
+        Format::BC4_UNORM_BLOCK => TextureFormat::Bc4RUnorm,
+        Format::BC4_SNORM_BLOCK => TextureFormat::Bc4RSnorm,
+        Format::BC5_UNORM_BLOCK => TextureFormat::Bc5RgUnorm,
+        Format::BC5_SNORM_BLOCK => TextureFormat::Bc5RgSnorm,
+        Format::BC6H_UFLOAT_BLOCK => TextureFormat::Bc6hRgbUfloat,
+        Format::BC6H_SFLOAT_BLOCK => TextureFormat::Bc6hRgbSfloat,
+        Format::BC7_UNORM_BLOCK => TextureFormat::Bc7RgbaUnorm,
+        Format::BC7_SRGB_BLOCK => TextureFormat::Bc7RgbaUnormSrgb,
+        Format::ETC2_R8G8B8_UNORM_BLOCK => TextureFormat::Etc2Rgb8Unorm,
+        Format::ETC2_R8G8B8_SRGB_BLOCK => TextureFormat::Etc2Rgb8UnormSrgb,
+        Format::ETC2_R8G8B8A1_UNORM_BLOCK => TextureFormat::Etc2Rgb8A1Unorm,
+        Format::ETC2_R8G8B8A1_SRGB_BLOCK => TextureFormat::Etc2Rgb8A1UnormSrgb,
+        Format::ETC2_R8G8B8A8_UNORM_BLOCK => TextureFormat::Etc2Rgba8Unorm,
+        Format::ETC2_R8G8B8A8_SRGB_BLOCK => TextureFormat::Etc2Rgba8UnormSrgb,
+        Format::EAC_R11_UNORM_BLOCK => TextureFormat::EacR11Unorm,
+        Format::EAC_R11_SNORM_BLOCK => TextureFormat::EacR11Snorm,
+        Format::EAC_R11G11_UNORM_BLOCK => TextureFormat::EacRg11Unorm,
+        Format::EAC_R11G11_SNORM_BLOCK => TextureFormat::EacRg11Snorm,
+        // ASTC formats alternate between UNORM and SRGB for each block size
+        format
+            if (Format::ASTC_4x4_UNORM_BLOCK..=Format::ASTC_12x12_SRGB_BLOCK).contains(&format) =>
+        {
+            let offset = format.0.get() - Format::ASTC_4x4_UNORM_BLOCK.0.get();
+            let block = [
+                AstcBlock::B4x4,
+                AstcBlock::B5x4,
+                AstcBlock::B5x5,
+                AstcBlock::B6x5,
+                AstcBlock::B6x6,
+                AstcBlock::B8x5,
+                AstcBlock::B8x6,
+                AstcBlock::B8x8,
+                AstcBlock::B10x5,
+                AstcBlock::B10x6,
+                AstcBlock::B10x8,
+                AstcBlock::B10x10,
+                AstcBlock::B12x10,
+                AstcBlock::B12x12,
+            ][offset as usize / 2];
+            let channel = match offset % 2 {
+                0 => AstcChannel::Unorm,
+                _ => AstcChannel::UnormSrgb,
+            };
+            TextureFormat::Astc { block, channel }
+        }
+        _ => return None,
+    })
+}
+
+// The filterable formats a DDS texture can store its levels in
+fn dds_format(dds: &ddsfile::Dds, color_space: ColorSpace) -> Result<wgpu::TextureFormat> {
+    use ddsfile::{D3DFormat, DxgiFormat, FourCC};
+    use wgpu::TextureFormat;
+
+    let pick = |linear, srgb| match color_space {
+        ColorSpace::Srgb => srgb,
+        ColorSpace::Linear => linear,
+    };
+    let format = match &dds.header10 {
+        Some(header10) => match header10.dxgi_format {
+            DxgiFormat::R8G8B8A8_UNorm => Some(TextureFormat::Rgba8Unorm),
+            DxgiFormat::R8G8B8A8_UNorm_sRGB => Some(TextureFormat::Rgba8UnormSrgb),
+            DxgiFormat::R8G8B8A8_Typeless => Some(color_space.rgba8_format()),
+            DxgiFormat::B8G8R8A8_UNorm => Some(TextureFormat::Bgra8Unorm),
+            DxgiFormat::B8G8R8A8_UNorm_sRGB => Some(TextureFormat::Bgra8UnormSrgb),
+            DxgiFormat::B8G8R8A8_Typeless => Some(pick(
+                TextureFormat::Bgra8Unorm,
+                TextureFormat::Bgra8UnormSrgb,
+            )),
+            DxgiFormat::R16G16B16A16_Float => Some(TextureFormat::Rgba16Float),
+            DxgiFormat::BC1_UNorm => Some(TextureFormat::Bc1RgbaUnorm),
+            DxgiFormat::BC1_UNorm_sRGB => Some(TextureFormat::Bc1RgbaUnormSrgb),
+            DxgiFormat::BC1_Typeless => Some(pick(
+                TextureFormat::Bc1RgbaUnorm,
+                TextureFormat::Bc1RgbaUnormSrgb,
+            )),
+            DxgiFormat::BC2_UNorm => Some(TextureFormat::Bc2RgbaUnorm),
+            DxgiFormat::BC2_UNorm_sRGB => Some(TextureFormat::Bc2RgbaUnormSrgb),
+            DxgiFormat::BC2_Typeless => Some(pick(
+                TextureFormat::Bc2RgbaUnorm,
+                TextureFormat::Bc2RgbaUnormSrgb,
+            )),
+            DxgiFormat::BC3_UNorm => Some(TextureFormat::Bc3RgbaUnorm),
+            DxgiFormat::BC3_UNorm_sRGB => Some(TextureFormat::Bc3RgbaUnormSrgb),
+            DxgiFormat::BC3_Typeless => Some(pick(
+                TextureFormat::Bc3RgbaUnorm,
+                TextureFormat::Bc3RgbaUnormSrgb,
+            )),
+            DxgiFormat::BC4_UNorm | DxgiFormat::BC4_Typeless => Some(TextureFormat::Bc4RUnorm),
+            DxgiFormat::BC4_SNorm => Some(TextureFormat::Bc4RSnorm),
+            DxgiFormat::BC5_UNorm | DxgiFormat::BC5_Typeless => Some(TextureFormat::Bc5RgUnorm),
+            DxgiFormat::BC5_SNorm => Some(TextureFormat::Bc5RgSnorm),
+            DxgiFormat::BC6H_UF16 | DxgiFormat::BC6H_Typeless => Some(TextureFormat::Bc6hRgbUfloat),
+            DxgiFormat::BC6H_SF16 => Some(TextureFormat::Bc6hRgbSfloat),
+            DxgiFormat::BC7_UNorm => Some(TextureFormat::Bc7RgbaUnorm),
+            DxgiFormat::BC7_UNorm_sRGB => Some(TextureFormat::Bc7RgbaUnormSrgb),
+            DxgiFormat::BC7_Typeless => Some(pick(
+                TextureFormat::Bc7RgbaUnorm,
+                TextureFormat::Bc7RgbaUnormSrgb,
+            )),
+            _ => None,
+        },
+        // Legacy headers name block compressed formats with a four character
+        // code, and describe uncompressed ones with channel masks
+        None => match dds.header.spf.fourcc.as_ref().map(|fourcc| fourcc.0) {
+            Some(FourCC::DXT1) => Some(pick(
+                TextureFormat::Bc1RgbaUnorm,
+                TextureFormat::Bc1RgbaUnormSrgb,
+            )),
+            Some(FourCC::DXT2 | FourCC::DXT3) => Some(pick(
+                TextureFormat::Bc2RgbaUnorm,
+                TextureFormat::Bc2RgbaUnormSrgb,
+            )),
+            Some(FourCC::DXT4 | FourCC::DXT5) => Some(pick(
+                TextureFormat::Bc3RgbaUnorm,
+                TextureFormat::Bc3RgbaUnormSrgb,
+            )),
+            Some(FourCC::ATI1 | FourCC::BC4_UNORM) => Some(TextureFormat::Bc4RUnorm),
+            Some(FourCC::BC4_SNORM) => Some(TextureFormat::Bc4RSnorm),
+            Some(FourCC::BC5_UNORM) => Some(TextureFormat::Bc5RgUnorm),
+            Some(FourCC::BC5_SNORM) => Some(TextureFormat::Bc5RgSnorm),
+            Some(FourCC::A16B16G16R16F) => Some(TextureFormat::Rgba16Float),
+            _ => match dds.get_d3d_format() {
+                Some(D3DFormat::A8B8G8R8) => Some(color_space.rgba8_format()),
+                Some(D3DFormat::A8R8G8B8) => Some(pick(
+                    TextureFormat::Bgra8Unorm,
+                    TextureFormat::Bgra8UnormSrgb,
+                )),
+                _ => None,
+            },
+        },
+    };
+    format.ok_or_else(|| {
+        let name = match (dds.get_dxgi_format(), dds.get_d3d_format()) {
+            (Some(format), _) => format!("{:?}", format),
+            (None, Some(format)) => format!("{:?}", format),
+            (None, None) => "unknown format".to_string(),
+        };
+        Error::UnsupportedFormat(format!("DDS {} textures", name))
+    })
+}
+
+// Bytes in one level of tightly packed texels or compressed blocks. Blocks
+// on the right and bottom edges may hang over the level.
+pub fn level_size(format: wgpu::TextureFormat, dimensions: (u32, u32)) -> usize {
+    let info = format.describe();
+    let (block_width, block_height) = info.block_dimensions;
+    let blocks_wide = dimensions.0.div_ceil(block_width as u32) as usize;
+    let blocks_high = dimensions.1.div_ceil(block_height as u32) as usize;
+    blocks_wide * blocks_high * info.block_size as usize
+}
+
+fn write_mip_level(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    level: u32,
+    bytes: &[u8],
+    dimensions: (u32, u32),
+    layers: u32,
+) {
+    // Compressed levels are copied in whole blocks
+    let info = texture.format().describe();
+    let (block_width, block_height) = info.block_dimensions;
+    let blocks_wide = dimensions.0.div_ceil(block_width as u32);
+    let blocks_high = dimensions.1.div_ceil(block_height as u32);
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: level,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytes,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(blocks_wide * info.block_size as u32),
+            rows_per_image: std::num::NonZeroU32::new(blocks_high),
+        },
+        wgpu::Extent3d {
+            width: blocks_wide * block_width as u32,
+            height: blocks_high * block_height as u32,
+            depth_or_array_layers: layers,
+        },
+    );
+}
+
+// Renders each level from the one above it. sRGB views are decoded when
+// sampled and encoded when written, so texels are averaged in linear space.
+fn render_mipmaps(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+) {
+    let bind_group_layout = mipmap_pipeline::create_bind_group_layout(device);
+    let render_pipeline =
+        mipmap_pipeline::create_render_pipeline(device, &bind_group_layout, format);
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Mipmap Sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let views: Vec<wgpu::TextureView> = (0..mip_level_count)
+        .map(|level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Mipmap View"),
+                base_mip_level: level,
+                mip_level_count: std::num::NonZeroU32::new(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+    for views in views.windows(2) {
+        let bind_group =
+            mipmap_pipeline::create_bind_group(device, &bind_group_layout, &views[0], &sampler);
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mipmap Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &views[1],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+fn box_filter_supports(format: wgpu::TextureFormat) -> bool {
+    use wgpu::TextureFormat;
+
+    matches!(
+        format,
+        TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Rgba8Snorm
+            | TextureFormat::Rgba16Unorm
+            | TextureFormat::Rgba16Float
+            | TextureFormat::Rgba32Float
+    )
+}
+
+// Averages each 2x2 block of RGBA texels into the next mip level, in linear
+// space for sRGB formats. Odd edges repeat their last row or column.
+pub fn box_filter(
+    bytes: &[u8],
+    dimensions: (u32, u32),
+    format: wgpu::TextureFormat,
+) -> Result<Vec<u8>> {
+    use wgpu::TextureFormat;
+
+    if !box_filter_supports(format) {
+        return Err(Error::UnsupportedFormat(format!(
+            "{:?} mipmap generation",
+            format
+        )));
+    }
+    let expected =
+        (format.describe().block_size as usize) * dimensions.0 as usize * dimensions.1 as usize;
+    if bytes.len() != expected {
+        return Err(Error::InvalidImageData {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+
+    let srgb = format == TextureFormat::Rgba8UnormSrgb;
+    let values: Vec<f32> = match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => bytes
+            .iter()
+            .enumerate()
+            .map(|(index, &sample)| {
+                let value = sample as f32 / u8::MAX as f32;
+                match index % 4 {
+                    0..=2 if srgb => srgb_to_linear(value),
+                    _ => value,
+                }
+            })
+            .collect(),
+        TextureFormat::Rgba8Snorm => bytes
+            .iter()
+            .map(|&sample| (sample as i8 as f32 / i8::MAX as f32).max(-1.0))
+            .collect(),
+        TextureFormat::Rgba16Unorm => bytes
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / u16::MAX as f32)
+            .collect(),
+        TextureFormat::Rgba32Float => bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect(),
+        _ => bytes
+            .chunks_exact(2)
+            .map(|bytes| half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32())
+            .collect(),
+    };
+
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+    let texel = |x: usize, y: usize, channel: usize| {
+        values[4 * (y.min(height - 1) * width + x.min(width - 1)) + channel]
+    };
+    let averages = (0..next_height).flat_map(|y| {
+        (0..next_width).flat_map(move |x| {
+            (0..4).map(move |channel| {
+                (texel(2 * x, 2 * y, channel)
+                    + texel(2 * x + 1, 2 * y, channel)
+                    + texel(2 * x, 2 * y + 1, channel)
+                    + texel(2 * x + 1, 2 * y + 1, channel))
+                    / 4.0
+            })
+        })
+    });
+
+    Ok(match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => averages
+            .enumerate()
+            .map(|(index, value)| {
+                let value = match index % 4 {
+                    0..=2 if srgb => linear_to_srgb(value),
+                    _ => value,
+                };
+                (value * u8::MAX as f32).round() as u8
+            })
+            .collect(),
+        TextureFormat::Rgba8Snorm => averages
+            .map(|value| (value * i8::MAX as f32).round() as i8 as u8)
+            .collect(),
+        TextureFormat::Rgba16Unorm => averages
+            .flat_map(|value| ((value * u16::MAX as f32).round() as u16).to_le_bytes())
+            .collect(),
+        TextureFormat::Rgba32Float => averages.flat_map(f32::to_le_bytes).collect(),
+        _ => to_half_floats(averages),
+    })
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    match value {
+        value if value <= 0.04045 => value / 12.92,
+        value => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    match value {
+        value if value <= 0.0031308 => value * 12.92,
+        value => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+// Grey and grey-alpha images are spread over RGB so every channel reads the
+// same as it would from the equivalent RGB image
+fn expand_to_rgba<T: Copy>(samples: &[T], channels: usize, opaque: T) -> Vec<T> {
+    samples
+        .chunks_exact(channels)
+        .flat_map(|pixel| match *pixel {
+            [grey] => [grey, grey, grey, opaque],
+            [grey, alpha] => [grey, grey, grey, alpha],
+            [red, green, blue] => [red, green, blue, opaque],
+            [red, green, blue, alpha] => [red, green, blue, alpha],
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn to_half_floats(values: impl Iterator<Item = f32>) -> Vec<u8> {
+    values
+        .flat_map(|value| half::f16::from_f32(value).to_le_bytes())
+        .collect()
+}
+
+// Converts a decoded glTF image into texels the material shader can filter.
+// 16-bit images keep their precision when the device supports 16-bit
+// normalized textures and otherwise become half floats, decoded to linear if
+// they hold sRGB color. Float images are linear already and become half
+// floats, since 32-bit float textures aren't filterable everywhere.
+pub fn convert_gltf_image(
+    image: &gltf::image::Data,
+    color_space: ColorSpace,
+    features: wgpu::Features,
+) -> (Vec<u8>, wgpu::TextureFormat) {
+    use gltf::image::Format;
+
+    let channels = match image.format {
+        Format::R8 | Format::R16 => 1,
+        Format::R8G8 | Format::R16G16 => 2,
+        Format::R8G8B8 | Format::R16G16B16 | Format::R32G32B32FLOAT => 3,
+        Format::R8G8B8A8 | Format::R16G16B16A16 | Format::R32G32B32A32FLOAT => 4,
+    };
+    match image.format {
+        Format::R8 | Format::R8G8 | Format::R8G8B8 | Format::R8G8B8A8 => (
+            expand_to_rgba(&image.pixels, channels, u8::MAX),
+            color_space.rgba8_format(),
+        ),
+        Format::R16 | Format::R16G16 | Format::R16G16B16 | Format::R16G16B16A16 => {
+            let samples: Vec<u16> = image
+                .pixels
+                .chunks_exact(2)
+                .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+                .collect();
+            let rgba = expand_to_rgba(&samples, channels, u16::MAX);
+            let unorm16 = features.contains(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM);
+            match (color_space, unorm16) {
+                (ColorSpace::Linear, true) => (
+                    rgba.iter()
+                        .flat_map(|sample| sample.to_le_bytes())
+                        .collect(),
+                    wgpu::TextureFormat::Rgba16Unorm,
+                ),
+                _ => {
+                    let values = rgba.iter().enumerate().map(|(index, &sample)| {
+                        let value = sample as f32 / u16::MAX as f32;
+                        match (color_space, index % 4) {
+                            (ColorSpace::Srgb, 0..=2) => srgb_to_linear(value),
+                            _ => value,
+                        }
+                    });
+                    (to_half_floats(values), wgpu::TextureFormat::Rgba16Float)
+                }
+            }
+        }
+        Format::R32G32B32FLOAT | Format::R32G32B32A32FLOAT => {
+            let samples: Vec<f32> = image
+                .pixels
+                .chunks_exact(4)
+                .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect();
+            let rgba = expand_to_rgba(&samples, channels, 1.0);
+            (
+                to_half_floats(rgba.into_iter()),
+                wgpu::TextureFormat::Rgba16Float,
+            )
+        }
     }
 }
@@ -0,0 +1,90 @@
+mod common;
+
+use rust_renderer::accessor;
+
+struct Attributes {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    tangents: Vec<[f32; 4]>,
+    texcoords: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+fn load_primitive_attributes(path: &str) -> Attributes {
+    let (gltf, buffers, _) = gltf::import(common::manifest_path(path)).unwrap();
+    let primitive = gltf.meshes().next().unwrap().primitives().next().unwrap();
+    let read = |semantic| primitive.get(&semantic).unwrap();
+    Attributes {
+        positions: accessor::read_f32(&read(gltf::Semantic::Positions), &buffers, [0.0; 3])
+            .unwrap(),
+        normals: accessor::read_f32(&read(gltf::Semantic::Normals), &buffers, [0.0; 3]).unwrap(),
+        tangents: accessor::read_f32(&read(gltf::Semantic::Tangents), &buffers, [0.0; 4]).unwrap(),
+        texcoords: accessor::read_f32(&read(gltf::Semantic::TexCoords(0)), &buffers, [0.0; 2])
+            .unwrap(),
+        indices: accessor::read_indices(&primitive.indices().unwrap(), &buffers).unwrap(),
+    }
+}
+
+fn assert_close<const N: usize>(actual: [f32; N], expected: [f32; N]) {
+    for (a, e) in actual.iter().zip(expected.iter()) {
+        assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn interleaved_normalized_attributes_are_dequantized() {
+    let Attributes {
+        positions,
+        normals,
+        tangents,
+        texcoords,
+        ..
+    } = load_primitive_attributes("tests/fixtures/quantized_quad.gltf");
+
+    assert_eq!(positions.len(), 4);
+    assert_close(positions[0], [-0.5, -0.5, 0.0]);
+    assert_close(positions[1], [0.5, -0.5, 0.0]);
+    assert_close(positions[3], [-0.5, 0.5, 0.0]);
+    for normal in normals {
+        assert_close(normal, [0.0, 0.0, 1.0]);
+    }
+    for tangent in tangents {
+        assert_close(tangent, [1.0, 0.0, 0.0, 1.0]);
+    }
+    assert_close(texcoords[0], [0.0, 1.0]);
+    assert_close(texcoords[1], [1.0, 1.0]);
+    assert_close(texcoords[2], [1.0, 0.0]);
+}
+
+#[test]
+fn sparse_values_replace_base_values() {
+    let positions = load_primitive_attributes("tests/fixtures/quantized_quad.gltf").positions;
+    assert_close(positions[2], [0.5, 0.75, 0.0]);
+}
+
+#[test]
+fn u8_indices_with_byte_offset_are_widened() {
+    let indices = load_primitive_attributes("tests/fixtures/quantized_quad.gltf").indices;
+    assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+}
+
+#[test]
+fn float_attributes_match_gltf_reader() {
+    let (gltf, buffers, _) =
+        gltf::import(common::manifest_path("res/avocado/avocado.gltf")).unwrap();
+    let primitive = gltf.meshes().next().unwrap().primitives().next().unwrap();
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions = accessor::read_f32(
+        &primitive.get(&gltf::Semantic::Positions).unwrap(),
+        &buffers,
+        [0.0; 3],
+    )
+    .unwrap();
+    assert!(positions.into_iter().eq(reader.read_positions().unwrap()));
+
+    let indices = accessor::read_indices(&primitive.indices().unwrap(), &buffers).unwrap();
+    assert!(indices
+        .into_iter()
+        .eq(reader.read_indices().unwrap().into_u32()));
+}
@@ -0,0 +1,508 @@
+#![allow(dead_code)]
+
+use rust_renderer::bind_groups::*;
+use rust_renderer::camera::{Camera, CameraExtrinsics, CameraIntrinsics};
+use rust_renderer::pipelines::*;
+use rust_renderer::*;
+
+use wgpu::util::DeviceExt;
+
+pub const WIDTH: u32 = 256;
+pub const HEIGHT: u32 = 256;
+
+// Largest per-channel difference still treated as a match, to absorb small
+// rasterization and filtering differences between adapters
+pub const CHANNEL_TOLERANCE: u8 = 8;
+// Fraction of pixels allowed to exceed the channel tolerance
+pub const MAX_MISMATCHED_FRACTION: f64 = 0.002;
+
+pub fn manifest_path(relative: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+pub fn headless_renderer() -> renderer::RendererState {
+    pollster::block_on(renderer::RendererState::new_headless(WIDTH, HEIGHT)).unwrap()
+}
+
+pub fn render_gltf(
+    path: &str,
+    extrinsics: CameraExtrinsics,
+    intrinsics: CameraIntrinsics,
+) -> image::RgbaImage {
+    render_gltf_with_debug(path, extrinsics, intrinsics, false)
+}
+
+// Like `render_gltf`, tinting surfaces by their shadow cascade when
+// `debug_cascades` is set
+pub fn render_gltf_with_debug(
+    path: &str,
+    extrinsics: CameraExtrinsics,
+    intrinsics: CameraIntrinsics,
+    debug_cascades: bool,
+) -> image::RgbaImage {
+    let mut renderer_state = headless_renderer();
+
+    let camera = Camera::new(extrinsics, intrinsics);
+    let camera_buffer =
+        renderer_state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[camera.to_uniform()]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+    let mesh = mesh::Mesh::from_gltf(
+        &manifest_path(path),
+        &renderer_state.device,
+        &renderer_state.queue,
+    )
+    .unwrap();
+
+    let camera_bind_group_layout =
+        camera_bind_group::create_bind_group_layout(&renderer_state.device);
+    let material_bind_group_layout =
+        material_bind_group::create_bind_group_layout(&renderer_state.device);
+    let light_bind_group_layout =
+        light_bind_group::create_bind_group_layout(&renderer_state.device);
+    let shadow_config = shadow::ShadowConfig::default();
+    let lights = mesh.light_uniforms(&camera, &shadow_config);
+    let light_buffer = light::create_buffer(&renderer_state.device, &lights);
+    light::write_debug_cascades(&renderer_state.queue, &light_buffer, debug_cascades);
+    let shadow_maps = shadow::ShadowMaps::new(
+        &renderer_state.device,
+        &camera_bind_group_layout,
+        &shadow_config,
+        shadow::layer_count(&lights),
+        shadow::cube_count(&lights),
+    );
+    let light_bind_group = light_bind_group::create_bind_group(
+        &renderer_state.device,
+        &light_bind_group_layout,
+        &light_buffer,
+        &shadow_maps,
+    );
+    let camera_bind_group = camera_bind_group::create_bind_group(
+        &renderer_state.device,
+        &camera_bind_group_layout,
+        &camera_buffer,
+    );
+    let material_bind_groups: Vec<_> = mesh
+        .materials
+        .iter()
+        .map(|material| {
+            material_bind_group::create_bind_group(
+                &renderer_state.device,
+                &material_bind_group_layout,
+                material,
+            )
+        })
+        .collect();
+    let render_pipeline = mesh_pipeline::create_render_pipeline(
+        &renderer_state.device,
+        renderer_state.surface_config.format,
+        &camera_bind_group_layout,
+        &material_bind_group_layout,
+        &light_bind_group_layout,
+    );
+    let depth_texture =
+        texture::Texture::create_depth_texture(&renderer_state.device, WIDTH, HEIGHT);
+
+    let shadow_pipeline =
+        shadow_pipeline::create_render_pipeline(&renderer_state.device, &camera_bind_group_layout);
+    let point_shadow_pipeline = shadow_pipeline::create_point_render_pipeline(
+        &renderer_state.device,
+        &camera_bind_group_layout,
+    );
+    shadow_maps.render(
+        &renderer_state.device,
+        &renderer_state.queue,
+        &shadow_pipeline,
+        &point_shadow_pipeline,
+        &mesh,
+        &lights,
+    );
+    renderer_state
+        .render(
+            &render_pipeline,
+            &camera_bind_group,
+            &material_bind_groups,
+            &light_bind_group,
+            &mesh,
+            &depth_texture.view,
+        )
+        .unwrap();
+
+    renderer_state
+        .read_offscreen_image()
+        .unwrap()
+        .unwrap()
+        .to_rgba8()
+}
+
+// Compares against `tests/golden/<name>.png`. Run with `UPDATE_GOLDEN=1` to
+// (re)write the reference images instead. On failure the actual render and a
+// diff image are written to `target/golden/`.
+pub fn assert_matches_golden(name: &str, actual: &image::RgbaImage) {
+    let reference_path = manifest_path(&format!("tests/golden/{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Could not open reference image '{}' ({}), run with UPDATE_GOLDEN=1 to create it",
+                reference_path.display(),
+                e
+            )
+        })
+        .to_rgba8();
+    assert_eq!(
+        reference.dimensions(),
+        actual.dimensions(),
+        "Render size differs from reference image '{}'",
+        reference_path.display()
+    );
+
+    let mut mismatched = 0;
+    let diff = image::RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let expected = reference.get_pixel(x, y);
+        let found = actual.get_pixel(x, y);
+        let max_difference = expected
+            .0
+            .iter()
+            .zip(found.0.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap();
+        if max_difference > CHANNEL_TOLERANCE {
+            mismatched += 1;
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            // Dimmed reference so mismatches stand out
+            let [r, g, b, _] = expected.0;
+            image::Rgba([r / 4, g / 4, b / 4, 255])
+        }
+    });
+
+    let total = (actual.width() * actual.height()) as f64;
+    if mismatched as f64 / total > MAX_MISMATCHED_FRACTION {
+        let output_directory = manifest_path("target/golden");
+        std::fs::create_dir_all(&output_directory).unwrap();
+        let actual_path = output_directory.join(format!("{}_actual.png", name));
+        let diff_path = output_directory.join(format!("{}_diff.png", name));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "'{}' differs from reference in {} of {} pixels, see '{}' and '{}'",
+            name,
+            mismatched,
+            total,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+// Draws every texel of mip level 0 into a `target_format` target and reads it
+// back, so the GPU's decoding of compressed formats can be compared to the CPU's
+pub fn decode_on_gpu(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    target_format: wgpu::TextureFormat,
+) -> Vec<u8> {
+    decode_level_on_gpu(device, queue, texture, 0, target_format)
+}
+
+// Like `decode_on_gpu`, for mip level `level`
+pub fn decode_level_on_gpu(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    level: u32,
+    target_format: wgpu::TextureFormat,
+) -> Vec<u8> {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Decode Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            format!(
+                r#"
+            @group(0) @binding(0)
+            var t_source: texture_2d<f32>;
+
+            @vertex
+            fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {{
+                let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+                return vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+            }}
+
+            @fragment
+            fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {{
+                return textureLoad(t_source, vec2<i32>(position.xy), {});
+            }}
+            "#,
+                level
+            )
+            .into(),
+        ),
+    });
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Decode Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        }],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Decode Bind Group"),
+        layout: &bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&view),
+        }],
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Decode Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Decode Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(target_format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    let target = texture::Texture::create_render_target(
+        device,
+        (texture.width() >> level).max(1),
+        (texture.height() >> level).max(1),
+        target_format,
+        "Decode Target",
+    );
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Decode Encoder"),
+    });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Decode Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+
+    screenshot::read_texture_bytes(device, queue, &target.texture, wgpu::TextureAspect::All)
+        .unwrap()
+}
+
+// Samples the center of each face of a cube view at mip level 0, in the
+// +X, -X, +Y, -Y, +Z, -Z order faces are stored in
+pub fn sample_cube_faces(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    view: &wgpu::TextureView,
+) -> Vec<[u8; 4]> {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Cube Faces Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            r#"
+            @group(0) @binding(0)
+            var t_cube: texture_cube<f32>;
+            @group(0) @binding(1)
+            var s_cube: sampler;
+
+            @vertex
+            fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+                let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+                return vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+            }
+
+            @fragment
+            fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+                var directions = array<vec3<f32>, 6>(
+                    vec3<f32>(1.0, 0.0, 0.0),
+                    vec3<f32>(-1.0, 0.0, 0.0),
+                    vec3<f32>(0.0, 1.0, 0.0),
+                    vec3<f32>(0.0, -1.0, 0.0),
+                    vec3<f32>(0.0, 0.0, 1.0),
+                    vec3<f32>(0.0, 0.0, -1.0),
+                );
+                let direction = directions[u32(position.x)];
+                return textureSampleLevel(t_cube, s_cube, direction, 0.0);
+            }
+            "#
+            .into(),
+        ),
+    });
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Cube Faces Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    });
+    // Face centers fall between texels on even sized faces
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Cube Faces Bind Group"),
+        layout: &bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Cube Faces Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Cube Faces Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    let target = texture::Texture::create_render_target(
+        device,
+        6,
+        1,
+        wgpu::TextureFormat::Rgba8Unorm,
+        "Cube Faces Target",
+    );
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Cube Faces Encoder"),
+    });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Cube Faces Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+
+    screenshot::read_texture_bytes(device, queue, &target.texture, wgpu::TextureAspect::All)
+        .unwrap()
+        .chunks_exact(4)
+        .map(|texel| texel.try_into().unwrap())
+        .collect()
+}
+
+// Deterministic bytes for fuzzing decoders
+pub fn random_bytes(count: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+// A device without any optional features, to exercise CPU fallbacks
+pub fn device_without_features() -> (wgpu::Device, wgpu::Queue) {
+    device_with_features(wgpu::Features::empty())
+}
+
+// A device with only those of `features` the adapter supports
+pub fn device_with_features(features: wgpu::Features) -> (wgpu::Device, wgpu::Queue) {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        force_fallback_adapter: false,
+        compatible_surface: None,
+    }))
+    .unwrap();
+    let limits = wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits());
+    pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Test Device"),
+            features: features & adapter.features(),
+            limits,
+        },
+        None,
+    ))
+    .unwrap()
+}
@@ -0,0 +1,85 @@
+use rust_renderer::compress::compress;
+use rust_renderer::decompress::decompress;
+use rust_renderer::Error;
+use wgpu::TextureFormat;
+
+// A different color in each block of a 12x8 image, brightened across and
+// down each block, with alpha fading across it
+fn test_texels() -> Vec<u8> {
+    const COLORS: [[u32; 3]; 6] = [
+        [200, 100, 50],
+        [30, 160, 220],
+        [90, 90, 90],
+        [240, 240, 20],
+        [10, 10, 10],
+        [120, 40, 180],
+    ];
+    let mut texels = Vec::new();
+    for y in 0..8 {
+        for x in 0..12 {
+            let color = COLORS[y / 4 * 3 + x / 4];
+            let offset = (6 * (x % 4) + 4 * (y % 4)) as u32;
+            let [red, green, blue] = color.map(|channel| (channel + offset).min(255) as u8);
+            texels.extend([red, green, blue, 255 - 20 * (x % 4) as u8]);
+        }
+    }
+    texels
+}
+
+fn assert_round_trips(format: TextureFormat, tolerance: u8, alpha: bool) {
+    let texels = test_texels();
+    let bytes = compress(&texels, (12, 8), format).unwrap();
+    let (decompressed, _) = decompress(&bytes, (12, 8), format).unwrap();
+    for (index, (actual, expected)) in decompressed.iter().zip(&texels).enumerate() {
+        let expected = if index % 4 == 3 && !alpha {
+            255
+        } else {
+            *expected
+        };
+        assert!(
+            actual.abs_diff(expected) <= tolerance,
+            "{:?} texel {} channel {}: {} instead of {}",
+            format,
+            index / 4,
+            index % 4,
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn bc7_round_trips() {
+    assert_round_trips(TextureFormat::Bc7RgbaUnorm, 10, true);
+}
+
+#[test]
+fn etc2_rgba_round_trips() {
+    assert_round_trips(TextureFormat::Etc2Rgba8Unorm, 8, true);
+}
+
+#[test]
+fn etc2_rgb_round_trips() {
+    assert_round_trips(TextureFormat::Etc2Rgb8Unorm, 8, false);
+}
+
+#[test]
+fn edge_blocks_repeat_the_last_texels() {
+    let texels: Vec<u8> = (0..6 * 5)
+        .flat_map(|index| [index as u8 * 8, 0, 0, 255])
+        .collect();
+    let bytes = compress(&texels, (6, 5), TextureFormat::Bc7RgbaUnorm).unwrap();
+    assert_eq!(bytes.len(), 4 * 16);
+}
+
+#[test]
+fn unsupported_formats_are_rejected() {
+    assert!(matches!(
+        compress(&[0; 64], (4, 4), TextureFormat::Bc1RgbaUnorm),
+        Err(Error::UnsupportedFormat(_))
+    ));
+    assert!(matches!(
+        compress(&[0; 60], (4, 4), TextureFormat::Bc7RgbaUnorm),
+        Err(Error::InvalidImageData { .. })
+    ));
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use rust_renderer::texture::{ColorSpace, Texture};
+use rust_renderer::Error;
+
+fn read(name: &str) -> Vec<u8> {
+    std::fs::read(common::manifest_path(&format!(
+        "tests/fixtures/dds/{}",
+        name
+    )))
+    .unwrap()
+}
+
+fn load(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    name: &str,
+    color_space: ColorSpace,
+) -> rust_renderer::Result<Texture> {
+    Texture::from_dds(device, queue, &read(name), color_space, name)
+}
+
+fn has_bc(device: &wgpu::Device) -> bool {
+    device
+        .features()
+        .contains(wgpu::Features::TEXTURE_COMPRESSION_BC)
+}
+
+#[test]
+fn legacy_headers_take_the_requested_color_space() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    if !has_bc(device) {
+        return;
+    }
+    let texture = load(device, queue, "bc1_mips.dds", ColorSpace::Srgb).unwrap();
+    assert_eq!(
+        texture.texture.format(),
+        wgpu::TextureFormat::Bc1RgbaUnormSrgb
+    );
+    assert_eq!(
+        (texture.texture.width(), texture.texture.height()),
+        (16, 16)
+    );
+    assert_eq!(texture.texture.mip_level_count(), 5);
+
+    let texture = load(device, queue, "bc1_mips.dds", ColorSpace::Linear).unwrap();
+    assert_eq!(texture.texture.format(), wgpu::TextureFormat::Bc1RgbaUnorm);
+
+    let texture = load(device, queue, "bgra8.dds", ColorSpace::Srgb).unwrap();
+    assert_eq!(
+        texture.texture.format(),
+        wgpu::TextureFormat::Bgra8UnormSrgb
+    );
+}
+
+#[test]
+fn dx10_formats_keep_their_color_space() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    if !has_bc(device) {
+        return;
+    }
+    let texture = load(device, queue, "bc7_srgb.dds", ColorSpace::Linear).unwrap();
+    assert_eq!(
+        texture.texture.format(),
+        wgpu::TextureFormat::Bc7RgbaUnormSrgb
+    );
+}
+
+#[test]
+fn array_layers_upload_with_every_level() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    if !has_bc(device) {
+        return;
+    }
+    let texture = load(device, queue, "bc1_array.dds", ColorSpace::Linear).unwrap();
+    assert_eq!(texture.texture.depth_or_array_layers(), 3);
+    assert_eq!(texture.texture.mip_level_count(), 2);
+}
+
+// Each face of the fixture is a solid color, with red counting up by face
+#[test]
+fn cube_faces_upload_in_order() {
+    let assert_faces = |device: &wgpu::Device, queue: &wgpu::Queue| {
+        let texture = load(device, queue, "bc1_cube.dds", ColorSpace::Linear).unwrap();
+        assert_eq!(texture.texture.depth_or_array_layers(), 6);
+        assert_eq!(texture.texture.mip_level_count(), 4);
+        let faces = common::sample_cube_faces(device, queue, &texture.view);
+        for (face, texel) in faces.iter().enumerate() {
+            let red = 4 * face as u8;
+            assert_eq!(texel, &[(red << 3) | (red >> 2), 255, 0, 255]);
+        }
+    };
+
+    {
+        let (device, queue) = common::device_without_features();
+        assert_faces(&device, &queue);
+    }
+    let renderer_state = common::headless_renderer();
+    if has_bc(&renderer_state.device) {
+        assert_faces(&renderer_state.device, &renderer_state.queue);
+    }
+}
+
+#[test]
+fn compressed_levels_are_decompressed_otherwise() {
+    let (device, queue) = common::device_without_features();
+    let texture = load(&device, &queue, "bc1_mips.dds", ColorSpace::Srgb).unwrap();
+    assert_eq!(
+        texture.texture.format(),
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    );
+    assert_eq!(texture.texture.mip_level_count(), 5);
+}
+
+#[test]
+fn volume_textures_are_unsupported() {
+    let renderer_state = common::headless_renderer();
+    assert!(matches!(
+        load(
+            &renderer_state.device,
+            &renderer_state.queue,
+            "volume.dds",
+            ColorSpace::Linear
+        ),
+        Err(Error::UnsupportedFormat(_))
+    ));
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+    let renderer_state = common::headless_renderer();
+    let result = Texture::from_dds(
+        &renderer_state.device,
+        &renderer_state.queue,
+        b"not a DDS file",
+        ColorSpace::Linear,
+        "Invalid",
+    );
+    assert!(matches!(result, Err(Error::Dds(_))));
+
+    let mut truncated = read("bc1_mips.dds");
+    truncated.truncate(truncated.len() - 8);
+    let result = Texture::from_dds(
+        &renderer_state.device,
+        &renderer_state.queue,
+        &truncated,
+        ColorSpace::Linear,
+        "Truncated",
+    );
+    assert!(matches!(result, Err(Error::InvalidImageData { .. })));
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use rust_renderer::decompress::decompress;
+use rust_renderer::texture::{level_size, Mipmaps, Texture};
+use wgpu::TextureFormat;
+
+fn srgb_to_linear(value: f32) -> f32 {
+    match value {
+        value if value <= 0.04045 => value / 12.92,
+        value => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+// A multiple of every ASTC block size. Most random ASTC blocks are malformed,
+// so it takes this many to decode a few thousand valid ones.
+const DIMENSIONS: (u32, u32) = (960, 960);
+
+// Random blocks, enough to cover every block mode and partition, decoded on
+// the GPU into `target_format`. `None` if the device can't sample `format`.
+fn decode_random_blocks(
+    format: TextureFormat,
+    target_format: TextureFormat,
+) -> Option<(Vec<u8>, Vec<u8>)> {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let required = format.describe().required_features;
+    if !device.features().contains(required) {
+        eprintln!("skipping {:?}, the device can't sample it", format);
+        return None;
+    }
+
+    let bytes = common::random_bytes(level_size(format, DIMENSIONS), 0x9e37_79b9_7f4a_7c15);
+    let texture = Texture::from_texels(
+        device,
+        queue,
+        &bytes,
+        DIMENSIONS,
+        format,
+        Mipmaps::Skip,
+        "Compressed Texture",
+    )
+    .unwrap();
+    let gpu = common::decode_on_gpu(device, queue, &texture.texture, target_format);
+    Some((bytes, gpu))
+}
+
+// Decodes random blocks on both the GPU and the CPU
+fn assert_matches_gpu(format: TextureFormat, tolerance: u8) {
+    let Some((bytes, gpu)) = decode_random_blocks(format, TextureFormat::Rgba8Unorm) else {
+        return;
+    };
+    let (cpu, rgba8_format) = decompress(&bytes, DIMENSIONS, format).unwrap();
+
+    // The GPU decodes sRGB formats to linear values when sampling
+    let srgb = format.describe().srgb;
+    assert_eq!(rgba8_format.describe().srgb, srgb);
+    let cpu: Vec<u8> = cpu
+        .iter()
+        .enumerate()
+        .map(|(index, &value)| match index % 4 {
+            0..=2 if srgb => (srgb_to_linear(value as f32 / 255.0) * 255.0).round() as u8,
+            _ => value,
+        })
+        .collect();
+    let mismatches = gpu
+        .chunks_exact(4)
+        .zip(cpu.chunks_exact(4))
+        .filter(|(gpu, cpu)| {
+            gpu.iter()
+                .zip(cpu.iter())
+                .any(|(gpu, cpu)| gpu.abs_diff(*cpu) > tolerance)
+        })
+        .count();
+    assert_eq!(mismatches, 0, "{:?} texels differ from the GPU", format);
+}
+
+#[test]
+fn etc2_rgb_matches_gpu() {
+    assert_matches_gpu(TextureFormat::Etc2Rgb8Unorm, 0);
+}
+
+#[test]
+fn etc2_punchthrough_matches_gpu() {
+    assert_matches_gpu(TextureFormat::Etc2Rgb8A1Unorm, 0);
+}
+
+// wgpu's GL backend uploads Etc2Rgba8Unorm as sRGB, so only the sRGB variant
+// decodes the same on every backend
+#[test]
+fn etc2_rgba_matches_gpu() {
+    assert_matches_gpu(TextureFormat::Etc2Rgba8UnormSrgb, 1);
+}
+
+#[test]
+fn eac_matches_gpu() {
+    assert_matches_gpu(TextureFormat::EacR11Unorm, 1);
+    assert_matches_gpu(TextureFormat::EacRg11Unorm, 1);
+}
+
+// llvmpipe rounds the eight value palettes of BC3 alpha, BC4 and BC5 less
+// precisely than the CPU
+#[test]
+fn bc1_to_bc5_match_gpu() {
+    assert_matches_gpu(TextureFormat::Bc1RgbaUnorm, 1);
+    assert_matches_gpu(TextureFormat::Bc2RgbaUnorm, 1);
+    assert_matches_gpu(TextureFormat::Bc3RgbaUnorm, 2);
+    assert_matches_gpu(TextureFormat::Bc4RUnorm, 2);
+    assert_matches_gpu(TextureFormat::Bc5RgUnorm, 2);
+}
+
+// Signed formats decode to Rgba8Snorm, which a float target compares without
+// clamping negative values
+fn assert_snorm_matches_gpu(format: TextureFormat, tolerance: u8) {
+    let Some((bytes, gpu)) = decode_random_blocks(format, TextureFormat::Rgba32Float) else {
+        return;
+    };
+    let (cpu, rgba8_format) = decompress(&bytes, DIMENSIONS, format).unwrap();
+    assert_eq!(rgba8_format, TextureFormat::Rgba8Snorm);
+
+    let gpu = gpu
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()));
+    let cpu = cpu
+        .iter()
+        .map(|&byte| (byte as i8 as f32 / 127.0).max(-1.0));
+    let mismatches = gpu
+        .zip(cpu)
+        .filter(|(gpu, cpu)| ((gpu - cpu) * 127.0).round().abs() > tolerance as f32)
+        .count();
+    assert_eq!(mismatches, 0, "{:?} channels differ from the GPU", format);
+}
+
+// llvmpipe truncates its signed BC4 and BC5 palettes, landing up to two
+// steps from the rounded ones
+#[test]
+fn snorm_eac_bc4_bc5_match_gpu() {
+    assert_snorm_matches_gpu(TextureFormat::EacR11Snorm, 1);
+    assert_snorm_matches_gpu(TextureFormat::EacRg11Snorm, 1);
+    assert_snorm_matches_gpu(TextureFormat::Bc4RSnorm, 2);
+    assert_snorm_matches_gpu(TextureFormat::Bc5RgSnorm, 2);
+}
+
+#[test]
+fn bc7_matches_gpu() {
+    assert_matches_gpu(TextureFormat::Bc7RgbaUnorm, 0);
+    assert_matches_gpu(TextureFormat::Bc7RgbaUnormSrgb, 1);
+}
+
+#[test]
+fn astc_matches_gpu() {
+    for block in [
+        wgpu::AstcBlock::B4x4,
+        wgpu::AstcBlock::B5x4,
+        wgpu::AstcBlock::B6x6,
+        wgpu::AstcBlock::B8x5,
+        wgpu::AstcBlock::B10x10,
+        wgpu::AstcBlock::B12x12,
+    ] {
+        assert_matches_gpu(
+            TextureFormat::Astc {
+                block,
+                channel: wgpu::AstcChannel::Unorm,
+            },
+            1,
+        );
+    }
+    assert_matches_gpu(
+        TextureFormat::Astc {
+            block: wgpu::AstcBlock::B8x8,
+            channel: wgpu::AstcChannel::UnormSrgb,
+        },
+        1,
+    );
+}
+
+// BC6H decodes to half floats, which a float target reads back exactly
+fn assert_bc6h_matches_gpu(format: TextureFormat) {
+    let Some((bytes, gpu)) = decode_random_blocks(format, TextureFormat::Rgba32Float) else {
+        return;
+    };
+    let (cpu, rgba16_format) = decompress(&bytes, DIMENSIONS, format).unwrap();
+    assert_eq!(rgba16_format, TextureFormat::Rgba16Float);
+
+    let gpu = gpu
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()));
+    let cpu = cpu
+        .chunks_exact(2)
+        .map(|bytes| half::f16::from_le_bytes(bytes.try_into().unwrap()).to_f32());
+    let mismatches = gpu.zip(cpu).filter(|(gpu, cpu)| gpu != cpu).count();
+    assert_eq!(mismatches, 0, "{:?} channels differ from the GPU", format);
+}
+
+#[test]
+fn bc6h_matches_gpu() {
+    assert_bc6h_matches_gpu(TextureFormat::Bc6hRgbUfloat);
+    assert_bc6h_matches_gpu(TextureFormat::Bc6hRgbSfloat);
+}
+
+#[test]
+fn partial_blocks_are_cropped() {
+    let bytes = common::random_bytes(4 * 8, 7);
+    let (rgba, _) = decompress(&bytes, (5, 5), TextureFormat::Etc2Rgb8Unorm).unwrap();
+    assert_eq!(rgba.len(), 5 * 5 * 4);
+}
+
+#[test]
+fn srgb_formats_decode_to_srgb_texels() {
+    let (_, format) = decompress(&[0; 8], (4, 4), TextureFormat::Etc2Rgb8UnormSrgb).unwrap();
+    assert_eq!(format, TextureFormat::Rgba8UnormSrgb);
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use rust_renderer::texture::Texture;
+use rust_renderer::Error;
+use wgpu::{TextureFormat, TextureSampleType, TextureUsages, TextureViewDimension};
+
+fn clear(device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, color: wgpu::Color) {
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Clear Encoder"),
+    });
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Clear Render Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(color),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+#[test]
+fn cube_faces_render_through_layer_views() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let cube = Texture::create_cube(
+        device,
+        4,
+        1,
+        TextureFormat::Rgba8Unorm,
+        1,
+        TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+        "Cube",
+    );
+    assert_eq!(cube.texture.depth_or_array_layers(), 6);
+    assert_eq!(cube.view_dimension, TextureViewDimension::Cube);
+
+    for face in 0..6 {
+        let color = wgpu::Color {
+            r: face as f64 / 5.0,
+            g: 1.0,
+            b: 0.0,
+            a: 1.0,
+        };
+        clear(device, queue, &cube.layer_view(face, 0), color);
+    }
+    let faces = common::sample_cube_faces(device, queue, &cube.view);
+    for (face, texel) in faces.iter().enumerate() {
+        assert_eq!(texel, &[51 * face as u8, 255, 0, 255]);
+    }
+}
+
+#[test]
+fn several_cubes_are_viewed_as_a_cube_array() {
+    let renderer_state = common::headless_renderer();
+    let cubes = Texture::create_cube(
+        &renderer_state.device,
+        4,
+        2,
+        TextureFormat::Depth32Float,
+        1,
+        TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+        "Cube Array",
+    );
+    assert_eq!(cubes.texture.depth_or_array_layers(), 12);
+    assert_eq!(
+        cubes.binding_type(),
+        wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: TextureViewDimension::CubeArray,
+            sample_type: TextureSampleType::Depth,
+        }
+    );
+}
+
+#[test]
+fn single_layer_arrays_are_still_arrays() {
+    let renderer_state = common::headless_renderer();
+    let array = Texture::create_2d_array(
+        &renderer_state.device,
+        (8, 4),
+        1,
+        TextureFormat::Rgba16Float,
+        3,
+        TextureUsages::TEXTURE_BINDING,
+        "Array",
+    );
+    assert_eq!(array.texture.mip_level_count(), 3);
+    assert_eq!(
+        array.binding_type(),
+        wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: TextureViewDimension::D2Array,
+            sample_type: TextureSampleType::Float { filterable: true },
+        }
+    );
+}
+
+#[test]
+fn volume_levels_halve_in_depth() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let size = wgpu::Extent3d {
+        width: 4,
+        height: 4,
+        depth_or_array_layers: 4,
+    };
+    let levels = vec![vec![0; 4 * 4 * 4 * 4], vec![0; 2 * 2 * 2 * 4], vec![0; 4]];
+    let volume = Texture::from_levels(
+        device,
+        queue,
+        &levels,
+        size,
+        TextureFormat::Rgba8Unorm,
+        TextureViewDimension::D3,
+        "Volume",
+    )
+    .unwrap();
+    assert_eq!(volume.texture.dimension(), wgpu::TextureDimension::D3);
+    assert_eq!(volume.texture.mip_level_count(), 3);
+    assert_eq!(volume.view_dimension, TextureViewDimension::D3);
+
+    // Array layers don't shrink, so the same levels are too small for them
+    let result = Texture::from_levels(
+        device,
+        queue,
+        &levels,
+        size,
+        TextureFormat::Rgba8Unorm,
+        TextureViewDimension::D2Array,
+        "Array",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::InvalidImageData {
+            expected: 64,
+            actual: 32
+        })
+    ));
+}
+
+#[test]
+fn empty_volumes_keep_their_depth() {
+    let renderer_state = common::headless_renderer();
+    let volume = Texture::create_3d(
+        &renderer_state.device,
+        wgpu::Extent3d {
+            width: 8,
+            height: 8,
+            depth_or_array_layers: 16,
+        },
+        TextureFormat::R32Float,
+        1,
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        "Volume",
+    );
+    assert_eq!(volume.texture.depth_or_array_layers(), 16);
+    assert_eq!(
+        volume.binding_type(),
+        wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: TextureViewDimension::D3,
+            sample_type: TextureSampleType::Float { filterable: false },
+        }
+    );
+}
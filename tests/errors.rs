@@ -0,0 +1,159 @@
+mod common;
+
+use rust_renderer::mesh::Mesh;
+use rust_renderer::normals::NormalMode;
+use rust_renderer::renderer::RendererState;
+use rust_renderer::texture::{ColorSpace, Mipmaps, Texture};
+use rust_renderer::Error;
+
+// 48 bytes, enough for three vertices of every attribute. All are zero except
+// byte 44, which is 7 so an index can point past the last vertex.
+const BUFFER_URI: &str =
+    "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAHAAAA";
+
+// A single triangle whose attributes and accessors are spliced in by each test
+fn triangle_gltf(attributes: &str, extra_accessor: &str, indices: &str) -> String {
+    format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "buffers": [{{ "byteLength": 48, "uri": "{BUFFER_URI}" }}],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 48 }}],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                   "min": [0, 0, 0], "max": [0, 0, 0] }},
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC4" }},
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC2" }}
+                {extra_accessor}
+            ],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ {attributes} }} {indices} }}] }}]
+        }}"#
+    )
+}
+
+fn load_primitives(json: &str) -> rust_renderer::Result<()> {
+    let (gltf, buffers, _) = gltf::import_slice(json.as_bytes())?;
+    Mesh::load_primitives(&gltf, &buffers, NormalMode::default()).map(|_| ())
+}
+
+#[test]
+fn missing_file_is_io_error() {
+    let renderer_state = common::headless_renderer();
+    let path = common::manifest_path("tests/fixtures/does_not_exist.gltf");
+    let result = Mesh::from_gltf(&path, &renderer_state.device, &renderer_state.queue);
+    assert!(matches!(result, Err(Error::Io(_))));
+}
+
+#[test]
+fn malformed_gltf_is_gltf_error() {
+    let result = load_primitives("{ \"asset\": ");
+    assert!(matches!(result, Err(Error::Gltf(_))));
+}
+
+// glTF validation already requires POSITION, every other attribute is
+// optional
+#[test]
+fn missing_positions_are_rejected() {
+    let json = triangle_gltf(r#""NORMAL": 0, "TANGENT": 1, "TEXCOORD_0": 2"#, "", "");
+    assert!(matches!(load_primitives(&json), Err(Error::Gltf(_))));
+}
+
+#[test]
+fn float_indices_are_unsupported() {
+    let json = triangle_gltf(
+        r#""POSITION": 0, "NORMAL": 0, "TANGENT": 1, "TEXCOORD_0": 2"#,
+        r#", { "bufferView": 0, "componentType": 5126, "count": 3, "type": "SCALAR" }"#,
+        r#", "indices": 3"#,
+    );
+    assert!(matches!(
+        load_primitives(&json),
+        Err(Error::UnsupportedFormat(_))
+    ));
+}
+
+#[test]
+fn accessor_past_end_of_buffer_is_invalid() {
+    let json = triangle_gltf(
+        r#""POSITION": 0, "NORMAL": 3, "TANGENT": 1, "TEXCOORD_0": 2"#,
+        r#", { "bufferView": 0, "byteOffset": 24, "componentType": 5126, "count": 3, "type": "VEC3" }"#,
+        "",
+    );
+    assert!(matches!(
+        load_primitives(&json),
+        Err(Error::InvalidAccessor { accessor: 3 })
+    ));
+}
+
+// The accessor's 36 bytes fit in the buffer, but not in its 24 byte view
+#[test]
+fn accessor_past_end_of_view_is_invalid() {
+    let json = triangle_gltf(
+        r#""POSITION": 0, "NORMAL": 3, "TANGENT": 1, "TEXCOORD_0": 2"#,
+        r#", { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }"#,
+        "",
+    )
+    .replace(
+        r#"{ "buffer": 0, "byteLength": 48 }"#,
+        r#"{ "buffer": 0, "byteLength": 48 }, { "buffer": 0, "byteLength": 24 }"#,
+    );
+    assert!(matches!(
+        load_primitives(&json),
+        Err(Error::InvalidAccessor { accessor: 3 })
+    ));
+}
+
+#[test]
+fn index_past_last_vertex_is_invalid() {
+    let json = triangle_gltf(
+        r#""POSITION": 0, "NORMAL": 0, "TANGENT": 1, "TEXCOORD_0": 2"#,
+        r#", { "bufferView": 0, "byteOffset": 44, "componentType": 5121, "count": 3, "type": "SCALAR" }"#,
+        r#", "indices": 3"#,
+    );
+    assert!(matches!(
+        load_primitives(&json),
+        Err(Error::InvalidAccessor { accessor: 3 })
+    ));
+}
+
+#[test]
+fn attribute_shorter_than_positions_is_invalid() {
+    let json = triangle_gltf(
+        r#""POSITION": 0, "NORMAL": 3, "TANGENT": 1, "TEXCOORD_0": 2"#,
+        r#", { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" }"#,
+        "",
+    );
+    assert!(matches!(
+        load_primitives(&json),
+        Err(Error::InvalidAccessor { accessor: 3 })
+    ));
+}
+
+#[test]
+fn texture_bytes_must_match_dimensions() {
+    let renderer_state = common::headless_renderer();
+    let result = Texture::from_bytes(
+        &renderer_state.device,
+        &renderer_state.queue,
+        &[255; 12],
+        (2, 2),
+        ColorSpace::Srgb,
+        Mipmaps::Generate,
+        "Undersized Texture",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::InvalidImageData {
+            expected: 16,
+            actual: 12
+        })
+    ));
+}
+
+#[test]
+fn no_backends_means_no_adapter() {
+    let result = pollster::block_on(RendererState::new_headless_with_backends(
+        common::WIDTH,
+        common::HEIGHT,
+        wgpu::Backends::empty(),
+    ));
+    assert!(matches!(result, Err(Error::NoAdapter)));
+}
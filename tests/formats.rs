@@ -0,0 +1,25 @@
+mod common;
+
+use rust_renderer::mesh::{Mesh, PrimitiveData};
+use rust_renderer::normals::NormalMode;
+
+fn load_primitives(path: &str) -> Vec<Vec<PrimitiveData>> {
+    let (gltf, buffers, _) = gltf::import(common::manifest_path(path)).unwrap();
+    Mesh::load_primitives(&gltf, &buffers, NormalMode::default()).unwrap()
+}
+
+#[test]
+fn same_geometry_from_every_container() {
+    let reference = load_primitives("res/avocado/avocado.gltf");
+    for path in [
+        "tests/fixtures/avocado/avocado.glb",
+        "tests/fixtures/avocado/avocado_embedded.gltf",
+        "tests/fixtures/avocado/avocado_split.gltf",
+    ] {
+        assert!(
+            load_primitives(path) == reference,
+            "Geometry loaded from '{}' differs from res/avocado/avocado.gltf",
+            path
+        );
+    }
+}
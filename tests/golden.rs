@@ -0,0 +1,226 @@
+mod common;
+
+use rust_renderer::camera::{CameraExtrinsics, CameraIntrinsics};
+use rust_renderer::constants::*;
+use std::f32::consts::*;
+
+const INTRINSICS: CameraIntrinsics = CameraIntrinsics {
+    aspect: common::WIDTH as f32 / common::HEIGHT as f32,
+    ..DEFAULT_CAMERA_INTRINSICS
+};
+
+#[test]
+fn avocado_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.03, 0.12, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("res/avocado/avocado.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("avocado_front", &image);
+}
+
+#[test]
+fn avocado_glb_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.03, 0.12, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("tests/fixtures/avocado/avocado.glb", extrinsics, INTRINSICS);
+    common::assert_matches_golden("avocado_front", &image);
+}
+
+// Generated tangents should perturb normals the same way as the authored ones
+#[test]
+fn avocado_generated_tangents_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.03, 0.12, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf(
+        "tests/fixtures/avocado/avocado_no_tangents.gltf",
+        extrinsics,
+        INTRINSICS,
+    );
+    common::assert_matches_golden("avocado_front", &image);
+}
+
+// Maps are found through the material's texture references and UV sets,
+// not by image order
+#[test]
+fn avocado_material_refs_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.03, 0.12, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf(
+        "tests/fixtures/avocado/avocado_material_refs.gltf",
+        extrinsics,
+        INTRINSICS,
+    );
+    common::assert_matches_golden("avocado_front", &image);
+}
+
+#[test]
+fn avocado_above() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.1, 0.07, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: -0.9,
+    };
+    let image = common::render_gltf("res/avocado/avocado.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("avocado_above", &image);
+}
+
+#[test]
+fn icosphere_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 3.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("res/icosphere.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("icosphere_front", &image);
+}
+
+#[test]
+fn two_meshes_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 4.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("tests/fixtures/two_meshes.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("two_meshes_front", &image);
+}
+
+#[test]
+fn node_hierarchy_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 4.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("tests/fixtures/node_hierarchy.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("node_hierarchy_front", &image);
+}
+
+#[test]
+fn quantized_quad_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 3.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("tests/fixtures/quantized_quad.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("quantized_quad_front", &image);
+}
+
+// A rough dielectric, a polished metal and an emissive sphere, lit by the
+// same light
+#[test]
+fn pbr_spheres_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 6.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("tests/fixtures/pbr_spheres.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("pbr_spheres_front", &image);
+}
+
+// A white sun, a red point light between the left and middle sphere, and a
+// blue spot light shining down on the right one
+#[test]
+fn lights_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 6.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("tests/fixtures/lights.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("lights_front", &image);
+}
+
+// A sun casting the spheres' shadows across the ground, and a spot light
+// casting the small sphere's shadow straight below it
+#[test]
+fn shadows_above() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 3.0, 6.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: -0.5,
+    };
+    let image = common::render_gltf("tests/fixtures/shadows.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("shadows_above", &image);
+}
+
+// Two rows of spheres receding over a large ground plane, shadowed by a sun
+// through four cascades
+#[test]
+fn cascades_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 1.0, 6.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: -0.2,
+    };
+    let image = common::render_gltf("tests/fixtures/cascades.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("cascades_front", &image);
+}
+
+#[test]
+fn cascades_debug_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 1.0, 6.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: -0.2,
+    };
+    let image = common::render_gltf_with_debug(
+        "tests/fixtures/cascades.gltf",
+        extrinsics,
+        INTRINSICS,
+        true,
+    );
+    common::assert_matches_golden("cascades_debug_front", &image);
+}
+
+// A room lit by three point lights, each casting the spheres' shadows across
+// the floor and walls
+#[test]
+fn point_shadows_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 1.0, 6.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: -0.2,
+    };
+    let image = common::render_gltf("tests/fixtures/point_shadows.gltf", extrinsics, INTRINSICS);
+    common::assert_matches_golden("point_shadows_front", &image);
+}
+
+// A plane whose left half mirrors the right half's UVs, with a normal map
+// tilting every normal towards +v. With the sun above the plane, both halves
+// should lean towards it and the image should be symmetric
+#[test]
+fn mirrored_uvs_front() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 3.0, 1.0),
+        yaw: -FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let image = common::render_gltf("tests/fixtures/mirrored_uvs.gltf", extrinsics, INTRINSICS);
+    let mirrored = image::imageops::flip_horizontal(&image);
+    let asymmetric = image
+        .pixels()
+        .zip(mirrored.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > 8))
+        .count();
+    assert!(
+        asymmetric * 500 <= image.pixels().len(),
+        "{} pixels differ from their mirror image",
+        asymmetric
+    );
+    common::assert_matches_golden("mirrored_uvs_front", &image);
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use rust_renderer::texture::{Mipmaps, Texture};
+use rust_renderer::Error;
+use wgpu::TextureFormat;
+
+// A 16x8 image with values above one, which 8-bit images can't hold
+fn texel(x: u32, y: u32) -> [f32; 3] {
+    [x as f32 / 2.0, y as f32 * 4.0, 0.5]
+}
+
+fn hdr_bytes() -> Vec<u8> {
+    let pixels: Vec<image::Rgb<f32>> = (0..8)
+        .flat_map(|y| (0..16).map(move |x| image::Rgb(texel(x, y))))
+        .collect();
+    let mut bytes = Vec::new();
+    image::codecs::hdr::HdrEncoder::new(&mut bytes)
+        .encode(&pixels, 16, 8)
+        .unwrap();
+    bytes
+}
+
+fn exr_bytes() -> Vec<u8> {
+    let image = image::Rgb32FImage::from_fn(16, 8, |x, y| image::Rgb(texel(x, y)));
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, image::ImageOutputFormat::OpenExr)
+        .unwrap();
+    bytes.into_inner()
+}
+
+// Reads level 0 back as 32-bit floats
+fn read_floats(device: &wgpu::Device, queue: &wgpu::Queue, texture: &Texture) -> Vec<f32> {
+    common::decode_on_gpu(device, queue, &texture.texture, TextureFormat::Rgba32Float)
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+fn assert_texels(values: &[f32]) {
+    for (index, rgba) in values.chunks_exact(4).enumerate() {
+        let [red, green, blue] = texel(index as u32 % 16, index as u32 / 16);
+        assert_eq!(rgba, [red, green, blue, 1.0]);
+    }
+}
+
+#[test]
+fn radiance_images_load_as_half_floats() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let texture = Texture::from_hdr(
+        device,
+        queue,
+        &hdr_bytes(),
+        TextureFormat::Rgba16Float,
+        Mipmaps::Generate,
+        "Radiance",
+    )
+    .unwrap();
+    assert_eq!(texture.texture.format(), TextureFormat::Rgba16Float);
+    assert_eq!(texture.texture.mip_level_count(), 5);
+    assert_texels(&read_floats(device, queue, &texture));
+}
+
+#[test]
+fn exr_images_load_as_full_floats() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let texture = Texture::from_hdr(
+        device,
+        queue,
+        &exr_bytes(),
+        TextureFormat::Rgba32Float,
+        Mipmaps::Generate,
+        "OpenEXR",
+    )
+    .unwrap();
+    assert_eq!(texture.texture.format(), TextureFormat::Rgba32Float);
+    assert_eq!(texture.texture.mip_level_count(), 5);
+    assert_texels(&read_floats(device, queue, &texture));
+}
+
+#[test]
+fn other_images_and_formats_are_unsupported() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::new(4, 4)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    assert!(matches!(
+        Texture::from_hdr(
+            device,
+            queue,
+            png.get_ref(),
+            TextureFormat::Rgba16Float,
+            Mipmaps::Skip,
+            "PNG"
+        ),
+        Err(Error::UnsupportedFormat(_))
+    ));
+    assert!(matches!(
+        Texture::from_hdr(
+            device,
+            queue,
+            &hdr_bytes(),
+            TextureFormat::Rgba8Unorm,
+            Mipmaps::Skip,
+            "Radiance"
+        ),
+        Err(Error::UnsupportedFormat(_))
+    ));
+}
+
+// A panorama whose texels hold the direction they look in, remapped from
+// -1..1 to 0..1, so every cube face center should read its own axis
+fn direction_panorama(format: TextureFormat) -> Vec<u8> {
+    let (width, height) = (64, 32);
+    let values = (0..height).flat_map(|y| {
+        (0..width).flat_map(move |x| {
+            let longitude = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+            let latitude = (y as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+            let direction = [
+                latitude.sin() * longitude.sin(),
+                latitude.cos(),
+                -latitude.sin() * longitude.cos(),
+            ];
+            let [x, y, z] = direction.map(|value| 0.5 * value + 0.5);
+            [x, y, z, 1.0]
+        })
+    });
+    match format {
+        TextureFormat::Rgba16Float => values
+            .flat_map(|value| half::f16::from_f32(value).to_le_bytes())
+            .collect(),
+        _ => values.flat_map(f32::to_le_bytes).collect(),
+    }
+}
+
+fn assert_cube_faces(format: TextureFormat) {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let panorama = Texture::from_texels(
+        device,
+        queue,
+        &direction_panorama(format),
+        (64, 32),
+        format,
+        Mipmaps::Generate,
+        "Panorama",
+    )
+    .unwrap();
+    let cube = Texture::cube_from_equirectangular(device, queue, &panorama, 16, "Cube");
+    assert_eq!(cube.texture.format(), TextureFormat::Rgba16Float);
+    assert_eq!(cube.texture.depth_or_array_layers(), 6);
+    assert_eq!(cube.texture.mip_level_count(), 5);
+
+    let axes = [
+        [255, 128, 128],
+        [0, 128, 128],
+        [128, 255, 128],
+        [128, 0, 128],
+        [128, 128, 255],
+        [128, 128, 0],
+    ];
+    let faces = common::sample_cube_faces(device, queue, &cube.view);
+    for (face, (texel, axis)) in faces.iter().zip(axes).enumerate() {
+        for (channel, expected) in axis.iter().enumerate() {
+            // Poles are a half texel row away from the panorama's edges
+            assert!(
+                texel[channel].abs_diff(*expected) <= 4,
+                "face {} reads {:?}, expected {:?}",
+                face,
+                texel,
+                axis
+            );
+        }
+    }
+}
+
+#[test]
+fn cube_faces_look_along_their_axes() {
+    assert_cube_faces(TextureFormat::Rgba16Float);
+}
+
+// 32-bit float panoramas are sampled without filtering
+#[test]
+fn unfilterable_panoramas_convert() {
+    assert_cube_faces(TextureFormat::Rgba32Float);
+}
@@ -0,0 +1,365 @@
+mod common;
+
+use rust_renderer::compress::compress;
+use rust_renderer::decompress::decompress;
+use rust_renderer::texture::Texture;
+use rust_renderer::Error;
+
+fn read(name: &str) -> Vec<u8> {
+    std::fs::read(common::manifest_path(&format!(
+        "tests/fixtures/ktx2/{}",
+        name
+    )))
+    .unwrap()
+}
+
+fn load(device: &wgpu::Device, queue: &wgpu::Queue, name: &str) -> rust_renderer::Result<Texture> {
+    Texture::from_ktx2(device, queue, &read(name), name)
+}
+
+#[test]
+fn levels_upload_as_stored() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let texture = load(device, queue, "rgba8_srgb.ktx2").unwrap();
+    assert_eq!(
+        texture.texture.format(),
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    );
+    assert_eq!((texture.texture.width(), texture.texture.height()), (8, 8));
+    assert_eq!(texture.texture.mip_level_count(), 4);
+}
+
+#[test]
+fn supercompressed_levels_match_uncompressed() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let decode = |name| {
+        let texture = load(device, queue, name).unwrap();
+        assert_eq!(texture.texture.mip_level_count(), 4);
+        common::decode_on_gpu(
+            device,
+            queue,
+            &texture.texture,
+            wgpu::TextureFormat::Rgba8Unorm,
+        )
+    };
+    let expected = decode("rgba8_srgb.ktx2");
+    assert_eq!(decode("rgba8_srgb_zstd.ktx2"), expected);
+    assert_eq!(decode("rgba8_srgb_zlib.ktx2"), expected);
+}
+
+#[test]
+fn missing_levels_are_generated() {
+    let renderer_state = common::headless_renderer();
+    let texture = load(
+        &renderer_state.device,
+        &renderer_state.queue,
+        "rgba8_srgb_no_levels.ktx2",
+    )
+    .unwrap();
+    assert_eq!(texture.texture.mip_level_count(), 4);
+}
+
+#[test]
+fn compressed_levels_upload_when_supported() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    if !device
+        .features()
+        .contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    {
+        return;
+    }
+    let texture = load(device, queue, "etc2_rgb.ktx2").unwrap();
+    assert_eq!(texture.texture.format(), wgpu::TextureFormat::Etc2Rgb8Unorm);
+    assert_eq!(texture.texture.mip_level_count(), 4);
+}
+
+#[test]
+fn compressed_levels_are_decompressed_otherwise() {
+    let decompressed = {
+        let (device, queue) = common::device_without_features();
+        let texture = load(&device, &queue, "etc2_rgb.ktx2").unwrap();
+        assert_eq!(texture.texture.format(), wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(texture.texture.mip_level_count(), 4);
+        common::decode_on_gpu(
+            &device,
+            &queue,
+            &texture.texture,
+            wgpu::TextureFormat::Rgba8Unorm,
+        )
+    };
+
+    // GL contexts can't overlap on one thread, so the device that samples the
+    // compressed texture is only created once the other one is gone
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    if device
+        .features()
+        .contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    {
+        let compressed = load(device, queue, "etc2_rgb.ktx2").unwrap();
+        assert_eq!(
+            common::decode_on_gpu(
+                device,
+                queue,
+                &compressed.texture,
+                wgpu::TextureFormat::Rgba8Unorm
+            ),
+            decompressed
+        );
+    }
+}
+
+// Blends two colors across a row of four texels, with ASTC's 2 bit weights
+fn astc_gradient(from: [u8; 4], to: [u8; 4]) -> [[u8; 4]; 4] {
+    [0, 21, 43, 64].map(|weight: u32| {
+        std::array::from_fn(|channel| {
+            let expand = |value: u8| value as u32 * 257;
+            let value =
+                (expand(from[channel]) * (64 - weight) + expand(to[channel]) * weight + 32) >> 6;
+            ((value * 255 + 32767) / 65535) as u8
+        })
+    })
+}
+
+// Lays out rows of 4x4 blocks whose rows all match
+fn block_rows(blocks: &[&[[[u8; 4]; 4]]]) -> Vec<u8> {
+    blocks
+        .iter()
+        .flat_map(|row| [*row; 4])
+        .flatten()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+// Two void extent blocks and two blocks blending their endpoints across
+// each row
+fn astc_4x4_texels() -> Vec<u8> {
+    block_rows(&[
+        &[
+            [[64, 128, 192, 255]; 4],
+            astc_gradient([255, 0, 0, 255], [0, 0, 255, 255]),
+        ],
+        &[
+            astc_gradient([0, 255, 0, 255], [255, 0, 0, 255]),
+            [[255, 255, 255, 128]; 4],
+        ],
+    ])
+}
+
+#[test]
+fn astc_decodes_to_known_texels() {
+    {
+        let (device, queue) = common::device_without_features();
+        let texture = load(&device, &queue, "astc_4x4.ktx2").unwrap();
+        assert_eq!(texture.texture.format(), wgpu::TextureFormat::Rgba8Unorm);
+        let texels = common::decode_on_gpu(
+            &device,
+            &queue,
+            &texture.texture,
+            wgpu::TextureFormat::Rgba8Unorm,
+        );
+        assert_eq!(texels, astc_4x4_texels());
+    }
+
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    if device
+        .features()
+        .contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR)
+    {
+        let texture = load(device, queue, "astc_4x4.ktx2").unwrap();
+        assert_eq!(
+            texture.texture.format(),
+            wgpu::TextureFormat::Astc {
+                block: wgpu::AstcBlock::B4x4,
+                channel: wgpu::AstcChannel::Unorm,
+            }
+        );
+        let texels = common::decode_on_gpu(
+            device,
+            queue,
+            &texture.texture,
+            wgpu::TextureFormat::Rgba8Unorm,
+        );
+        assert_eq!(texels, astc_4x4_texels());
+    }
+}
+
+// The texels a transcoder that compresses `texels` to `format` keeps
+fn recompressed(texels: &[u8], dimensions: (u32, u32), format: wgpu::TextureFormat) -> Vec<u8> {
+    let bytes = compress(texels, dimensions, format).unwrap();
+    decompress(&bytes, dimensions, format).unwrap().0
+}
+
+// Loads a Basis Universal texture on devices with each set of features, and
+// checks it's transcoded to the expected format and texels. The GPU may
+// round its decode a step away from the CPU's.
+fn assert_transcodes(name: &str, targets: &[(wgpu::Features, wgpu::TextureFormat, Vec<u8>)]) {
+    for (features, format, expected) in targets {
+        let (device, queue) = common::device_with_features(*features);
+        if !device.features().contains(*features) {
+            continue;
+        }
+        let texture = load(&device, &queue, name).unwrap();
+        assert_eq!(texture.texture.format(), *format, "{:?}", features);
+        // wgpu's GL backend uploads Etc2Rgba8Unorm as sRGB, so it's read back
+        // through an sRGB target to undo the decode
+        let target_format = match format {
+            wgpu::TextureFormat::Etc2Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+            _ => wgpu::TextureFormat::Rgba8Unorm,
+        };
+        let texels = common::decode_on_gpu(&device, &queue, &texture.texture, target_format);
+        assert_eq!(texels.len(), expected.len());
+        for (index, (actual, expected)) in texels.iter().zip(expected).enumerate() {
+            assert!(
+                actual.abs_diff(*expected) <= 1,
+                "{:?} texel {} channel {}: {} instead of {}",
+                format,
+                index / 4,
+                index % 4,
+                actual,
+                expected
+            );
+        }
+    }
+}
+
+// A solid block, an RGB gradient with 8 bit endpoints, one with trit coded
+// endpoints and 4 bit weights, and two luminance and alpha subsets
+#[test]
+fn uastc_transcodes_to_known_texels() {
+    use wgpu::{Features, TextureFormat};
+
+    let expected = block_rows(&[
+        &[
+            [[10, 20, 30, 255]; 4],
+            astc_gradient([255, 0, 0, 255], [0, 0, 255, 255]),
+        ],
+        &[
+            astc_gradient([126, 0, 255, 255], [255, 130, 0, 255]),
+            [
+                [200, 200, 200, 255],
+                [200, 200, 200, 255],
+                [50, 50, 50, 128],
+                [50, 50, 50, 128],
+            ],
+        ],
+    ]);
+    let bc7 = TextureFormat::Bc7RgbaUnorm;
+    let etc2 = TextureFormat::Etc2Rgba8Unorm;
+    assert_transcodes(
+        "basis_uastc.ktx2",
+        &[
+            (
+                Features::empty(),
+                TextureFormat::Rgba8Unorm,
+                expected.clone(),
+            ),
+            (
+                Features::TEXTURE_COMPRESSION_BC | Features::TEXTURE_COMPRESSION_ETC2,
+                bc7,
+                recompressed(&expected, (8, 8), bc7),
+            ),
+            (
+                Features::TEXTURE_COMPRESSION_ETC2,
+                etc2,
+                recompressed(&expected, (8, 8), etc2),
+            ),
+        ],
+    );
+}
+
+// An ETC1S block's texels: its 5 bit color offset by the modifier table
+// entry its selector picks in each column
+fn etc1s_block(
+    color: [u8; 3],
+    table: usize,
+    selectors: [usize; 4],
+    alpha: [u8; 4],
+) -> [[u8; 4]; 4] {
+    const MODIFIERS: [[i32; 4]; 2] = [[-17, -5, 5, 17], [-42, -13, 13, 42]];
+    std::array::from_fn(|column| {
+        let modifier = MODIFIERS[table][selectors[column]];
+        let [red, green, blue] = color
+            .map(|channel| ((channel << 3 | channel >> 2) as i32 + modifier).clamp(0, 255) as u8);
+        [red, green, blue, alpha[column]]
+    })
+}
+
+// Two endpoints and two selectors, predicted from neighboring blocks and
+// the selector history, with an alpha slice painted by the second endpoint.
+// ETC1S colors carry over to ETC2 unchanged.
+#[test]
+fn etc1s_transcodes_to_known_texels() {
+    use wgpu::{Features, TextureFormat};
+
+    let alpha = [189, 201, 211, 223];
+    let ramp = [0, 1, 2, 3];
+    let high = [3; 4];
+    let first = |selectors| etc1s_block([20, 10, 5], 1, selectors, alpha);
+    let second = |selectors| etc1s_block([5, 25, 30], 0, selectors, alpha);
+    let expected = block_rows(&[
+        &[first(ramp), second(high), second(high), second(high)],
+        &[first(high), first(high), second(high), second(ramp)],
+    ]);
+    let bc7 = TextureFormat::Bc7RgbaUnorm;
+    assert_transcodes(
+        "basis_etc1s.ktx2",
+        &[
+            (
+                Features::empty(),
+                TextureFormat::Rgba8Unorm,
+                expected.clone(),
+            ),
+            (
+                Features::TEXTURE_COMPRESSION_BC | Features::TEXTURE_COMPRESSION_ETC2,
+                TextureFormat::Etc2Rgba8Unorm,
+                expected.clone(),
+            ),
+            (
+                Features::TEXTURE_COMPRESSION_BC,
+                bc7,
+                recompressed(&expected, (16, 8), bc7),
+            ),
+        ],
+    );
+}
+
+#[test]
+fn invalid_containers_are_rejected() {
+    let renderer_state = common::headless_renderer();
+    let mut bytes = read("rgba8_srgb.ktx2");
+    bytes[0] = 0;
+    assert!(matches!(
+        Texture::from_ktx2(
+            &renderer_state.device,
+            &renderer_state.queue,
+            &bytes,
+            "Invalid"
+        ),
+        Err(Error::Ktx2(_))
+    ));
+}
+
+#[test]
+fn truncated_basis_data_is_rejected() {
+    let renderer_state = common::headless_renderer();
+    let mut bytes = read("basis_etc1s.ktx2");
+    // Claim more endpoint codebook than the global data holds
+    let global_data = u64::from_le_bytes(bytes[64..72].try_into().unwrap()) as usize;
+    bytes[global_data + 4..global_data + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(
+        Texture::from_ktx2(
+            &renderer_state.device,
+            &renderer_state.queue,
+            &bytes,
+            "Truncated"
+        ),
+        Err(Error::Io(_))
+    ));
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use rust_renderer::camera::Camera;
+use rust_renderer::constants::*;
+use rust_renderer::light::{self, Light, LightKind, ShadowSettings};
+use rust_renderer::mesh::Mesh;
+use rust_renderer::shadow::{self, ShadowConfig, DEFAULT_SHADOW_MAP_SIZE};
+
+extern crate nalgebra_glm as glm;
+
+fn load_mesh(path: &str) -> Mesh {
+    let renderer_state = common::headless_renderer();
+    Mesh::from_gltf(
+        &common::manifest_path(path),
+        &renderer_state.device,
+        &renderer_state.queue,
+    )
+    .unwrap()
+}
+
+fn light_uniforms(mesh: &Mesh) -> Vec<light::LightUniform> {
+    let camera = Camera::new(DEFAULT_CAMERA_EXTRINSICS, DEFAULT_CAMERA_INTRINSICS);
+    mesh.light_uniforms(&camera, &ShadowConfig::default())
+}
+
+fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+    assert!(
+        glm::distance(&actual.into(), &expected.into()) < 1e-5,
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn punctual_lights_are_read_from_the_extension() {
+    let mesh = load_mesh("tests/fixtures/lights.gltf");
+    assert_eq!(
+        mesh.lights,
+        [
+            Light {
+                name: Some("Sun".to_string()),
+                kind: LightKind::Directional,
+                color: glm::vec3(1.0, 0.9, 0.8),
+                intensity: 0.5,
+                range: None,
+                shadow: Some(ShadowSettings::default()),
+            },
+            Light {
+                name: Some("Red Bulb".to_string()),
+                kind: LightKind::Point,
+                color: glm::vec3(1.0, 0.2, 0.1),
+                intensity: 5.0,
+                range: Some(3.0),
+                shadow: Some(ShadowSettings::default()),
+            },
+            Light {
+                name: Some("Blue Spot".to_string()),
+                kind: LightKind::Spot {
+                    inner_cone_angle: 0.2,
+                    outer_cone_angle: 0.4,
+                },
+                color: glm::vec3(0.2, 0.4, 1.0),
+                intensity: 40.0,
+                range: None,
+                shadow: Some(ShadowSettings::default()),
+            },
+        ]
+    );
+}
+
+#[test]
+fn lights_are_placed_by_their_nodes() {
+    let mesh = load_mesh("tests/fixtures/lights.gltf");
+    let [sun, bulb, spot] = <[_; 3]>::try_from(light_uniforms(&mesh)).unwrap();
+    let half_sqrt_2 = std::f32::consts::FRAC_1_SQRT_2;
+
+    assert_eq!(sun.kind, light::LIGHT_DIRECTIONAL);
+    assert_near(sun.direction, [0.0, -half_sqrt_2, -half_sqrt_2]);
+
+    // The bulb and spot are offset by their parent
+    assert_eq!(bulb.kind, light::LIGHT_POINT);
+    assert_near(bulb.position, [-1.1, 1.0, 1.5]);
+    assert_eq!(bulb.range, 3.0);
+
+    assert_eq!(spot.kind, light::LIGHT_SPOT);
+    assert_near(spot.position, [2.2, 3.0, 1.0]);
+    assert_near(spot.direction, [0.0, -1.0, 0.0]);
+    assert_eq!(spot.range, 0.0);
+    // Full strength inside the inner cone, none outside the outer one
+    let cone = |angle: f32| angle.cos() * spot.angle_scale + spot.angle_offset;
+    assert!((cone(0.2) - 1.0).abs() < 1e-4);
+    assert!(cone(0.4).abs() < 1e-4);
+}
+
+#[test]
+fn scenes_without_lights_get_the_default_light() {
+    let mesh = load_mesh("res/icosphere.gltf");
+    assert!(mesh.lights.is_empty());
+    let (default_light, world_matrix) = light::default_light();
+    let bounds = mesh.world_bounds().unwrap();
+    let camera = Camera::new(DEFAULT_CAMERA_EXTRINSICS, DEFAULT_CAMERA_INTRINSICS);
+    assert_eq!(
+        mesh.light_uniforms(&camera, &ShadowConfig::default()),
+        [default_light.to_shadowed_uniform(
+            &world_matrix,
+            &bounds,
+            &camera,
+            DEFAULT_SHADOW_MAP_SIZE,
+            0
+        )]
+    );
+}
+
+#[test]
+fn shadow_layers_are_given_to_shadow_casters_in_order() {
+    let mesh = load_mesh("tests/fixtures/lights.gltf");
+    let lights = light_uniforms(&mesh);
+    let [sun, bulb, spot] = <[_; 3]>::try_from(lights.clone()).unwrap();
+    // The sun takes a layer per cascade, and the bulb a cube of its own
+    assert_eq!((sun.shadow_layer, sun.cascade_count), (0, 4));
+    assert_eq!((bulb.shadow_layer, bulb.shadow_far), (0, 3.0));
+    assert_eq!((spot.shadow_layer, spot.cascade_count), (4, 1));
+    assert_eq!(shadow::layer_count(&lights), 5);
+    assert_eq!(shadow::cube_count(&lights), 1);
+
+    let settings = ShadowSettings::default();
+    assert_eq!(spot.depth_bias, settings.depth_bias);
+    assert_eq!(spot.normal_bias, settings.normal_bias);
+    assert_eq!(spot.pcf_radius, settings.pcf_radius);
+}
+
+#[test]
+fn spot_shadow_projections_enclose_the_scene() {
+    let mesh = load_mesh("tests/fixtures/lights.gltf");
+    let bounds = mesh.world_bounds().unwrap();
+    let [_, _, spot] = <[_; 3]>::try_from(light_uniforms(&mesh)).unwrap();
+    let view_proj = glm::Mat4::from(spot.shadow_view_projs[0]);
+
+    // Only the right sphere is inside the cone, but all of them lie between
+    // the near and far planes
+    for corner in 0..8 {
+        let pick = |bit: usize, axis: usize| {
+            if corner & (1 << bit) == 0 {
+                bounds.min[axis]
+            } else {
+                bounds.max[axis]
+            }
+        };
+        let clip = view_proj * glm::vec4(pick(0, 0), pick(1, 1), pick(2, 2), 1.0);
+        let depth = clip.z / clip.w;
+        assert!((0.0..=1.0).contains(&depth), "{}", depth);
+    }
+}
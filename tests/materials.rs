@@ -0,0 +1,111 @@
+mod common;
+
+use rust_renderer::material::{Material, MaterialUniform, TextureBytes};
+use rust_renderer::mesh::Mesh;
+use rust_renderer::sampler::SamplerCache;
+use rust_renderer::texture::Mipmaps;
+
+fn load(path: &str) -> Mesh {
+    let renderer_state = common::headless_renderer();
+    Mesh::from_gltf(
+        &common::manifest_path(path),
+        &renderer_state.device,
+        &renderer_state.queue,
+    )
+    .unwrap()
+}
+
+#[test]
+fn primitives_use_their_gltf_material() {
+    let mesh = load("tests/fixtures/avocado/avocado_material_refs.gltf");
+    // Both glTF materials, then the default
+    assert_eq!(mesh.materials.len(), 3);
+    assert_eq!(mesh.primitives[0].material_index, 1);
+
+    let material = &mesh.materials[1];
+    assert_eq!(material.uniform.albedo_texcoord, 1);
+    assert_eq!(material.uniform.normal_texcoord, 1);
+    assert_eq!(material.uniform.roughness_metalness_texcoord, 1);
+    assert_eq!(material.uniform.occlusion_texcoord, 0);
+    assert_eq!(
+        (
+            material.albedo_map.texture.width(),
+            material.occlusion_map.texture.width()
+        ),
+        (2048, 1)
+    );
+}
+
+#[test]
+fn primitives_without_material_use_the_default() {
+    let mesh = load("res/icosphere.gltf");
+    assert_eq!(mesh.materials.len(), 1);
+    assert!(mesh
+        .primitives
+        .iter()
+        .all(|primitive| primitive.material_index == 0));
+}
+
+#[test]
+fn missing_maps_fall_back_to_defaults() {
+    let renderer_state = common::headless_renderer();
+    let material = Material::from_bytes(
+        &renderer_state.device,
+        &renderer_state.queue,
+        &mut SamplerCache::default(),
+        Some(TextureBytes {
+            bytes: &[255; 4 * 4 * 2],
+            dimensions: (4, 2),
+            mipmaps: Mipmaps::Generate,
+        }),
+        None,
+        None,
+        MaterialUniform::default(),
+        "Partial Material",
+    )
+    .unwrap();
+    let size = |texture: &rust_renderer::texture::Texture| {
+        (texture.texture.width(), texture.texture.height())
+    };
+    assert_eq!(size(&material.albedo_map), (4, 2));
+    assert_eq!(material.albedo_map.texture.mip_level_count(), 3);
+    assert_eq!(size(&material.normal_map), (1, 1));
+    assert_eq!(size(&material.roughness_metalness_map), (1, 1));
+    assert_eq!(size(&material.occlusion_map), (1, 1));
+    assert_eq!(size(&material.emissive_map), (1, 1));
+}
+
+#[test]
+fn factors_are_read_from_gltf() {
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "materials": [{
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [0.5, 0.25, 1.0, 0.75],
+                "metallicFactor": 0.2,
+                "roughnessFactor": 0.6
+            },
+            "emissiveFactor": [1.0, 0.5, 0.0]
+        }]
+    }"#;
+    let (gltf, _, images) = gltf::import_slice(json.as_bytes()).unwrap();
+    let renderer_state = common::headless_renderer();
+    let material = Material::from_gltf(
+        &renderer_state.device,
+        &renderer_state.queue,
+        &mut SamplerCache::default(),
+        &gltf.materials().next().unwrap(),
+        &images,
+    )
+    .unwrap();
+    assert_eq!(
+        material.uniform,
+        MaterialUniform {
+            base_color_factor: [0.5, 0.25, 1.0, 0.75],
+            emissive_factor: [1.0, 0.5, 0.0],
+            metallic_factor: 0.2,
+            roughness_factor: 0.6,
+            ..Default::default()
+        }
+    );
+}
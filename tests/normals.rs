@@ -0,0 +1,163 @@
+mod common;
+
+use rust_renderer::mesh::{Mesh, PrimitiveData};
+use rust_renderer::normals::{self, NormalMode};
+
+extern crate nalgebra_glm as glm;
+
+// Unit cube sharing its 8 corners between faces, wound counter-clockwise
+// when seen from outside
+fn cube() -> PrimitiveData {
+    let positions: Vec<[f32; 3]> = (0..8)
+        .map(|corner| {
+            let coordinate = |bit| if corner & bit == 0 { -1.0 } else { 1.0 };
+            [coordinate(1), coordinate(2), coordinate(4)]
+        })
+        .collect();
+    #[rustfmt::skip]
+    let indices = vec![
+        0, 2, 3, 0, 3, 1, // -Z
+        4, 5, 7, 4, 7, 6, // +Z
+        0, 1, 5, 0, 5, 4, // -Y
+        2, 6, 7, 2, 7, 3, // +Y
+        0, 4, 6, 0, 6, 2, // -X
+        1, 3, 7, 1, 7, 5, // +X
+    ];
+    PrimitiveData {
+        material_index: None,
+        texcoords: vec![[0.0; 2]; positions.len()],
+        texcoords_1: vec![[0.0; 2]; positions.len()],
+        positions,
+        normals: Vec::new(),
+        tangents: Vec::new(),
+        indices: Some(indices),
+    }
+}
+
+fn load_icosphere(path: &str, normal_mode: NormalMode) -> PrimitiveData {
+    let (gltf, buffers, _) = gltf::import(common::manifest_path(path)).unwrap();
+    Mesh::load_primitives(&gltf, &buffers, normal_mode)
+        .unwrap()
+        .remove(0)
+        .remove(0)
+}
+
+fn triangle_normals(
+    data: &PrimitiveData,
+) -> impl Iterator<Item = ([glm::Vec3; 3], [glm::Vec3; 3])> + '_ {
+    data.indices
+        .as_ref()
+        .unwrap()
+        .chunks_exact(3)
+        .map(|triangle| {
+            let corners = [0, 1, 2].map(|corner| triangle[corner] as usize);
+            (
+                corners.map(|vertex| data.positions[vertex].into()),
+                corners.map(|vertex| data.normals[vertex].into()),
+            )
+        })
+}
+
+fn assert_flat(data: &PrimitiveData) {
+    for (positions, normals) in triangle_normals(data) {
+        let face_normal = (positions[1] - positions[0])
+            .cross(&(positions[2] - positions[0]))
+            .normalize();
+        for normal in normals {
+            assert!((normal - face_normal).norm() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn flat_cube_has_a_normal_per_face() {
+    let mut data = cube();
+    normals::generate(&mut data, NormalMode::Flat);
+    assert_eq!(data.positions.len(), 24);
+    assert_eq!(data.indices.as_ref().unwrap().len(), 36);
+    assert_flat(&data);
+    for normal in &data.normals {
+        assert_eq!(normal.iter().map(|c| c.abs()).sum::<f32>(), 1.0);
+    }
+}
+
+#[test]
+fn cube_edges_sharper_than_crease_angle_stay_hard() {
+    let mut flat = cube();
+    normals::generate(&mut flat, NormalMode::Flat);
+    let mut smooth = cube();
+    normals::generate(
+        &mut smooth,
+        NormalMode::Smooth {
+            crease_angle: 80f32.to_radians(),
+        },
+    );
+    assert_eq!(flat, smooth);
+}
+
+#[test]
+fn smooth_cube_normals_point_out_of_corners() {
+    let mut data = cube();
+    normals::generate(
+        &mut data,
+        NormalMode::Smooth {
+            crease_angle: 100f32.to_radians(),
+        },
+    );
+    assert_eq!(data.positions.len(), 8);
+    for (position, normal) in data.positions.iter().zip(&data.normals) {
+        let expected = glm::Vec3::from(*position).normalize();
+        assert!((glm::Vec3::from(*normal) - expected).norm() < 1e-5);
+    }
+}
+
+// The bundled icosphere is authored with flat shading
+#[test]
+fn flat_icosphere_matches_authored_normals() {
+    let authored = load_icosphere("res/icosphere.gltf", NormalMode::default());
+    let generated = load_icosphere("tests/fixtures/icosphere_no_normals.gltf", NormalMode::Flat);
+    assert_flat(&generated);
+    for (position, normal) in generated.positions.iter().zip(&generated.normals) {
+        assert!(authored.positions.iter().zip(&authored.normals).any(
+            |(authored_position, authored_normal)| {
+                let normal = glm::Vec3::from(*normal);
+                authored_position == position
+                    && normal.dot(&glm::Vec3::from(*authored_normal)) > 0.9999
+            }
+        ));
+    }
+}
+
+#[test]
+fn smooth_icosphere_normals_are_radial() {
+    let data = load_icosphere(
+        "tests/fixtures/icosphere_no_normals.gltf",
+        NormalMode::Smooth {
+            crease_angle: 60f32.to_radians(),
+        },
+    );
+    for (position, normal) in data.positions.iter().zip(&data.normals) {
+        let radial = glm::Vec3::from(*position).normalize();
+        assert!(glm::Vec3::from(*normal).dot(&radial) > 0.999);
+    }
+}
+
+// Scanned meshes often have neither normals nor UVs
+#[test]
+fn position_only_primitives_load() {
+    let data = load_icosphere(
+        "tests/fixtures/icosphere_positions_only.gltf",
+        NormalMode::default(),
+    );
+    assert_flat(&data);
+    assert!(data
+        .texcoords
+        .iter()
+        .all(|&texcoords| texcoords == [0.0; 2]));
+    assert_eq!(data.tangents.len(), data.positions.len());
+    for (tangent, normal) in data.tangents.iter().zip(&data.normals) {
+        let tangent = glm::vec3(tangent[0], tangent[1], tangent[2]);
+        assert!((tangent.norm() - 1.0).abs() < 1e-5);
+        assert!(tangent.dot(&glm::Vec3::from(*normal)).abs() < 1e-5);
+    }
+}
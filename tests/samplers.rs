@@ -0,0 +1,92 @@
+mod common;
+
+use std::sync::Arc;
+
+use rust_renderer::mesh::Mesh;
+use rust_renderer::sampler::{gltf_mipmaps, SamplerCache, SamplerKey};
+use rust_renderer::texture::Mipmaps;
+
+#[test]
+fn keys_follow_gltf_samplers() {
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "samplers": [
+            { "wrapS": 33648, "wrapT": 33071, "magFilter": 9728, "minFilter": 9729 },
+            { "minFilter": 9986 },
+            {}
+        ]
+    }"#;
+    let (gltf, _, _) = gltf::import_slice(json.as_bytes()).unwrap();
+    let samplers: Vec<_> = gltf.samplers().collect();
+
+    assert_eq!(
+        SamplerKey::from_gltf(&samplers[0]),
+        SamplerKey {
+            address_mode_u: wgpu::AddressMode::MirrorRepeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+        }
+    );
+    assert_eq!(gltf_mipmaps(&samplers[0]), Mipmaps::Skip);
+
+    // NEAREST_MIPMAP_LINEAR
+    assert_eq!(
+        SamplerKey::from_gltf(&samplers[1]),
+        SamplerKey {
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }
+    );
+    assert_eq!(gltf_mipmaps(&samplers[1]), Mipmaps::Generate);
+
+    assert_eq!(SamplerKey::from_gltf(&samplers[2]), SamplerKey::default());
+    assert_eq!(gltf_mipmaps(&samplers[2]), Mipmaps::Generate);
+}
+
+#[test]
+fn cache_shares_identical_samplers() {
+    let renderer_state = common::headless_renderer();
+    let device = &renderer_state.device;
+    let mut cache = SamplerCache::default();
+    let repeat = cache.get(device, SamplerKey::default());
+    let clamp_key = SamplerKey {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        ..Default::default()
+    };
+    let clamp = cache.get(device, clamp_key);
+
+    assert!(Arc::ptr_eq(
+        &repeat,
+        &cache.get(device, SamplerKey::default())
+    ));
+    assert!(!Arc::ptr_eq(&repeat, &clamp));
+    assert_eq!(cache.len(), 2);
+}
+
+// The avocado's textures don't name a sampler, so every map of its material
+// and of the default material share glTF's default sampler
+#[test]
+fn materials_share_samplers_across_a_mesh() {
+    let renderer_state = common::headless_renderer();
+    let mesh = Mesh::from_gltf(
+        &common::manifest_path("tests/fixtures/avocado/avocado_embedded.gltf"),
+        &renderer_state.device,
+        &renderer_state.queue,
+    )
+    .unwrap();
+    let sampler = &mesh.materials[0].albedo_sampler;
+    for material in &mesh.materials {
+        for other in [
+            &material.albedo_sampler,
+            &material.normal_sampler,
+            &material.roughness_metalness_sampler,
+            &material.occlusion_sampler,
+            &material.emissive_sampler,
+        ] {
+            assert!(Arc::ptr_eq(sampler, other));
+        }
+    }
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use rust_renderer::scene::Scene;
+use rust_renderer::Error;
+
+extern crate nalgebra_glm as glm;
+
+fn load_scene(path: &str) -> Scene {
+    let gltf = gltf::Gltf::open(common::manifest_path(path)).unwrap();
+    Scene::from_gltf(&gltf.document).unwrap()
+}
+
+fn world_origin(scene: &Scene, name: &str) -> glm::Vec3 {
+    let node = scene
+        .nodes
+        .iter()
+        .find(|node| node.name.as_deref() == Some(name))
+        .unwrap();
+    (node.world_matrix() * glm::Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz()
+}
+
+#[test]
+fn world_matrices_compose_parent_transforms() {
+    let scene = load_scene("tests/fixtures/node_hierarchy.gltf");
+
+    assert!(glm::distance(&world_origin(&scene, "Root"), &glm::vec3(0.0, 0.5, 0.0)) < 1e-5);
+    assert!(glm::distance(&world_origin(&scene, "Left"), &glm::vec3(-1.0, 0.5, 0.0)) < 1e-5);
+    assert!(glm::distance(&world_origin(&scene, "Right"), &glm::vec3(1.0, 0.5, 0.0)) < 1e-5);
+
+    // Leaf is offset along its parent's rotated -Y axis
+    let half_sqrt_2 = std::f32::consts::FRAC_1_SQRT_2;
+    let expected = glm::vec3(1.0 + half_sqrt_2, 0.5 - half_sqrt_2, 0.0);
+    assert!(glm::distance(&world_origin(&scene, "Leaf"), &expected) < 1e-5);
+}
+
+#[test]
+fn only_nodes_with_meshes_are_instanced() {
+    let scene = load_scene("tests/fixtures/node_hierarchy.gltf");
+    let names: Vec<_> = scene
+        .mesh_nodes()
+        .into_iter()
+        .map(|index| scene.nodes[index].name.clone().unwrap())
+        .collect();
+    assert_eq!(names, ["Left", "Right", "Leaf"]);
+    assert_eq!(scene.instances().len(), 3);
+}
+
+#[test]
+fn moving_a_parent_moves_its_children() {
+    let mut scene = load_scene("tests/fixtures/node_hierarchy.gltf");
+    let before = world_origin(&scene, "Leaf");
+    scene.nodes[0].translation += glm::vec3(1.0, 0.0, 0.0);
+    scene.update_world_matrices().unwrap();
+    let after = world_origin(&scene, "Leaf");
+    assert!(glm::distance(&(after - before), &glm::vec3(1.0, 0.0, 0.0)) < 1e-5);
+}
+
+#[test]
+fn cyclic_node_graphs_are_rejected() {
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [
+            { "name": "A", "children": [1] },
+            { "name": "B", "children": [2] },
+            { "name": "C", "children": [1] }
+        ]
+    }"#;
+    let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+    assert!(matches!(
+        Scene::from_gltf(&gltf.document),
+        Err(Error::InvalidNodeHierarchy { node: 1 })
+    ));
+}
+
+#[test]
+fn cycles_added_after_loading_are_rejected() {
+    let mut scene = load_scene("tests/fixtures/node_hierarchy.gltf");
+    scene.nodes[3].children.push(0);
+    assert!(matches!(
+        scene.update_world_matrices(),
+        Err(Error::InvalidNodeHierarchy { node: 0 })
+    ));
+    assert_eq!(scene.mesh_nodes().len(), 3);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use rust_renderer::screenshot;
+use rust_renderer::texture::Texture;
+use rust_renderer::Error;
+
+// A depth texture cleared to `depth`
+fn cleared_depth_texture(device: &wgpu::Device, queue: &wgpu::Queue, depth: f32) -> Texture {
+    let texture = Texture::create_depth_texture(device, 4, 4);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Clear Depth Encoder"),
+    });
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Clear Depth Pass"),
+        color_attachments: &[],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: &texture.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(depth),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+    });
+    queue.submit(std::iter::once(encoder.finish()));
+    texture
+}
+
+fn output_path(name: &str) -> std::path::PathBuf {
+    let directory = common::manifest_path("target/screenshot");
+    std::fs::create_dir_all(&directory).unwrap();
+    directory.join(name)
+}
+
+#[test]
+fn depth_reads_back() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let texture = cleared_depth_texture(device, queue, 0.25);
+    let image = screenshot::read_depth_image(device, queue, &texture.texture).unwrap();
+    assert!(image.pixels().all(|pixel| pixel[0] == 0.25));
+}
+
+#[test]
+fn depth_readback_needs_a_depth_texture() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let texture = Texture::create_render_target(
+        device,
+        4,
+        4,
+        wgpu::TextureFormat::Rgba8Unorm,
+        "Color Texture",
+    );
+    assert!(matches!(
+        screenshot::read_depth_image(device, queue, &texture.texture),
+        Err(Error::UnsupportedFormat(_))
+    ));
+}
+
+#[test]
+fn depth_saves_to_png_as_16_bit_luma() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let texture = cleared_depth_texture(device, queue, 0.5);
+    let image = screenshot::read_depth_image(device, queue, &texture.texture).unwrap();
+
+    let path = output_path("depth.png");
+    screenshot::save_depth_image(&image, &path).unwrap();
+    let saved = image::open(&path).unwrap();
+    assert_eq!(saved.color(), image::ColorType::L16);
+    assert!(saved.to_luma16().pixels().all(|pixel| pixel[0] == 32768));
+}
+
+#[test]
+fn depth_is_not_saved_to_8_bit_formats() {
+    let image = screenshot::DepthImage::new(4, 4);
+    assert!(matches!(
+        screenshot::save_depth_image(&image, &output_path("depth.jpg")),
+        Err(Error::UnsupportedFormat(_))
+    ));
+}
@@ -0,0 +1,292 @@
+mod common;
+
+use rust_renderer::camera::{Camera, CameraExtrinsics};
+use rust_renderer::constants::*;
+use rust_renderer::light::{self, Light};
+use rust_renderer::mesh::{Bounds, Mesh};
+use rust_renderer::shadow::{self, ShadowConfig, DEFAULT_SHADOW_MAP_SIZE};
+
+extern crate nalgebra_glm as glm;
+
+fn scene_bounds() -> Bounds {
+    Bounds {
+        min: glm::vec3(-20.0, -1.0, -20.0),
+        max: glm::vec3(20.0, 4.0, 20.0),
+    }
+}
+
+fn camera_at(position: glm::Vec3, yaw: f32) -> Camera {
+    let extrinsics = CameraExtrinsics {
+        position: position.push(1.0),
+        yaw,
+        pitch: -0.2,
+    };
+    Camera::new(extrinsics, DEFAULT_CAMERA_INTRINSICS)
+}
+
+fn sun_cascades(camera: &Camera) -> Vec<(glm::Mat4, f32)> {
+    let (sun, world_matrix) = light::default_light();
+    sun.shadow_projections(
+        &world_matrix,
+        &scene_bounds(),
+        camera,
+        DEFAULT_SHADOW_MAP_SIZE,
+    )
+}
+
+// Corners of the camera's view between `near` and `far`, in world space
+fn slice_corners(camera: &Camera, near: f32, far: f32) -> Vec<glm::Vec3> {
+    let inverse = glm::inverse(&(camera.proj_matrix * camera.view_matrix));
+    let to_ndc = |depth: f32| {
+        let clip = camera.proj_matrix * glm::vec4(0.0, 0.0, -depth, 1.0);
+        clip.z / clip.w
+    };
+    let mut corners = Vec::new();
+    for z in [to_ndc(near), to_ndc(far)] {
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            let point = inverse * glm::vec4(x, y, z, 1.0);
+            corners.push(point.xyz() / point.w);
+        }
+    }
+    corners
+}
+
+#[test]
+fn cascade_splits_divide_the_view_from_near_to_far() {
+    let splits = shadow::cascade_splits(&DEFAULT_CAMERA_INTRINSICS, 4);
+    assert_eq!(splits.len(), 4);
+    assert!(splits[0] > DEFAULT_CAMERA_INTRINSICS.near);
+    assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!((splits[3] - DEFAULT_CAMERA_INTRINSICS.far).abs() < 1e-3);
+    // Nearer cascades are thinner
+    assert!(splits[0] < splits[1] - splits[0]);
+}
+
+#[test]
+fn cascades_cover_their_slice_of_the_view() {
+    let camera = camera_at(glm::vec3(3.0, 1.0, 8.0), -2.0);
+    let cascades = sun_cascades(&camera);
+    assert_eq!(cascades.len(), 4);
+
+    let splits = shadow::cascade_splits(&camera.intrinsics, cascades.len());
+    let nears = std::iter::once(camera.intrinsics.near).chain(splits.iter().copied());
+    for ((near, far), (view_proj, _)) in nears.zip(splits.iter().copied()).zip(&cascades) {
+        for corner in slice_corners(&camera, near, far) {
+            let ndc = view_proj * corner.push(1.0);
+            assert!(ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0, "{:?}", ndc);
+        }
+    }
+    // Later cascades cover more
+    assert!(cascades.windows(2).all(|pair| pair[0].1 < pair[1].1));
+}
+
+#[test]
+fn cascades_cover_every_caster_in_depth() {
+    let bounds = scene_bounds();
+    let camera = camera_at(glm::vec3(0.0, 1.0, 0.0), 0.0);
+    for (view_proj, _) in sun_cascades(&camera) {
+        for point in [bounds.min, bounds.max, bounds.center()] {
+            let depth = (view_proj * point.push(1.0)).z;
+            assert!((0.0..=1.0).contains(&depth), "{}", depth);
+        }
+    }
+}
+
+#[test]
+fn turning_the_camera_keeps_cascade_sizes() {
+    let position = glm::vec3(1.0, 1.5, 2.0);
+    let before = sun_cascades(&camera_at(position, 0.3));
+    let after = sun_cascades(&camera_at(position, 2.1));
+    for ((_, before), (_, after)) in before.iter().zip(&after) {
+        assert_eq!(before, after);
+    }
+}
+
+#[test]
+fn moving_the_camera_shifts_cascades_by_whole_texels() {
+    let before = sun_cascades(&camera_at(glm::vec3(1.0, 1.5, 2.0), 0.3));
+    let after = sun_cascades(&camera_at(glm::vec3(1.137, 1.52, 1.911), 0.3));
+    let point = glm::vec4(0.4, 0.2, -0.7, 1.0);
+    for ((before, _), (after, _)) in before.iter().zip(&after) {
+        let shift = (after * point - before * point).xy() * DEFAULT_SHADOW_MAP_SIZE as f32 / 2.0;
+        for texels in shift.iter() {
+            assert!((texels - texels.round()).abs() < 0.02, "{:?}", shift);
+        }
+    }
+}
+
+#[test]
+fn cascade_count_is_configurable() {
+    let (mut sun, world_matrix) = light::default_light();
+    let camera = camera_at(glm::vec3(0.0, 1.0, 0.0), 0.0);
+    let cascades = |sun: &Light| {
+        sun.shadow_projections(
+            &world_matrix,
+            &scene_bounds(),
+            &camera,
+            DEFAULT_SHADOW_MAP_SIZE,
+        )
+        .len()
+    };
+    sun.shadow.as_mut().unwrap().cascade_count = 2;
+    assert_eq!(cascades(&sun), 2);
+    // Clamped to the cascades the shader can read
+    sun.shadow.as_mut().unwrap().cascade_count = 9;
+    assert_eq!(cascades(&sun), shadow::MAX_CASCADES);
+}
+
+// The face a direction selects in a cube map, and where it lands on the face
+// in texture coordinates, as the Vulkan specification defines them
+fn cube_face_coords(direction: glm::Vec3) -> (usize, glm::Vec2) {
+    let [x, y, z] = [direction.x, direction.y, direction.z];
+    let (face, major, s, t) = if x.abs() >= y.abs() && x.abs() >= z.abs() {
+        if x > 0.0 {
+            (0, x, -z, -y)
+        } else {
+            (1, x, z, -y)
+        }
+    } else if y.abs() >= z.abs() {
+        if y > 0.0 {
+            (2, y, x, z)
+        } else {
+            (3, y, x, -z)
+        }
+    } else if z > 0.0 {
+        (4, z, x, -y)
+    } else {
+        (5, z, -x, -y)
+    };
+    let coords = glm::vec2(s, t) / major.abs();
+    (face, (coords + glm::vec2(1.0, 1.0)) / 2.0)
+}
+
+#[test]
+fn cube_faces_render_where_cube_lookups_read() {
+    let position = glm::vec3(0.5, -1.0, 2.0);
+    let view_projs = shadow::cube_face_view_projs(&position, 10.0);
+    let directions = [
+        glm::vec3(1.0, 0.2, -0.3),
+        glm::vec3(-1.0, -0.4, 0.1),
+        glm::vec3(0.3, 1.0, 0.6),
+        glm::vec3(-0.2, -1.0, 0.5),
+        glm::vec3(0.4, -0.7, 1.0),
+        glm::vec3(-0.6, 0.1, -1.0),
+    ];
+    for direction in directions {
+        let (face, expected) = cube_face_coords(direction);
+        let clip = view_projs[face] * (position + direction).push(1.0);
+        let ndc = clip.xyz() / clip.w;
+        // Texture rows run down
+        let coords = glm::vec2(ndc.x + 1.0, 1.0 - ndc.y) / 2.0;
+        assert!(
+            glm::distance(&coords, &expected) < 1e-5,
+            "face {}: {:?} != {:?}",
+            face,
+            coords,
+            expected
+        );
+        assert!((0.0..=1.0).contains(&ndc.z));
+    }
+}
+
+fn load_point_shadows() -> Mesh {
+    let renderer_state = common::headless_renderer();
+    Mesh::from_gltf(
+        &common::manifest_path("tests/fixtures/point_shadows.gltf"),
+        &renderer_state.device,
+        &renderer_state.queue,
+    )
+    .unwrap()
+}
+
+#[test]
+fn point_shadows_go_to_the_lights_nearest_the_camera() {
+    let mesh = load_point_shadows();
+    let camera = camera_at(glm::vec3(0.0, 1.0, 6.0), -std::f32::consts::FRAC_PI_2);
+    let shadow_layers = |max_point_shadows: u32| {
+        let config = ShadowConfig {
+            max_point_shadows,
+            ..Default::default()
+        };
+        let lights = mesh.light_uniforms(&camera, &config);
+        assert_eq!(shadow::cube_count(&lights), max_point_shadows.min(3));
+        assert_eq!(shadow::layer_count(&lights), 0);
+        lights
+            .iter()
+            .map(|light| light.shadow_layer)
+            .collect::<Vec<_>>()
+    };
+    // Lamp, Back Lamp, Far Lamp
+    assert_eq!(shadow_layers(0), [-1, -1, -1]);
+    assert_eq!(shadow_layers(1), [0, -1, -1]);
+    assert_eq!(shadow_layers(2), [0, 1, -1]);
+    assert_eq!(shadow_layers(8), [0, 1, 2]);
+
+    // Walking to the back of the room brings the far lamp within budget
+    let camera = camera_at(glm::vec3(-2.0, 1.0, -1.5), 0.0);
+    let lights = mesh.light_uniforms(
+        &camera,
+        &ShadowConfig {
+            max_point_shadows: 1,
+            ..Default::default()
+        },
+    );
+    assert_eq!(lights[2].shadow_layer, 0);
+
+    // Cubes are handed out nearest first, not in scene order
+    let lights = mesh.light_uniforms(&camera, &ShadowConfig::default());
+    assert_eq!(lights[2].shadow_layer, 0);
+}
+
+#[test]
+fn point_shadows_reach_past_the_scene_without_a_range() {
+    let mesh = load_point_shadows();
+    let camera = camera_at(glm::vec3(0.0, 1.0, 6.0), -std::f32::consts::FRAC_PI_2);
+    let bounds = mesh.world_bounds().unwrap();
+    let lights = mesh.light_uniforms(&camera, &ShadowConfig::default());
+    // The lamp's range limits its shadow
+    assert_eq!(lights[0].shadow_far, 8.0);
+    for light in &lights[1..] {
+        let corners = [bounds.min, bounds.max];
+        for corner in corners {
+            let distance = glm::distance(&glm::Vec3::from(light.position), &corner);
+            assert!(distance <= light.shadow_far, "{}", distance);
+        }
+    }
+}
+
+// Square arrays with a multiple of six layers would be created as cube maps
+// on GL, which can't be sampled as arrays
+#[test]
+fn shadow_arrays_are_never_mistaken_for_cubes() {
+    let renderer_state = common::headless_renderer();
+    let layout = rust_renderer::bind_groups::camera_bind_group::create_bind_group_layout(
+        &renderer_state.device,
+    );
+    let config = ShadowConfig {
+        map_size: 16,
+        cube_size: 16,
+        ..Default::default()
+    };
+    for (layer_count, cube_count) in [(0, 0), (6, 1), (12, 2), (5, 3)] {
+        let shadow_maps = shadow::ShadowMaps::new(
+            &renderer_state.device,
+            &layout,
+            &config,
+            layer_count,
+            cube_count,
+        );
+        let layers = shadow_maps.texture.texture.depth_or_array_layers();
+        let faces = shadow_maps.cube_texture.texture.depth_or_array_layers();
+        assert!(
+            layers >= layer_count.max(1) && !layers.is_multiple_of(6),
+            "{}",
+            layers
+        );
+        assert!(
+            faces >= 6 * cube_count.max(1) && !faces.is_multiple_of(6),
+            "{}",
+            faces
+        );
+    }
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use rust_renderer::mesh::{Mesh, PrimitiveData};
+use rust_renderer::normals::NormalMode;
+use rust_renderer::tangents;
+
+extern crate nalgebra_glm as glm;
+
+// Unit quad in the XY plane facing +Z, with u along +X and v along -Y
+fn quad(indices: Option<Vec<u32>>) -> PrimitiveData {
+    PrimitiveData {
+        material_index: None,
+        positions: vec![
+            [-1.0, -1.0, 0.0],
+            [1.0, -1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [-1.0, 1.0, 0.0],
+        ],
+        normals: vec![[0.0, 0.0, 1.0]; 4],
+        tangents: Vec::new(),
+        texcoords: vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+        texcoords_1: Vec::new(),
+        indices,
+    }
+}
+
+fn assert_tangents_near(data: &PrimitiveData, expected: [f32; 4]) {
+    for tangent in &data.tangents {
+        let difference = glm::Vec4::from(*tangent) - glm::Vec4::from(expected);
+        assert!(difference.norm() < 1e-5, "{:?} != {:?}", tangent, expected);
+    }
+}
+
+#[test]
+fn quad_tangents_follow_u() {
+    let mut data = quad(Some(vec![0, 1, 2, 0, 2, 3]));
+    tangents::generate(&mut data);
+    assert_eq!(data.positions.len(), 4);
+    assert_eq!(data.indices, Some(vec![0, 1, 2, 0, 2, 3]));
+    assert_tangents_near(&data, [1.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn mirrored_uvs_flip_bitangent_sign() {
+    let mut data = quad(Some(vec![0, 1, 2, 0, 2, 3]));
+    for texcoord in &mut data.texcoords {
+        texcoord[0] = 1.0 - texcoord[0];
+    }
+    tangents::generate(&mut data);
+    assert_tangents_near(&data, [-1.0, 0.0, 0.0, -1.0]);
+}
+
+#[test]
+fn unindexed_corners_are_welded() {
+    let mut data = quad(None);
+    let corners = [0, 1, 2, 0, 2, 3];
+    data.positions = corners.iter().map(|&i| data.positions[i]).collect();
+    data.normals = corners.iter().map(|&i| data.normals[i]).collect();
+    data.texcoords = corners.iter().map(|&i| data.texcoords[i]).collect();
+    tangents::generate(&mut data);
+    assert_eq!(data.positions.len(), 4);
+    assert_eq!(data.indices.as_ref().map(Vec::len), Some(6));
+    assert_tangents_near(&data, [1.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn generated_tangents_agree_with_authored() {
+    let load = |path: &str| {
+        let (gltf, buffers, _) = gltf::import(common::manifest_path(path)).unwrap();
+        Mesh::load_primitives(&gltf, &buffers, NormalMode::default())
+            .unwrap()
+            .remove(0)
+            .remove(0)
+    };
+    let authored = load("res/avocado/avocado.gltf");
+    let generated = load("tests/fixtures/avocado/avocado_no_tangents.gltf");
+
+    // Match each generated vertex back to the authored vertex it came from
+    let authored_index: std::collections::HashMap<_, _> = authored
+        .positions
+        .iter()
+        .zip(&authored.texcoords)
+        .enumerate()
+        .map(|(index, (position, texcoord))| {
+            (
+                (position.map(f32::to_bits), texcoord.map(f32::to_bits)),
+                index,
+            )
+        })
+        .collect();
+    // A few corners touch triangles with degenerate UVs, where the sign of the
+    // bitangent is arbitrary
+    let agreeing = (0..generated.tangents.len())
+        .filter(|&vertex| {
+            let key = (
+                generated.positions[vertex].map(f32::to_bits),
+                generated.texcoords[vertex].map(f32::to_bits),
+            );
+            let tangent = glm::Vec4::from(generated.tangents[vertex]);
+            let expected = glm::Vec4::from(authored.tangents[authored_index[&key]]);
+            tangent.w == expected.w && tangent.xyz().dot(&expected.xyz()) > 0.99
+        })
+        .count();
+    assert!(
+        agreeing as f32 >= 0.99 * generated.tangents.len() as f32,
+        "{} of {} tangents agree",
+        agreeing,
+        generated.tangents.len()
+    );
+}
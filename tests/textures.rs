@@ -0,0 +1,310 @@
+mod common;
+
+use gltf::image::{Data, Format};
+use rust_renderer::camera::CameraExtrinsics;
+use rust_renderer::constants::*;
+use rust_renderer::texture::{
+    box_filter, convert_gltf_image, mip_level_count, ColorSpace, Mipmaps, Texture,
+};
+
+fn image(format: Format, pixels: Vec<u8>) -> Data {
+    Data {
+        pixels,
+        format,
+        width: 1,
+        height: 1,
+    }
+}
+
+fn halves(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(2)
+        .map(|half| half::f16::from_le_bytes([half[0], half[1]]).to_f32())
+        .collect()
+}
+
+// The quad faces the camera with a +X tangent and no material, so it gets the
+// default flat normal map and glTF's default rough white metal. Looking
+// straight down +Z, the key light from (1, 2, 2) / 3 reflects
+// 3 * 2/3 * F * D * V = 3 * 2/3 * 1 * 1/pi * 0.3 of its intensity, on top of
+// the ambient 0.1, which only holds with the normal along +Z.
+#[test]
+fn flat_normal_map_decodes_to_positive_z() {
+    let extrinsics = CameraExtrinsics {
+        position: nalgebra_glm::Vec4::new(0.0, 0.0, 3.0, 1.0),
+        yaw: -std::f32::consts::FRAC_PI_2,
+        pitch: 0.0,
+    };
+    let intrinsics = rust_renderer::camera::CameraIntrinsics {
+        aspect: 1.0,
+        ..DEFAULT_CAMERA_INTRINSICS
+    };
+    let image = common::render_gltf("tests/fixtures/quantized_quad.gltf", extrinsics, intrinsics);
+
+    // The render target is sRGB, so undo its encoding to recover radiance
+    let pixel = image.get_pixel(common::WIDTH / 2, common::HEIGHT / 2);
+    let radiance = [0, 1, 2].map(|channel| {
+        let encoded = pixel[channel] as f32 / 255.0;
+        match encoded {
+            encoded if encoded <= 0.04045 => encoded / 12.92,
+            encoded => ((encoded + 0.055) / 1.055).powf(2.4),
+        }
+    });
+    let expected = 0.6 / std::f32::consts::PI + 0.1;
+    for component in radiance {
+        assert!(
+            (component - expected).abs() < 0.01,
+            "radiance {:?}, expected {}",
+            radiance,
+            expected
+        );
+    }
+}
+
+#[test]
+fn grey_images_spread_over_rgb() {
+    let (bytes, format) = convert_gltf_image(
+        &image(Format::R8, vec![40]),
+        ColorSpace::Srgb,
+        wgpu::Features::empty(),
+    );
+    assert_eq!(
+        (bytes, format),
+        (vec![40, 40, 40, 255], wgpu::TextureFormat::Rgba8UnormSrgb)
+    );
+
+    let (bytes, format) = convert_gltf_image(
+        &image(Format::R8G8, vec![40, 90]),
+        ColorSpace::Linear,
+        wgpu::Features::empty(),
+    );
+    assert_eq!(
+        (bytes, format),
+        (vec![40, 40, 40, 90], wgpu::TextureFormat::Rgba8Unorm)
+    );
+}
+
+#[test]
+fn rgb_images_gain_opaque_alpha() {
+    let (bytes, _) = convert_gltf_image(
+        &image(Format::R8G8B8, vec![1, 2, 3]),
+        ColorSpace::Srgb,
+        wgpu::Features::empty(),
+    );
+    assert_eq!(bytes, vec![1, 2, 3, 255]);
+}
+
+#[test]
+fn sixteen_bit_images_keep_their_precision() {
+    let height = 40_001u16;
+    let (bytes, format) = convert_gltf_image(
+        &image(Format::R16, height.to_ne_bytes().to_vec()),
+        ColorSpace::Linear,
+        wgpu::Features::TEXTURE_FORMAT_16BIT_NORM,
+    );
+    assert_eq!(format, wgpu::TextureFormat::Rgba16Unorm);
+    assert_eq!(bytes[0..2], height.to_le_bytes());
+    assert_eq!(bytes[6..8], u16::MAX.to_le_bytes());
+
+    // Without 16-bit normalized textures the value survives as a half float
+    let (bytes, format) = convert_gltf_image(
+        &image(Format::R16, height.to_ne_bytes().to_vec()),
+        ColorSpace::Linear,
+        wgpu::Features::empty(),
+    );
+    assert_eq!(format, wgpu::TextureFormat::Rgba16Float);
+    let expected = height as f32 / u16::MAX as f32;
+    assert!(halves(&bytes)[..3]
+        .iter()
+        .all(|value| (value - expected).abs() < 1e-3));
+}
+
+#[test]
+fn sixteen_bit_srgb_images_are_decoded_to_linear() {
+    let pixels = [u16::MAX / 2, 0, u16::MAX, u16::MAX / 2]
+        .iter()
+        .flat_map(|sample| sample.to_ne_bytes())
+        .collect();
+    let (bytes, format) = convert_gltf_image(
+        &image(Format::R16G16B16A16, pixels),
+        ColorSpace::Srgb,
+        wgpu::Features::TEXTURE_FORMAT_16BIT_NORM,
+    );
+    assert_eq!(format, wgpu::TextureFormat::Rgba16Float);
+    let values = halves(&bytes);
+    assert!((values[0] - 0.214).abs() < 1e-3);
+    assert_eq!(values[1..3], [0.0, 1.0]);
+    // Alpha is always linear
+    assert!((values[3] - 0.5).abs() < 1e-3);
+}
+
+#[test]
+fn float_images_become_half_floats() {
+    let pixels = [2.5f32, 0.25, 1.0]
+        .iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect();
+    let (bytes, format) = convert_gltf_image(
+        &image(Format::R32G32B32FLOAT, pixels),
+        ColorSpace::Linear,
+        wgpu::Features::empty(),
+    );
+    assert_eq!(format, wgpu::TextureFormat::Rgba16Float);
+    assert_eq!(halves(&bytes), vec![2.5, 0.25, 1.0, 1.0]);
+}
+
+#[test]
+fn every_gltf_format_uploads() {
+    let renderer_state = common::headless_renderer();
+    let formats = [
+        (Format::R8, 1),
+        (Format::R8G8, 2),
+        (Format::R8G8B8, 3),
+        (Format::R8G8B8A8, 4),
+        (Format::R16, 2),
+        (Format::R16G16, 4),
+        (Format::R16G16B16, 6),
+        (Format::R16G16B16A16, 8),
+        (Format::R32G32B32FLOAT, 12),
+        (Format::R32G32B32A32FLOAT, 16),
+    ];
+    for (format, pixel_size) in formats {
+        for color_space in [ColorSpace::Srgb, ColorSpace::Linear] {
+            let data = Data {
+                pixels: vec![0; 3 * 2 * pixel_size],
+                format,
+                width: 3,
+                height: 2,
+            };
+            let texture = Texture::from_gltf_image(
+                &renderer_state.device,
+                &renderer_state.queue,
+                &data,
+                color_space,
+                Mipmaps::Generate,
+                "Converted Texture",
+            )
+            .unwrap();
+            assert_eq!(texture.texture.width(), 3);
+            assert_eq!(texture.texture.mip_level_count(), 2);
+        }
+    }
+}
+
+#[test]
+fn mip_chains_reach_one_texel() {
+    assert_eq!(mip_level_count((1, 1)), 1);
+    assert_eq!(mip_level_count((2048, 2048)), 12);
+    assert_eq!(mip_level_count((5, 3)), 3);
+    assert_eq!(mip_level_count((1, 300)), 9);
+}
+
+#[test]
+fn box_filter_averages_srgb_in_linear_space() {
+    let bytes = [[0, 0, 0, 255], [255; 4], [255; 4], [0, 0, 0, 255]].concat();
+    let linear = box_filter(&bytes, (2, 2), wgpu::TextureFormat::Rgba8Unorm).unwrap();
+    assert_eq!(linear, [128, 128, 128, 255]);
+    let srgb = box_filter(&bytes, (2, 2), wgpu::TextureFormat::Rgba8UnormSrgb).unwrap();
+    assert_eq!(srgb, [188, 188, 188, 255]);
+}
+
+#[test]
+fn box_filter_repeats_odd_edges() {
+    let texels: Vec<f32> = (0..3).flat_map(|x| [x as f32; 4]).collect();
+    let bytes: Vec<u8> = texels
+        .iter()
+        .flat_map(|&value| half::f16::from_f32(value).to_le_bytes())
+        .collect();
+    let next = box_filter(&bytes, (3, 1), wgpu::TextureFormat::Rgba16Float).unwrap();
+    assert_eq!(halves(&next), [0.5; 4]);
+}
+
+// The GPU's bilinear tap in the middle of each 2x2 block should average the
+// same texels as the CPU's box filter
+#[test]
+fn gpu_mipmaps_match_box_filter() {
+    let renderer_state = common::headless_renderer();
+    let (device, queue) = (&renderer_state.device, &renderer_state.queue);
+    let bytes = common::random_bytes(4 * 16 * 16, 15);
+    for color_space in [ColorSpace::Srgb, ColorSpace::Linear] {
+        let texture = Texture::from_bytes(
+            device,
+            queue,
+            &bytes,
+            (16, 16),
+            color_space,
+            Mipmaps::Generate,
+            "Mipmapped Texture",
+        )
+        .unwrap();
+        let format = texture.texture.format();
+        let gpu = common::decode_level_on_gpu(device, queue, &texture.texture, 1, format);
+        let cpu = box_filter(&bytes, (16, 16), format).unwrap();
+        assert_eq!(gpu.len(), cpu.len());
+        for (gpu, cpu) in gpu.iter().zip(&cpu) {
+            assert!(
+                gpu.abs_diff(*cpu) <= 1,
+                "{:?}: {} != {}",
+                color_space,
+                gpu,
+                cpu
+            );
+        }
+    }
+}
+
+#[test]
+fn box_filter_keeps_signed_values() {
+    let texels = [
+        [-127i8, 0, 127, 127],
+        [-127, 0, 127, 127],
+        [127, 0, -127, 127],
+        [127; 4],
+    ];
+    let bytes: Vec<u8> = texels.concat().iter().map(|&value| value as u8).collect();
+    let next = box_filter(&bytes, (2, 2), wgpu::TextureFormat::Rgba8Snorm).unwrap();
+    let next: Vec<i8> = next.iter().map(|&value| value as i8).collect();
+    assert_eq!(next, [0, 32, 64, 127]);
+}
+
+#[test]
+fn box_filter_rejects_unsupported_formats() {
+    assert!(box_filter(&[0; 4], (1, 1), wgpu::TextureFormat::R32Float).is_err());
+}
+
+#[test]
+fn textures_can_opt_out_of_mipmaps() {
+    let renderer_state = common::headless_renderer();
+    let texture = Texture::from_bytes(
+        &renderer_state.device,
+        &renderer_state.queue,
+        &[255; 4 * 16 * 16],
+        (16, 16),
+        ColorSpace::Srgb,
+        Mipmaps::Skip,
+        "Single Level Texture",
+    )
+    .unwrap();
+    assert_eq!(texture.texture.mip_level_count(), 1);
+}
+
+// Rgba16Unorm can't be rendered to everywhere, so its chain is box filtered
+#[test]
+fn unrenderable_formats_get_cpu_mipmaps() {
+    let renderer_state = common::headless_renderer();
+    let features = renderer_state.device.features();
+    if !features.contains(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM) {
+        return;
+    }
+    let texture = Texture::from_texels(
+        &renderer_state.device,
+        &renderer_state.queue,
+        &[0; 8 * 8 * 4],
+        (8, 4),
+        wgpu::TextureFormat::Rgba16Unorm,
+        Mipmaps::Generate,
+        "Box Filtered Texture",
+    )
+    .unwrap();
+    assert_eq!(texture.texture.mip_level_count(), 4);
+}